@@ -0,0 +1,50 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// Compare reading and writing through the bit specification with
+// the compiled plan.
+fn access(c: &mut Criterion) {
+    let bit_spec = bit_lang::parse("1[4..7]..3[0..3];16").unwrap();
+    let compiled = bit_spec.compile(8).unwrap();
+    let mut data = vec![0xA5u8; bit_spec.max_size() + 1];
+
+    c.bench_function("bit_spec extract", |b| {
+        b.iter(|| {
+            (0..16)
+                .map(|element| bit_spec.extract(black_box(&data), 8, element).unwrap())
+                .sum::<u64>()
+        })
+    });
+
+    c.bench_function("compiled extract", |b| {
+        b.iter(|| {
+            (0..16)
+                .map(|element| compiled.extract(black_box(&data), element).unwrap())
+                .sum::<u64>()
+        })
+    });
+
+    c.bench_function("bit_spec insert", |b| {
+        b.iter(|| {
+            for element in 0..16 {
+                bit_spec
+                    .insert(black_box(&mut data), 8, element, 0x5A5A)
+                    .unwrap();
+            }
+        })
+    });
+
+    c.bench_function("compiled insert", |b| {
+        b.iter(|| {
+            for element in 0..16 {
+                compiled
+                    .insert(black_box(&mut data), element, 0x5A5A)
+                    .unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, access);
+criterion_main!(benches);
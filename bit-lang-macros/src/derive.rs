@@ -0,0 +1,443 @@
+//! Expansion of `#[derive(BitLayout)]`.
+
+use bit_lang::parser::{BitSpec, FieldType, Repeat};
+use bit_lang::CompiledSpec;
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Expr, Fields, Ident, Lit, LitInt, LitStr, Type};
+
+use crate::spec::{compiled_spec_tokens, parse_spec};
+
+// The Rust types that a field, or the elements of an array field, can have
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Scalar {
+    Bool,
+    Unsigned(usize),
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_type(ty: &Type) -> Option<Scalar> {
+        let Type::Path(path) = ty else {
+            return None;
+        };
+        let ident = path.path.get_ident()?.to_string();
+
+        match ident.as_str() {
+            "bool" => Some(Scalar::Bool),
+            "u8" => Some(Scalar::Unsigned(8)),
+            "u16" => Some(Scalar::Unsigned(16)),
+            "u32" => Some(Scalar::Unsigned(32)),
+            "u64" => Some(Scalar::Unsigned(64)),
+            "usize" => Some(Scalar::Unsigned(usize::BITS as usize)),
+            "f32" => Some(Scalar::F32),
+            "f64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    // The Rust type for the field type, as used by the generated Rust modules:
+    // half precision floats and enumerations are kept as their raw bits
+    fn expected(field_type: &FieldType) -> &'static str {
+        match field_type {
+            FieldType::Bool => "bool",
+            FieldType::F32 => "f32",
+            FieldType::Fixed { .. } => "f64",
+            FieldType::Unsigned | FieldType::F16 | FieldType::Enum(_) => "an unsigned integer",
+        }
+    }
+
+    fn holds(&self, field_type: &FieldType) -> bool {
+        matches!(
+            (self, field_type),
+            (Scalar::Bool, FieldType::Bool)
+                | (Scalar::F32, FieldType::F32)
+                | (Scalar::F64, FieldType::Fixed { .. })
+                | (
+                    Scalar::Unsigned(_),
+                    FieldType::Unsigned | FieldType::F16 | FieldType::Enum(_)
+                )
+        )
+    }
+
+    // Convert the raw bits to the Rust type
+    fn decode_tokens(self, ty: &Type, raw: TokenStream, field_type: &FieldType) -> TokenStream {
+        match (self, field_type) {
+            (
+                Scalar::F64,
+                FieldType::Fixed {
+                    integer_bits,
+                    fractional_bits,
+                },
+            ) => {
+                // Sign extend from the top bit of the field
+                let shift = 64 - (*integer_bits as u32 + *fractional_bits as u32);
+                let scale = (1u64 << fractional_bits) as f64;
+                quote! { ((#raw << #shift) as i64 >> #shift) as f64 / #scale }
+            }
+            (Scalar::Bool, _) => quote! { #raw != 0 },
+            (Scalar::F32, _) => quote! { f32::from_bits(#raw as u32) },
+            _ => quote! { #raw as #ty },
+        }
+    }
+
+    // Convert the Rust value to the raw bits, ignoring any bits
+    // that do not fit into the width
+    fn encode_tokens(
+        self,
+        value: TokenStream,
+        width: usize,
+        field_type: &FieldType,
+    ) -> TokenStream {
+        let mask = if width >= 64 {
+            u64::MAX
+        } else {
+            (1u64 << width) - 1
+        };
+
+        match (self, field_type) {
+            (
+                Scalar::F64,
+                FieldType::Fixed {
+                    fractional_bits, ..
+                },
+            ) => {
+                // Round half away from zero without the standard library
+                let scale = (1u64 << fractional_bits) as f64;
+                quote! {{
+                    let scaled = (#value) * #scale;
+                    let truncated = scaled as i64;
+                    let fraction = scaled - truncated as f64;
+                    let rounded = truncated + (fraction >= 0.5) as i64 - (fraction <= -0.5) as i64;
+                    (rounded as u64) & #mask
+                }}
+            }
+            (Scalar::Bool, _) => quote! { (#value) as u64 },
+            (Scalar::F32, _) => quote! { (#value).to_bits() as u64 },
+            _ => quote! { ((#value) as u64) & #mask },
+        }
+    }
+}
+
+struct LayoutField {
+    ident: Ident,
+    span: Span,
+    bit_spec: BitSpec,
+    scalar: Scalar,
+    // The Rust type of the field or the array elements
+    scalar_type: Type,
+    is_array: bool,
+    // The value of the bits of a literal field
+    literal: Option<u64>,
+}
+
+pub fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let word_bits = word_bits(input)?;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "BitLayout can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            input.span(),
+            "BitLayout needs a struct with named fields",
+        ));
+    };
+
+    let mut layout_fields: Vec<LayoutField> = Vec::new();
+    for field in &fields.named {
+        let layout_field = layout_field(field, word_bits)?;
+        check_overlaps(&layout_fields, &layout_field, word_bits)?;
+        layout_fields.push(layout_field);
+    }
+
+    let word_bytes = word_bits / 8;
+    let size = layout_fields
+        .iter()
+        .map(|field| (field.bit_spec.start.index + field.bit_spec.max_size()) * word_bytes)
+        .max()
+        .unwrap_or(0);
+
+    let decode_fields = layout_fields.iter().map(|field| {
+        let ident = &field.ident;
+        let spec = compiled_spec_tokens(&field.bit_spec, word_bits);
+        let raw = quote! { spec.extract(data, element).expect("buffer checked against SIZE") };
+        let field_type = &field.bit_spec.field_type;
+        let value = field
+            .scalar
+            .decode_tokens(&field.scalar_type, raw, field_type);
+        if field.is_array {
+            quote! {
+                #ident: {
+                    let spec = #spec;
+                    ::core::array::from_fn(|element| #value)
+                }
+            }
+        } else {
+            quote! {
+                #ident: {
+                    let spec = #spec;
+                    let element = 0;
+                    #value
+                }
+            }
+        }
+    });
+
+    let encode_fields = layout_fields.iter().map(|field| {
+        let ident = &field.ident;
+        let spec = compiled_spec_tokens(&field.bit_spec, word_bits);
+        let width = field.bit_spec.bit_width(word_bits);
+        let field_type = &field.bit_spec.field_type;
+        if let Some(literal) = field.literal {
+            // The value of a literal is written whatever the field holds
+            let repeats = field.bit_spec.repeat.max_repeats();
+            quote! {
+                let spec = #spec;
+                for element in 0..#repeats {
+                    spec.insert(data, element, #literal).expect("buffer checked against SIZE");
+                }
+            }
+        } else if field.is_array {
+            let raw = field
+                .scalar
+                .encode_tokens(quote! { *value }, width, field_type);
+            quote! {
+                let spec = #spec;
+                for (element, value) in self.#ident.iter().enumerate() {
+                    spec.insert(data, element, #raw).expect("buffer checked against SIZE");
+                }
+            }
+        } else {
+            let raw = field
+                .scalar
+                .encode_tokens(quote! { self.#ident }, width, field_type);
+            quote! {
+                let spec = #spec;
+                spec.insert(data, 0, #raw).expect("buffer checked against SIZE");
+            }
+        }
+    });
+
+    let check_literals = layout_fields.iter().filter_map(|field| {
+        let literal = field.literal?;
+        let spec = compiled_spec_tokens(&field.bit_spec, word_bits);
+        let repeats = field.bit_spec.repeat.max_repeats();
+        let name = field.ident.to_string();
+        Some(quote! {
+            let spec = #spec;
+            let mut elements = 0..#repeats;
+            if elements.any(|element| {
+                spec.extract(data, element).expect("buffer checked against SIZE") != #literal
+            }) {
+                return Err(#name);
+            }
+        })
+    });
+
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let size_message = format!("buffer is smaller than {name}::SIZE");
+
+    Ok(quote! {
+        impl #impl_generics #name #type_generics #where_clause {
+            /// The number of bytes needed to hold all the fields.
+            pub const SIZE: usize = #size;
+
+            /// Decode the fields from `data`.
+            ///
+            /// # Panics
+            /// If `data` is shorter than `SIZE`.
+            pub fn decode(data: &[u8]) -> Self {
+                assert!(data.len() >= Self::SIZE, #size_message);
+
+                Self {
+                    #(#decode_fields),*
+                }
+            }
+
+            /// Check the literals in `data`, returning the name of the first
+            /// field that does not match.
+            ///
+            /// # Panics
+            /// If `data` is shorter than `SIZE`.
+            pub fn validate(data: &[u8]) -> ::core::result::Result<(), &'static str> {
+                assert!(data.len() >= Self::SIZE, #size_message);
+
+                #({ #check_literals })*
+                Ok(())
+            }
+
+            /// Encode the fields into `data`, leaving any other bits untouched.
+            /// Bits in a value that do not fit into the field are ignored, and
+            /// literal fields are given the value of the literal.
+            ///
+            /// # Panics
+            /// If `data` is shorter than `SIZE`.
+            pub fn encode(&self, data: &mut [u8]) {
+                assert!(data.len() >= Self::SIZE, #size_message);
+
+                #({ #encode_fields })*
+            }
+        }
+    })
+}
+
+// The word size from #[bit_layout(word_bits = n)], defaulting to 8
+fn word_bits(input: &DeriveInput) -> syn::Result<usize> {
+    let mut word_bits: usize = 8;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("bit_layout"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("word_bits") {
+                let value: LitInt = meta.value()?.parse()?;
+                word_bits = value.base10_parse()?;
+                if word_bits == 0 || !word_bits.is_multiple_of(8) || word_bits > 64 {
+                    return Err(meta.error("word_bits needs to be a multiple of 8 up to 64"));
+                }
+                Ok(())
+            } else {
+                Err(meta.error("unsupported bit_layout attribute"))
+            }
+        })?;
+    }
+
+    Ok(word_bits)
+}
+
+fn layout_field(field: &syn::Field, word_bits: usize) -> syn::Result<LayoutField> {
+    let ident = field.ident.clone().expect("named field");
+
+    let attr = field
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("bits"))
+        .ok_or_else(|| {
+            syn::Error::new(
+                field.span(),
+                format!("field `{ident}` needs a #[bits(\"...\")] attribute"),
+            )
+        })?;
+    let literal: LitStr = attr.parse_args()?;
+    let bit_spec = parse_spec(&literal)?;
+    CompiledSpec::new(&bit_spec, word_bits)
+        .map_err(|error| syn::Error::new(literal.span(), error.to_string()))?;
+
+    let (scalar_type, repeats) = match (&field.ty, &bit_spec.repeat) {
+        (Type::Array(array), Repeat::Fixed(number)) => (*array.elem.clone(), Some(*number)),
+        (_, Repeat::Fixed(_)) => {
+            return Err(syn::Error::new(
+                field.ty.span(),
+                "a field with a fixed repeat needs to be an array",
+            ))
+        }
+        (_, Repeat::Variable { .. } | Repeat::Conditional { .. }) => {
+            return Err(syn::Error::new(
+                literal.span(),
+                "variable and conditional repeats are not supported by BitLayout",
+            ))
+        }
+        (_, Repeat::None) => (field.ty.clone(), None),
+    };
+
+    if let (Some(number), Type::Array(array)) = (repeats, &field.ty) {
+        if array_len(&array.len) != Some(number) {
+            return Err(syn::Error::new(
+                array.len.span(),
+                format!("array length needs to be the number of repeats ({number})"),
+            ));
+        }
+    }
+
+    let scalar = Scalar::from_type(&scalar_type).ok_or_else(|| {
+        syn::Error::new(
+            scalar_type.span(),
+            "field type needs to be bool, f32, f64 or an unsigned integer",
+        )
+    })?;
+    let field_type = &bit_spec.field_type;
+    if !scalar.holds(field_type) {
+        return Err(syn::Error::new(
+            scalar_type.span(),
+            format!(
+                "field `{ident}` needs to be {} for the type of its bits",
+                Scalar::expected(field_type)
+            ),
+        ));
+    }
+    let width = bit_spec.bit_width(word_bits);
+    if matches!(scalar, Scalar::Unsigned(bits) if width > bits) {
+        return Err(syn::Error::new(
+            literal.span(),
+            format!("{width} bits do not fit the type of field `{ident}`"),
+        ));
+    }
+
+    Ok(LayoutField {
+        ident,
+        span: literal.span(),
+        scalar,
+        scalar_type,
+        is_array: repeats.is_some(),
+        literal: bit_spec.literal().map(|literal| literal.value()),
+        bit_spec,
+    })
+}
+
+fn array_len(len: &Expr) -> Option<usize> {
+    match len {
+        Expr::Lit(expr) => match &expr.lit {
+            Lit::Int(value) => value.base10_parse().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// The absolute bit ranges, end exclusive, covered by all repetitions
+fn bit_ranges(field: &LayoutField, word_bits: usize) -> Vec<(usize, usize)> {
+    let bit_spec = &field.bit_spec;
+    let offset = bit_spec.bit_offset(word_bits);
+    let width = bit_spec.bit_width(word_bits);
+    let stride = bit_spec.word_count() * word_bits;
+
+    (0..bit_spec.repeat.max_repeats())
+        .map(|element| {
+            let start = offset + element * stride;
+            (start, start + width)
+        })
+        .collect()
+}
+
+fn check_overlaps(
+    previous: &[LayoutField],
+    field: &LayoutField,
+    word_bits: usize,
+) -> syn::Result<()> {
+    let ranges = bit_ranges(field, word_bits);
+
+    for other in previous {
+        let overlaps = bit_ranges(other, word_bits).iter().any(|(start, end)| {
+            ranges
+                .iter()
+                .any(|(other_start, other_end)| start < other_end && other_start < end)
+        });
+        if overlaps {
+            return Err(syn::Error::new(
+                field.span,
+                format!("bits of `{}` overlap with `{}`", field.ident, other.ident),
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! Procedural macros for [bit-lang](bit_lang).
+//!
+//! Bit specifications are parsed when compiling, using the same parser as
+//! `bit_lang::parse`, so that errors are reported by the compiler rather than
+//! at run time.
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+mod derive;
+mod spec;
+
+/// Parse a bit specification at compile time.
+///
+/// With just the specification the macro expands to a [`BitSpec`](bit_lang::BitSpec):
+/// ```
+/// use bit_lang::{BitRange, BitSpec};
+/// use bit_lang_macros::bit_spec;
+///
+/// let bit_spec: BitSpec = bit_spec!("3[4..7]..6[0..5];48");
+/// assert_eq!(bit_spec.start.bit_range, BitRange::Range(4, 7));
+/// ```
+///
+/// Giving the word size as well expands to a [`CompiledSpec`](bit_lang::CompiledSpec)
+/// that can be used as a constant:
+/// ```
+/// use bit_lang::CompiledSpec;
+/// use bit_lang_macros::bit_spec;
+///
+/// const TEMPERATURE: CompiledSpec = bit_spec!("1[]..2[]", 8);
+///
+/// assert_eq!(TEMPERATURE.extract(&[0x00, 0x34, 0x12], 0).unwrap(), 0x1234);
+/// ```
+///
+/// An invalid specification fails to compile:
+/// ```compile_fail
+/// let bit_spec = bit_lang_macros::bit_spec!("3[4..7");
+/// ```
+///
+/// As do bits outside the words:
+/// ```compile_fail
+/// const FLAG: bit_lang::CompiledSpec = bit_lang_macros::bit_spec!("[9]", 8);
+/// ```
+#[proc_macro]
+pub fn bit_spec(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as spec::BitSpecInput);
+
+    spec::expand(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+/// Generate typed accessors for a struct whose fields are given by bit specifications.
+///
+/// Each field has a `#[bits("...")]` attribute and is either a `bool`, `f32`,
+/// `f64`, an unsigned integer or, for a fixed repeat, an array of these. The Rust
+/// type follows the type of the bits as in the generated Rust modules: `:bool` is
+/// a `bool`, `:f32` an `f32`, fixed point an `f64`, and other bits, including
+/// `:f16` and enumerations, an unsigned integer holding the raw value. The word
+/// size can be given with `#[bit_layout(word_bits = 16)]` and defaults to 8.
+///
+/// This generates a `SIZE` constant, derived from the maximum size of the fields,
+/// and the methods `decode(&[u8]) -> Self` and `encode(&self, &mut [u8])`. Encoding
+/// writes the value of each literal, and `validate(&[u8])` checks the literals,
+/// returning the name of the first field that does not match.
+/// ```
+/// use bit_lang_macros::BitLayout;
+///
+/// #[derive(BitLayout, Debug, PartialEq)]
+/// struct Status {
+///     #[bits("0[0..3]")]
+///     mode: u8,
+///     #[bits("0[7]:bool")]
+///     enabled: bool,
+///     #[bits("1[]..2[]")]
+///     level: u16,
+///     #[bits("3[];2")]
+///     samples: [u8; 2],
+/// }
+///
+/// let status = Status::decode(&[0x82, 0x34, 0x12, 0x05, 0x06]);
+/// assert_eq!(status, Status { mode: 2, enabled: true, level: 0x1234, samples: [5, 6] });
+///
+/// let mut data = [0u8; Status::SIZE];
+/// status.encode(&mut data);
+/// assert_eq!(data, [0x82, 0x34, 0x12, 0x05, 0x06]);
+/// ```
+///
+/// Fields with overlapping bits fail to compile:
+/// ```compile_fail
+/// #[derive(bit_lang_macros::BitLayout)]
+/// struct Overlapping {
+///     #[bits("0[0..3]")]
+///     low: u8,
+///     #[bits("0[3..7]")]
+///     high: u8,
+/// }
+/// ```
+///
+/// As do fields whose Rust type does not match the type of the bits:
+/// ```compile_fail
+/// #[derive(bit_lang_macros::BitLayout)]
+/// struct Raw {
+///     #[bits("0[]..1[]:q8.8")]
+///     level: u16,
+/// }
+/// ```
+/// ```compile_fail
+/// #[derive(bit_lang_macros::BitLayout)]
+/// struct Untyped {
+///     #[bits("0[]..3[]")]
+///     gain: f32,
+/// }
+/// ```
+#[proc_macro_derive(BitLayout, attributes(bits, bit_layout))]
+pub fn bit_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    derive::expand(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
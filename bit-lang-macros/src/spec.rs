@@ -0,0 +1,223 @@
+//! Expansion of the `bit_spec!` macro.
+
+use bit_lang::parser::{BitRange, BitSpec, Condition, FieldType, LiteralType, Repeat, Word};
+use bit_lang::CompiledSpec;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{LitInt, LitStr, Token};
+
+// "spec" or "spec", word_bits
+pub struct BitSpecInput {
+    spec: LitStr,
+    word_bits: Option<LitInt>,
+}
+
+impl Parse for BitSpecInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let spec = input.parse()?;
+        let word_bits = if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
+        Ok(BitSpecInput { spec, word_bits })
+    }
+}
+
+pub fn expand(input: &BitSpecInput) -> syn::Result<TokenStream> {
+    let bit_spec = parse_spec(&input.spec)?;
+
+    match &input.word_bits {
+        Some(word_bits) => compiled_spec(&bit_spec, &input.spec, word_bits),
+        None => Ok(bit_spec_tokens(&bit_spec)),
+    }
+}
+
+/// Parse the bit specification in a string literal, with any error
+/// located at the literal.
+pub fn parse_spec(literal: &LitStr) -> syn::Result<BitSpec> {
+    let text = literal.value();
+
+    match bit_lang::parser::bit_spec(&text) {
+        Ok(("", bit_spec)) => Ok(bit_spec),
+        Ok((remaining, _)) => Err(syn::Error::new(
+            literal.span(),
+            format!("unexpected `{remaining}` in bit specification `{text}`"),
+        )),
+        Err(_) => Err(syn::Error::new(
+            literal.span(),
+            format!("invalid bit specification `{text}`"),
+        )),
+    }
+}
+
+// Compile the bit specification now and expand to a constant with the same plan.
+// Errors are located at the word size if it is not supported and otherwise at
+// the bit specification.
+fn compiled_spec(
+    bit_spec: &BitSpec,
+    spec: &LitStr,
+    word_bits: &LitInt,
+) -> syn::Result<TokenStream> {
+    let word_bits_value: usize = word_bits.base10_parse()?;
+    CompiledSpec::new(bit_spec, word_bits_value).map_err(|error| {
+        let span = match error {
+            bit_lang::Error::InvalidWordSize => word_bits.span(),
+            _ => spec.span(),
+        };
+        syn::Error::new(span, error.to_string())
+    })?;
+
+    Ok(compiled_spec_tokens(bit_spec, word_bits_value))
+}
+
+/// A constant expression for the compiled bit specification. The bit
+/// specification needs to have already been compiled successfully.
+pub fn compiled_spec_tokens(bit_spec: &BitSpec, word_bits: usize) -> TokenStream {
+    let last = bit_spec.end.as_ref().unwrap_or(&bit_spec.start);
+    let offset = bit_spec.bit_offset(word_bits);
+    let end = last.bit_offset(word_bits) + last.bit_width(word_bits);
+    let word_count = bit_spec.word_count();
+    let repeats = bit_spec.repeat.max_repeats();
+    let count = match &bit_spec.repeat {
+        Repeat::Variable { word, .. } => {
+            let start = word.bit_offset(word_bits);
+            let end = start + word.bit_width(word_bits);
+            quote! { ::core::option::Option::Some((#start, #end)) }
+        }
+        // Conditional repeats do not compile
+        Repeat::None | Repeat::Fixed(_) | Repeat::Conditional { .. } => {
+            quote! { ::core::option::Option::None }
+        }
+    };
+
+    quote! {
+        {
+            const SPEC: ::bit_lang::CompiledSpec = match ::bit_lang::CompiledSpec::from_offsets(
+                #word_bits,
+                #offset,
+                #end,
+                #word_count,
+                #repeats,
+                #count,
+            ) {
+                ::core::result::Result::Ok(spec) => spec,
+                ::core::result::Result::Err(_) => panic!("invalid bit specification"),
+            };
+            SPEC
+        }
+    }
+}
+
+/// An expression that constructs the bit specification.
+pub fn bit_spec_tokens(bit_spec: &BitSpec) -> TokenStream {
+    let pointer = option_tokens(&bit_spec.pointer);
+    let start = word_tokens(&bit_spec.start);
+    let end = option_tokens(&bit_spec.end);
+    let repeat = repeat_tokens(&bit_spec.repeat);
+    let field_type = field_type_tokens(&bit_spec.field_type);
+
+    quote! {
+        ::bit_lang::parser::BitSpec {
+            pointer: #pointer,
+            start: #start,
+            end: #end,
+            repeat: #repeat,
+            field_type: #field_type,
+        }
+    }
+}
+
+fn option_tokens(word: &Option<Word>) -> TokenStream {
+    match word {
+        Some(word) => {
+            let word = word_tokens(word);
+            quote! { ::core::option::Option::Some(#word) }
+        }
+        None => quote! { ::core::option::Option::None },
+    }
+}
+
+fn word_tokens(word: &Word) -> TokenStream {
+    let index = word.index;
+    let bit_range = match &word.bit_range {
+        BitRange::Single(bit) => quote! { ::bit_lang::parser::BitRange::Single(#bit) },
+        BitRange::Range(start, end) => {
+            quote! { ::bit_lang::parser::BitRange::Range(#start, #end) }
+        }
+        BitRange::WholeWord => quote! { ::bit_lang::parser::BitRange::WholeWord },
+        BitRange::Literal(LiteralType::Hex(value)) => quote! {
+            ::bit_lang::parser::BitRange::Literal(::bit_lang::parser::LiteralType::Hex(#value))
+        },
+        BitRange::Literal(LiteralType::Bin(value)) => quote! {
+            ::bit_lang::parser::BitRange::Literal(::bit_lang::parser::LiteralType::Bin(#value))
+        },
+    };
+
+    quote! {
+        ::bit_lang::parser::Word {
+            index: #index,
+            bit_range: #bit_range,
+        }
+    }
+}
+
+fn repeat_tokens(repeat: &Repeat) -> TokenStream {
+    match repeat {
+        Repeat::None => quote! { ::bit_lang::parser::Repeat::None },
+        Repeat::Fixed(number) => quote! { ::bit_lang::parser::Repeat::Fixed(#number) },
+        Repeat::Variable {
+            word,
+            condition,
+            limit,
+        } => {
+            let word = word_tokens(word);
+            let condition = match condition {
+                Condition::Lt => quote! { ::bit_lang::parser::Condition::Lt },
+                Condition::Lte => quote! { ::bit_lang::parser::Condition::Lte },
+            };
+            quote! {
+                ::bit_lang::parser::Repeat::Variable {
+                    word: #word,
+                    condition: #condition,
+                    limit: #limit,
+                }
+            }
+        }
+        Repeat::Conditional { word, value } => {
+            let word = word_tokens(word);
+            quote! {
+                ::bit_lang::parser::Repeat::Conditional {
+                    word: #word,
+                    value: #value,
+                }
+            }
+        }
+    }
+}
+
+fn field_type_tokens(field_type: &FieldType) -> TokenStream {
+    match field_type {
+        FieldType::Bool => quote! { ::bit_lang::parser::FieldType::Bool },
+        FieldType::F16 => quote! { ::bit_lang::parser::FieldType::F16 },
+        FieldType::F32 => quote! { ::bit_lang::parser::FieldType::F32 },
+        FieldType::Fixed {
+            integer_bits,
+            fractional_bits,
+        } => quote! {
+            ::bit_lang::parser::FieldType::Fixed {
+                integer_bits: #integer_bits,
+                fractional_bits: #fractional_bits,
+            }
+        },
+        FieldType::Enum(variants) => {
+            let variants = variants.iter().map(|(value, name)| {
+                quote! { (#value, ::bit_lang::__private::String::from(#name)) }
+            });
+            quote! { ::bit_lang::parser::FieldType::Enum(::bit_lang::__private::vec![#(#variants),*]) }
+        }
+        FieldType::Unsigned => quote! { ::bit_lang::parser::FieldType::Unsigned },
+    }
+}
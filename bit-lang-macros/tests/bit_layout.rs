@@ -0,0 +1,136 @@
+use bit_lang_macros::BitLayout;
+
+#[derive(BitLayout, Debug, PartialEq)]
+struct Frame {
+    #[bits("[]")]
+    sync: u8,
+    #[bits("1[4..7]..2[0..3]")]
+    value: u16,
+    #[bits("1[0]:bool")]
+    flag: bool,
+    #[bits("3[]..6[]:f32")]
+    gain: f32,
+    #[bits("7[]..8[];3")]
+    samples: [u16; 3],
+}
+
+#[derive(BitLayout, Debug, PartialEq)]
+#[bit_layout(word_bits = 16)]
+struct Wide {
+    #[bits("1[]")]
+    second: u16,
+    #[bits("[8..15]")]
+    high: u8,
+}
+
+#[test]
+fn test_size() {
+    assert_eq!(Frame::SIZE, 13);
+    assert_eq!(Wide::SIZE, 4);
+}
+
+#[test]
+fn test_round_trip() {
+    let frame = Frame {
+        sync: 0xA5,
+        value: 0xBC,
+        flag: true,
+        gain: -2.5,
+        samples: [1, 0x203, 0xFFFF],
+    };
+
+    let mut data = [0u8; Frame::SIZE];
+    frame.encode(&mut data);
+    assert_eq!(&data[..3], &[0xA5, 0xC1, 0x0B]);
+    assert_eq!(&data[3..7], &(-2.5f32).to_bits().to_le_bytes());
+    assert_eq!(&data[7..], &[1, 0, 3, 2, 0xFF, 0xFF]);
+
+    assert_eq!(Frame::decode(&data), frame);
+}
+
+#[test]
+fn test_word_bits() {
+    let wide = Wide::decode(&[0x00, 0x7F, 0x34, 0x12]);
+    assert_eq!(
+        wide,
+        Wide {
+            second: 0x1234,
+            high: 0x7F
+        }
+    );
+}
+
+#[test]
+fn test_encode_ignores_extra_bits() {
+    #[derive(BitLayout, Debug, PartialEq)]
+    struct Nibble {
+        #[bits("[4..7]")]
+        high: u8,
+    }
+
+    let mut data = [0x0F];
+    Nibble { high: 0x1A }.encode(&mut data);
+    assert_eq!(data, [0xAF]);
+}
+
+#[test]
+fn test_fixed_point() {
+    #[derive(BitLayout, Debug, PartialEq)]
+    struct Reading {
+        #[bits("[]..1[]:q8.8")]
+        level: f64,
+        #[bits("2[]:q4.4")]
+        offset: f64,
+    }
+
+    let reading = Reading::decode(&[0x80, 0x01, 0xE8]);
+    assert_eq!(
+        reading,
+        Reading {
+            level: 1.5,
+            offset: -1.5
+        }
+    );
+
+    let mut data = [0u8; Reading::SIZE];
+    Reading {
+        level: -1.5,
+        offset: 0.53,
+    }
+    .encode(&mut data);
+    assert_eq!(data, [0x80, 0xFE, 0x08]);
+}
+
+#[test]
+fn test_literals() {
+    #[derive(BitLayout, Debug, PartialEq)]
+    struct Header {
+        #[bits("[0xA5]")]
+        sync: u8,
+        #[bits("1[0b01];2")]
+        marks: [u8; 2],
+        #[bits("3[]")]
+        length: u8,
+    }
+
+    // The literals are written whatever the fields hold
+    let mut data = [0u8; Header::SIZE];
+    Header {
+        sync: 0,
+        marks: [0, 0],
+        length: 7,
+    }
+    .encode(&mut data);
+    assert_eq!(data, [0xA5, 0x01, 0x01, 0x07]);
+    assert_eq!(Header::validate(&data), Ok(()));
+
+    data[2] = 0x02;
+    assert_eq!(Header::validate(&data), Err("marks"));
+    assert_eq!(Header::validate(&[0x5A, 1, 1, 0]), Err("sync"));
+}
+
+#[test]
+#[should_panic(expected = "buffer is smaller than Frame::SIZE")]
+fn test_short_buffer() {
+    Frame::decode(&[0; 4]);
+}
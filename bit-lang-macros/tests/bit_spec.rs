@@ -0,0 +1,33 @@
+use bit_lang::{parse, BitSpec, CompiledSpec};
+use bit_lang_macros::bit_spec;
+
+#[test]
+fn test_bit_spec_matches_parse() {
+    let specs: [(BitSpec, &str); 6] = [
+        (bit_spec!("5"), "5"),
+        (bit_spec!("3[4..7]..6[0..5];48"), "3[4..7]..6[0..5];48"),
+        (bit_spec!("4[]..7[];(3[])<49"), "4[]..7[];(3[])<49"),
+        (bit_spec!("5[0b1101_0001]"), "5[0b1101_0001]"),
+        (bit_spec!("1[]..2[]:q8.8"), "1[]..2[]:q8.8"),
+        (bit_spec!("[0..1]:{0=Off,1=On}"), "[0..1]:{0=Off,1=On}"),
+    ];
+
+    for (bit_spec, text) in specs {
+        assert_eq!(bit_spec, parse(text).unwrap());
+    }
+}
+
+#[test]
+fn test_compiled_spec() {
+    const COUNT: CompiledSpec = bit_spec!("[0..3]", 8);
+    const SAMPLES: CompiledSpec = bit_spec!("1[]..2[];(0[0..3])<4", 16);
+
+    let data = [0x02, 0x00, 0x34, 0x12, 0x78, 0x56];
+    assert_eq!(COUNT.extract(&data, 0).unwrap(), 2);
+    assert_eq!(SAMPLES.repeats(&data).unwrap(), 2);
+    assert_eq!(SAMPLES.extract(&data, 0).unwrap(), 0x5678_1234);
+    assert_eq!(
+        SAMPLES,
+        parse("1[]..2[];(0[0..3])<4").unwrap().compile(16).unwrap()
+    );
+}
@@ -0,0 +1,729 @@
+//! Reading and writing the bits referred to by a [`BitSpec`] in a buffer.
+//!
+//! Bits are numbered from the least significant bit of each word and words
+//! follow each other in the buffer, so bit `b` in word `w` is at the absolute
+//! bit offset `w * word_bits + b`. Multi-byte words are stored little endian.
+//! Values that span several words are assembled with the lowest absolute bit
+//! as the least significant bit of the value.
+//!
+//! The start of an indirect bit specification, such as `@(2[])[0..3]`, is read
+//! from its pointer word when decoding: the pointer gives the index of the word
+//! that the start and end word indexes are relative to. The words it points to
+//! must follow the pointer word, so that the data cannot alias the header.
+
+use crate::parser::{BitRange, BitSpec, Condition, FieldType, LiteralType, Repeat, Word};
+use crate::Error;
+
+/// How the bits in a word are numbered when giving absolute bit offsets.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum BitOrder {
+    // Bit 0 is the least significant bit of the word, as in bit specifications
+    #[default]
+    Lsb0,
+    // Bit 0 is the most significant bit of the word, as in many protocol
+    // specifications
+    Msb0,
+}
+
+/// Read `width` bits starting at the absolute bit `offset` in `data`.
+pub fn extract_bits(data: &[u8], offset: usize, width: usize) -> Result<u64, Error> {
+    if width > 64 {
+        return Err(Error::TooWide);
+    }
+    if offset + width > data.len() * 8 {
+        return Err(Error::OutOfBounds);
+    }
+
+    let mut value: u64 = 0;
+    let mut done = 0;
+    while done < width {
+        let bit = offset + done;
+        let shift = bit % 8;
+        let n = (8 - shift).min(width - done);
+        let mask = ((1u16 << n) - 1) as u8;
+        let bits = (data[bit / 8] >> shift) & mask;
+        value |= (bits as u64) << done;
+        done += n;
+    }
+
+    Ok(value)
+}
+
+/// Write the `width` least significant bits of `value` at the absolute bit
+/// `offset` in `data`, leaving all other bits untouched.
+pub fn insert_bits(data: &mut [u8], offset: usize, width: usize, value: u64) -> Result<(), Error> {
+    if width > 64 {
+        return Err(Error::TooWide);
+    }
+    if width < 64 && value >> width != 0 {
+        return Err(Error::ValueOutOfRange);
+    }
+    if offset + width > data.len() * 8 {
+        return Err(Error::OutOfBounds);
+    }
+
+    let mut done = 0;
+    while done < width {
+        let bit = offset + done;
+        let shift = bit % 8;
+        let n = (8 - shift).min(width - done);
+        let mask = (((1u16 << n) - 1) as u8) << shift;
+        let bits = ((value >> done) as u8) << shift;
+        data[bit / 8] = (data[bit / 8] & !mask) | (bits & mask);
+        done += n;
+    }
+
+    Ok(())
+}
+
+impl LiteralType {
+    /// The numeric value of the literal.
+    pub const fn value(&self) -> u64 {
+        match self {
+            LiteralType::Hex(value) | LiteralType::Bin(value) => *value,
+        }
+    }
+}
+
+impl Word {
+    // The first bit in the word that is referred to.
+    fn first_bit(&self) -> usize {
+        match &self.bit_range {
+            BitRange::Single(bit) => *bit as usize,
+            BitRange::Range(start, _) => *start as usize,
+            BitRange::WholeWord | BitRange::Literal(_) => 0,
+        }
+    }
+
+    // The last bit (inclusive) in the word that is referred to.
+    fn last_bit(&self, word_bits: usize) -> usize {
+        match &self.bit_range {
+            BitRange::Single(bit) => *bit as usize,
+            BitRange::Range(_, end) => *end as usize,
+            BitRange::WholeWord | BitRange::Literal(_) => word_bits.saturating_sub(1),
+        }
+    }
+
+    /// The absolute offset of the first bit referred to by the word.
+    pub fn bit_offset(&self, word_bits: usize) -> usize {
+        self.index * word_bits + self.first_bit()
+    }
+
+    /// The number of bits referred to by the word.
+    pub fn bit_width(&self, word_bits: usize) -> usize {
+        (self.last_bit(word_bits) + 1).saturating_sub(self.first_bit())
+    }
+
+    // Check that the bits are inside the word.
+    pub(crate) fn check_bits(&self, word_bits: usize) -> Result<(), Error> {
+        check_word_bits(word_bits)?;
+        let valid = match self.bit_range {
+            BitRange::Single(bit) => (bit as usize) < word_bits,
+            BitRange::Range(start, end) => start <= end && (end as usize) < word_bits,
+            BitRange::WholeWord | BitRange::Literal(_) => true,
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidBitSpec)
+        }
+    }
+
+    /// Read the bits referred to by the word.
+    pub fn extract(&self, data: &[u8], word_bits: usize) -> Result<u64, Error> {
+        check_word_bits(word_bits)?;
+        extract_bits(data, self.bit_offset(word_bits), self.bit_width(word_bits))
+    }
+}
+
+impl BitSpec {
+    /// The number of words covered by a single repetition of the bit specification.
+    pub fn word_count(&self) -> usize {
+        match &self.end {
+            Some(end) => end.index.saturating_sub(self.start.index) + 1,
+            None => 1,
+        }
+    }
+
+    /// The absolute offset of the first bit in the first repetition.
+    ///
+    /// For an indirect bit specification this is relative to the word pointed to.
+    pub fn bit_offset(&self, word_bits: usize) -> usize {
+        self.start.bit_offset(word_bits)
+    }
+
+    /// The number of bits in a single repetition.
+    pub fn bit_width(&self, word_bits: usize) -> usize {
+        let last_word = self.end.as_ref().unwrap_or(&self.start);
+        let last = last_word.index * word_bits + last_word.last_bit(word_bits);
+
+        (last + 1).saturating_sub(self.bit_offset(word_bits))
+    }
+
+    /// The literal value that the bits should have, if any.
+    pub fn literal(&self) -> Option<&LiteralType> {
+        match &self.start.bit_range {
+            BitRange::Literal(literal) => Some(literal),
+            _ => None,
+        }
+    }
+
+    /// The number of repetitions in `data`.
+    ///
+    /// For a variable repeat this is read from the repeat word and checked
+    /// against the limit. A conditional repeat is once if its word has the
+    /// value and none otherwise.
+    pub fn repeats(&self, data: &[u8], word_bits: usize) -> Result<usize, Error> {
+        check_word_bits(word_bits)?;
+        match &self.repeat {
+            Repeat::None => Ok(1),
+            Repeat::Fixed(number) => Ok(*number),
+            Repeat::Variable { word, .. } => {
+                let count = word.extract(data, word_bits)? as usize;
+                self.check_repeats(count)?;
+                Ok(count)
+            }
+            Repeat::Conditional { word, value } => {
+                Ok(usize::from(word.extract(data, word_bits)? == *value))
+            }
+        }
+    }
+
+    // Check that the number of repetitions is allowed by the repeat.
+    pub(crate) fn check_repeats(&self, count: usize) -> Result<(), Error> {
+        let allowed = match &self.repeat {
+            Repeat::None => count == 1,
+            Repeat::Fixed(number) => count == *number,
+            Repeat::Variable {
+                condition: Condition::Lt,
+                limit,
+                ..
+            } => count < *limit,
+            Repeat::Variable {
+                condition: Condition::Lte,
+                limit,
+                ..
+            } => count <= *limit,
+            Repeat::Conditional { .. } => count <= 1,
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::RepeatOutOfRange)
+        }
+    }
+
+    /// The absolute offset just after the last bit of the last repetition in `data`.
+    pub fn end_offset(&self, data: &[u8], word_bits: usize) -> Result<usize, Error> {
+        match self.repeats(data, word_bits)? {
+            0 => Ok(self.base_offset(data, word_bits)? + self.bit_offset(word_bits)),
+            repeats => {
+                Ok(self.element_offset(data, word_bits, repeats - 1)? + self.bit_width(word_bits))
+            }
+        }
+    }
+
+    // The absolute offset of the word that the start and end words are relative
+    // to, which is read from the pointer of an indirect bit specification.
+    pub(crate) fn base_offset(&self, data: &[u8], word_bits: usize) -> Result<usize, Error> {
+        let Some(pointer) = &self.pointer else {
+            return Ok(0);
+        };
+
+        let base = usize::try_from(pointer.extract(data, word_bits)?)
+            .ok()
+            .and_then(|index| index.checked_mul(word_bits))
+            .filter(|base| *base <= data.len() * 8)
+            .ok_or(Error::OutOfBounds)?;
+        if base + self.bit_offset(word_bits) < (pointer.index + 1) * word_bits {
+            return Err(Error::InvalidPointer);
+        }
+
+        Ok(base)
+    }
+
+    /// Check that the bit specification can be used with words of `word_bits` bits.
+    ///
+    /// This finds problems that parsing cannot, such as bits outside of the word,
+    /// literals that are too large or field types that do not fit the bits.
+    pub fn validate(&self, word_bits: usize) -> Result<(), Error> {
+        self.check_bits(word_bits)?;
+
+        let width = self.bit_width(word_bits);
+        if width > 64 {
+            return Err(Error::TooWide);
+        }
+        if let Some(literal) = self.literal() {
+            let value = literal.value();
+            if width < 64 && value >> width != 0 {
+                return Err(Error::ValueOutOfRange);
+            }
+        }
+        self.check_repeat(word_bits)?;
+        if let Some(pointer) = &self.pointer {
+            if pointer.bit_width(word_bits) > 64 {
+                return Err(Error::TooWide);
+            }
+        }
+
+        self.field_type.check_width(width)
+    }
+
+    // Check that the bits are inside their words and that there are some.
+    pub(crate) fn check_bits(&self, word_bits: usize) -> Result<(), Error> {
+        let words = [self.pointer.as_ref(), Some(&self.start), self.end.as_ref()];
+        for word in words.into_iter().flatten() {
+            word.check_bits(word_bits)?;
+        }
+        if let Some(Word {
+            bit_range: BitRange::Literal(_),
+            ..
+        }) = &self.pointer
+        {
+            return Err(Error::InvalidBitSpec);
+        }
+        if let Some(end) = &self.end {
+            if end.index < self.start.index || self.bit_width(word_bits) == 0 {
+                return Err(Error::InvalidBitSpec);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Check that the word of a variable or conditional repeat can be read, that
+    // the value of a condition fits in it and that a limit allows a repetition.
+    pub(crate) fn check_repeat(&self, word_bits: usize) -> Result<(), Error> {
+        match &self.repeat {
+            Repeat::Variable { word, .. } | Repeat::Conditional { word, .. }
+                if word.bit_width(word_bits) > 64 =>
+            {
+                Err(Error::TooWide)
+            }
+            Repeat::Variable {
+                condition: Condition::Lt,
+                limit: 0,
+                ..
+            } => Err(Error::RepeatOutOfRange),
+            Repeat::Conditional { word, value } => {
+                let width = word.bit_width(word_bits);
+                if width < 64 && value >> width != 0 {
+                    return Err(Error::ValueOutOfRange);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Check that the position of the bits does not depend on the data, for
+    // uses that need to know it before decoding.
+    pub(crate) fn check_direct(&self) -> Result<(), Error> {
+        match self.pointer {
+            Some(_) => Err(Error::IndirectWord),
+            None => Ok(()),
+        }
+    }
+
+    /// The absolute bit offset and width of the first repetition, with the
+    /// bits of each word numbered in `bit_order`.
+    ///
+    /// With [`BitOrder::Msb0`] only bits within one word can be converted. The
+    /// words of a value are joined least significant first, so the bits of a
+    /// value over several words are not consecutive in that order, and give
+    /// [`Error::NotConsecutive`]. Indirect bit specifications have no offset
+    /// until they are decoded.
+    pub fn to_offset(
+        &self,
+        word_bits: usize,
+        bit_order: BitOrder,
+    ) -> Result<(usize, usize), Error> {
+        self.check_bits(word_bits)?;
+        self.check_direct()?;
+
+        let offset = self.bit_offset(word_bits);
+        let width = self.bit_width(word_bits);
+        match bit_order {
+            BitOrder::Lsb0 => Ok((offset, width)),
+            BitOrder::Msb0 if self.word_count() == 1 => {
+                Ok((msb0_offset(offset, width, word_bits), width))
+            }
+            BitOrder::Msb0 => Err(Error::NotConsecutive),
+        }
+    }
+
+    /// The most compact bit specification for `width` bits at the absolute
+    /// bit `offset`, with the bits of each word numbered in `bit_order`.
+    ///
+    /// The bits are a single bit or range within a word, or run from a bit to
+    /// the top of the first word and from the bottom of the last word to a bit,
+    /// e.g. `4[5]..5[0]`.
+    ///
+    /// As for [`BitSpec::to_offset`], bits over several words give
+    /// [`Error::NotConsecutive`] with [`BitOrder::Msb0`]. Bits in a word after
+    /// word 255 give [`Error::IndexOutOfRange`], as the index could not be
+    /// parsed back.
+    pub fn from_offset(
+        offset: usize,
+        width: usize,
+        word_bits: usize,
+        bit_order: BitOrder,
+    ) -> Result<BitSpec, Error> {
+        // Bit indexes are held in a u8
+        if word_bits == 0 || word_bits > 256 {
+            return Err(Error::InvalidWordSize);
+        }
+        if width == 0 {
+            return Err(Error::InvalidBitSpec);
+        }
+
+        let last = offset
+            .checked_add(width - 1)
+            .ok_or(Error::IndexOutOfRange)?;
+        let (first_word, last_word) = (offset / word_bits, last / word_bits);
+        if last_word > u8::MAX as usize {
+            return Err(Error::IndexOutOfRange);
+        }
+        let offset = match bit_order {
+            BitOrder::Lsb0 => offset,
+            BitOrder::Msb0 if first_word == last_word => msb0_offset(offset, width, word_bits),
+            BitOrder::Msb0 => return Err(Error::NotConsecutive),
+        };
+        let last = offset + width - 1;
+        let (first_bit, last_bit) = (offset % word_bits, last % word_bits);
+
+        if first_word == last_word {
+            let bit_range = match (first_bit, last_bit) {
+                (0, last) if last == word_bits - 1 => BitRange::WholeWord,
+                (first, last) if first == last => BitRange::Single(first as u8),
+                (first, last) => BitRange::Range(first as u8, last as u8),
+            };
+            return Ok(BitSpec {
+                pointer: None,
+                start: Word {
+                    index: first_word,
+                    bit_range,
+                },
+                end: None,
+                repeat: Repeat::None,
+                field_type: FieldType::Unsigned,
+            });
+        }
+
+        // The start runs from its bit to the top of the word and the end from
+        // the bottom of the word to its bit
+        Ok(BitSpec {
+            pointer: None,
+            start: Word {
+                index: first_word,
+                bit_range: match first_bit {
+                    0 => BitRange::WholeWord,
+                    bit => BitRange::Single(bit as u8),
+                },
+            },
+            end: Some(Word {
+                index: last_word,
+                bit_range: match last_bit {
+                    bit if bit == word_bits - 1 => BitRange::WholeWord,
+                    bit => BitRange::Single(bit as u8),
+                },
+            }),
+            repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
+        })
+    }
+
+    /// The absolute offset of the first bit of the repetition `element` in `data`,
+    /// reading the pointer of an indirect bit specification.
+    pub fn element_offset(
+        &self,
+        data: &[u8],
+        word_bits: usize,
+        element: usize,
+    ) -> Result<usize, Error> {
+        check_word_bits(word_bits)?;
+        Ok(self.base_offset(data, word_bits)?
+            + self.bit_offset(word_bits)
+            + element * self.word_count() * word_bits)
+    }
+
+    /// Read the raw bits of the repetition `element`.
+    pub fn extract(&self, data: &[u8], word_bits: usize, element: usize) -> Result<u64, Error> {
+        extract_bits(
+            data,
+            self.element_offset(data, word_bits, element)?,
+            self.bit_width(word_bits),
+        )
+    }
+
+    /// Write the raw bits of the repetition `element`.
+    pub fn insert(
+        &self,
+        data: &mut [u8],
+        word_bits: usize,
+        element: usize,
+        value: u64,
+    ) -> Result<(), Error> {
+        insert_bits(
+            data,
+            self.element_offset(data, word_bits, element)?,
+            self.bit_width(word_bits),
+            value,
+        )
+    }
+}
+
+// Number the bits of `width` bits at `offset` within a word from the other
+// end of the word, which converts between Lsb0 and Msb0 in both directions
+// Check that words have bits, as bit specifications number the bits of a word
+// from 0 to one less than its size.
+pub(crate) fn check_word_bits(word_bits: usize) -> Result<(), Error> {
+    match word_bits {
+        0 => Err(Error::InvalidWordSize),
+        _ => Ok(()),
+    }
+}
+
+fn msb0_offset(offset: usize, width: usize, word_bits: usize) -> usize {
+    let start = offset - offset % word_bits;
+    start + word_bits - (offset - start) - width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    #[cfg(not(feature = "std"))]
+    use std::prelude::rust_2021::*;
+
+    #[test]
+    fn test_extract_bits() {
+        let data = [0b1010_0101, 0x34, 0x12];
+
+        assert_eq!(extract_bits(&data, 0, 8).unwrap(), 0b1010_0101);
+        assert_eq!(extract_bits(&data, 2, 4).unwrap(), 0b1001);
+        assert_eq!(extract_bits(&data, 8, 16).unwrap(), 0x1234);
+        assert_eq!(extract_bits(&data, 4, 8).unwrap(), 0x4A);
+        assert_eq!(extract_bits(&data, 23, 1).unwrap(), 0);
+
+        assert_eq!(extract_bits(&data, 20, 8), Err(Error::OutOfBounds));
+        assert_eq!(extract_bits(&[0; 9], 0, 65), Err(Error::TooWide));
+    }
+
+    #[test]
+    fn test_insert_bits() {
+        let mut data = [0xFF, 0x00, 0x00];
+
+        insert_bits(&mut data, 2, 4, 0).unwrap();
+        assert_eq!(data, [0b1100_0011, 0x00, 0x00]);
+
+        insert_bits(&mut data, 4, 16, 0xABCD).unwrap();
+        assert_eq!(data, [0xD3, 0xBC, 0x0A]);
+
+        assert_eq!(
+            insert_bits(&mut data, 0, 4, 0x10),
+            Err(Error::ValueOutOfRange)
+        );
+        assert_eq!(insert_bits(&mut data, 20, 8, 0), Err(Error::OutOfBounds));
+    }
+
+    #[test]
+    fn test_literal_value() {
+        assert_eq!(LiteralType::Hex(0x23FF).value(), 0x23FF);
+        assert_eq!(LiteralType::Bin(0xD1).value(), 0xD1);
+    }
+
+    #[test]
+    fn test_bit_width() {
+        let bit_spec = parse("3[4..7]..6[0..5]").unwrap();
+        assert_eq!(bit_spec.bit_offset(8), 28);
+        assert_eq!(bit_spec.bit_width(8), 26);
+
+        let bit_spec = parse("2[]").unwrap();
+        assert_eq!(bit_spec.bit_offset(16), 32);
+        assert_eq!(bit_spec.bit_width(16), 16);
+
+        let bit_spec = parse("5").unwrap();
+        assert_eq!(bit_spec.bit_offset(8), 5);
+        assert_eq!(bit_spec.bit_width(8), 1);
+    }
+
+    #[test]
+    fn test_extract_and_insert() {
+        let bit_spec = parse("1[4..7]..2[0..3]").unwrap();
+        let mut data = [0x00, 0xA0, 0x0B];
+        assert_eq!(bit_spec.extract(&data, 8, 0).unwrap(), 0xBA);
+
+        bit_spec.insert(&mut data, 8, 0, 0x5C).unwrap();
+        assert_eq!(data, [0x00, 0xC0, 0x05]);
+
+        let bit_spec = parse("1[]..2[];3").unwrap();
+        let data = [0, 1, 0, 2, 0, 3, 0];
+        assert_eq!(bit_spec.extract(&data, 8, 2).unwrap(), 3);
+        assert_eq!(bit_spec.extract(&data, 8, 3), Err(Error::OutOfBounds));
+    }
+
+    #[test]
+    fn test_indirect() {
+        let bit_spec = parse("@(1[0..3])[4..7]..1[0..3]").unwrap();
+        assert_eq!(bit_spec.validate(8), Ok(()));
+
+        let data = [0xFF, 0x03, 0x00, 0xA0, 0x0B];
+        assert_eq!(bit_spec.extract(&data, 8, 0).unwrap(), 0xBA);
+        assert_eq!(bit_spec.element_offset(&data, 8, 0), Ok(28));
+        assert_eq!(bit_spec.end_offset(&data, 8), Ok(36));
+
+        let mut data = [0x00, 0x02, 0x00, 0x00];
+        bit_spec.insert(&mut data, 8, 0, 0x5C).unwrap();
+        assert_eq!(data, [0x00, 0x02, 0xC0, 0x05]);
+
+        // The data cannot start in or before the pointer word, nor after the buffer
+        assert_eq!(
+            bit_spec.extract(&[0, 1, 0], 8, 0),
+            Err(Error::InvalidPointer)
+        );
+        assert_eq!(bit_spec.extract(&[0, 9, 0], 8, 0), Err(Error::OutOfBounds));
+        assert_eq!(bit_spec.extract(&[0, 2, 0], 8, 0), Err(Error::OutOfBounds));
+        assert_eq!(bit_spec.extract(&[0], 8, 0), Err(Error::OutOfBounds));
+
+        assert_eq!(
+            parse("@([0x1])[]").unwrap().validate(8),
+            Err(Error::InvalidBitSpec)
+        );
+        assert_eq!(
+            bit_spec.to_offset(8, BitOrder::Lsb0),
+            Err(Error::IndirectWord)
+        );
+    }
+
+    #[test]
+    fn test_end_offset() {
+        let bit_spec = parse("1[]..2[];(0[])<4").unwrap();
+        assert_eq!(bit_spec.end_offset(&[2, 0, 0, 0, 0], 8).unwrap(), 40);
+        assert_eq!(bit_spec.end_offset(&[0], 8).unwrap(), 8);
+
+        let bit_spec = parse("1[2..5]").unwrap();
+        assert_eq!(bit_spec.end_offset(&[], 8).unwrap(), 14);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(parse("3[4..7]..6[0..5];48").unwrap().validate(8), Ok(()));
+        assert_eq!(parse("2[]").unwrap().validate(16), Ok(()));
+
+        assert_eq!(
+            parse("[9]").unwrap().validate(8),
+            Err(Error::InvalidBitSpec)
+        );
+        assert_eq!(
+            parse("[6..2]").unwrap().validate(8),
+            Err(Error::InvalidBitSpec)
+        );
+        assert_eq!(
+            parse("4[]..2[]").unwrap().validate(8),
+            Err(Error::InvalidBitSpec)
+        );
+        assert_eq!(parse("[]..8[]").unwrap().validate(8), Err(Error::TooWide));
+        assert_eq!(
+            parse("[0x1FF]").unwrap().validate(8),
+            Err(Error::ValueOutOfRange)
+        );
+        assert_eq!(
+            parse("[0..1]:bool").unwrap().validate(8),
+            Err(Error::TypeMismatch)
+        );
+
+        // Words need bits for even a whole word to be referred to
+        let bit_spec = parse("1[];(0[])<3").unwrap();
+        assert_eq!(bit_spec.validate(0), Err(Error::InvalidWordSize));
+        assert_eq!(bit_spec.repeats(&[1, 0], 0), Err(Error::InvalidWordSize));
+        assert_eq!(bit_spec.extract(&[1, 0], 0, 0), Err(Error::InvalidWordSize));
+        assert_eq!(
+            bit_spec.to_offset(0, BitOrder::Lsb0),
+            Err(Error::InvalidWordSize)
+        );
+    }
+
+    #[test]
+    fn test_repeats() {
+        let bit_spec = parse("1[];(0[])<3").unwrap();
+        assert_eq!(bit_spec.repeats(&[2, 0, 0], 8).unwrap(), 2);
+        assert_eq!(
+            bit_spec.repeats(&[3, 0, 0, 0], 8),
+            Err(Error::RepeatOutOfRange)
+        );
+
+        let bit_spec = parse("1[];4").unwrap();
+        assert_eq!(bit_spec.repeats(&[], 8).unwrap(), 4);
+
+        let bit_spec = parse("1[];if 0[4..7]==0xA").unwrap();
+        assert_eq!(bit_spec.repeats(&[0xA0], 8).unwrap(), 1);
+        assert_eq!(bit_spec.repeats(&[0xB0], 8).unwrap(), 0);
+        assert_eq!(bit_spec.validate(8), Ok(()));
+        let bit_spec = parse("1[];if 0[7]==2").unwrap();
+        assert_eq!(bit_spec.validate(8), Err(Error::ValueOutOfRange));
+
+        // No number of repeats is less than 0
+        let bit_spec = parse("1[];(0[])<0").unwrap();
+        assert_eq!(bit_spec.validate(8), Err(Error::RepeatOutOfRange));
+        assert_eq!(bit_spec.repeat.max_repeats(), 0);
+    }
+
+    #[test]
+    fn test_offset() {
+        for (offset, width, word_bits, text) in [
+            (37, 12, 8, "4[5]..6[0]"),
+            (37, 3, 8, "4[5..7]"),
+            (37, 1, 8, "4[5]"),
+            (32, 16, 8, "4[]..5[]"),
+            (20, 20, 16, "1[4]..2[7]"),
+            (16, 16, 16, "1[]"),
+        ] {
+            let bit_spec = BitSpec::from_offset(offset, width, word_bits, BitOrder::Lsb0).unwrap();
+            assert_eq!(bit_spec.to_string(), text);
+            assert_eq!(
+                parse(text).unwrap().to_offset(word_bits, BitOrder::Lsb0),
+                Ok((offset, width))
+            );
+        }
+
+        // The first 3 bits of word 1 are its most significant bits
+        let bit_spec = BitSpec::from_offset(8, 3, 8, BitOrder::Msb0).unwrap();
+        assert_eq!(bit_spec.to_string(), "1[5..7]");
+        assert_eq!(bit_spec.to_offset(8, BitOrder::Msb0), Ok((8, 3)));
+        assert_eq!(
+            BitSpec::from_offset(12, 8, 8, BitOrder::Msb0),
+            Err(Error::NotConsecutive)
+        );
+        assert_eq!(
+            parse("[4]..1[3]").unwrap().to_offset(8, BitOrder::Msb0),
+            Err(Error::NotConsecutive)
+        );
+
+        assert_eq!(
+            BitSpec::from_offset(0, 0, 8, BitOrder::Lsb0),
+            Err(Error::InvalidBitSpec)
+        );
+        // Word indexes are parsed as a u8
+        let bit_spec = BitSpec::from_offset(255 * 32, 32, 32, BitOrder::Lsb0).unwrap();
+        assert_eq!(parse(&bit_spec.to_string()), Ok(bit_spec));
+        assert_eq!(
+            BitSpec::from_offset(256 * 32, 32, 32, BitOrder::Lsb0),
+            Err(Error::IndexOutOfRange)
+        );
+        assert_eq!(
+            BitSpec::from_offset(255 * 8 + 4, 8, 8, BitOrder::Lsb0),
+            Err(Error::IndexOutOfRange)
+        );
+        assert_eq!(
+            BitSpec::from_offset(usize::MAX, 2, 8, BitOrder::Lsb0),
+            Err(Error::IndexOutOfRange)
+        );
+        assert_eq!(
+            parse("[9]").unwrap().to_offset(8, BitOrder::Lsb0),
+            Err(Error::InvalidBitSpec)
+        );
+    }
+}
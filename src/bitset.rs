@@ -0,0 +1,242 @@
+//! Sets of absolute bit offsets, for comparing the bits used by bit specifications.
+//!
+//! A [`BitSet`] holds the bits as ordered ranges that neither overlap nor
+//! touch, so two sets with the same bits are equal however they were built.
+//! [`BitSpec::bit_set`] gives the bits of all repetitions of a specification:
+//! ```
+//! let samples = bit_lang::parse("2[];3").unwrap().bit_set(8).unwrap();
+//! let count = bit_lang::parse("3[0..3]").unwrap().bit_set(8).unwrap();
+//! assert!(samples.contains(&count));
+//! assert_eq!(samples.difference(&count).ranges(), [16..24, 28..40]);
+//! ```
+
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::ops::Range;
+
+use crate::access::check_word_bits;
+use crate::parser::BitSpec;
+use crate::Error;
+
+/// A set of absolute bit offsets.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BitSet {
+    // Ordered, non-empty ranges with gaps between them
+    ranges: Vec<Range<usize>>,
+}
+
+impl BitSet {
+    /// An empty set.
+    pub fn new() -> Self {
+        BitSet::default()
+    }
+
+    /// The ranges of consecutive bits in order.
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    /// The number of bits in the set.
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|range| range.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Add the bits in `range`.
+    pub fn insert(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+
+        // The ranges that overlap or touch the new one are merged with it
+        let first = self.ranges.partition_point(|r| r.end < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end);
+        let merged = match self.ranges[first..last] {
+            [] => range,
+            ref touching => {
+                touching[0].start.min(range.start)..touching[touching.len() - 1].end.max(range.end)
+            }
+        };
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    /// Whether `bit` is in the set.
+    pub fn contains_bit(&self, bit: usize) -> bool {
+        let index = self.ranges.partition_point(|r| r.end <= bit);
+        self.ranges
+            .get(index)
+            .is_some_and(|range| range.contains(&bit))
+    }
+
+    /// Whether any bit is in both sets.
+    pub fn overlaps(&self, other: &BitSet) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    /// Whether all the bits of `other` are in this set.
+    pub fn contains(&self, other: &BitSet) -> bool {
+        other.difference(self).is_empty()
+    }
+
+    /// The bits that are in both sets.
+    pub fn intersection(&self, other: &BitSet) -> BitSet {
+        let mut ranges = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while let (Some(a), Some(b)) = (self.ranges.get(i), other.ranges.get(j)) {
+            let range = a.start.max(b.start)..a.end.min(b.end);
+            if !range.is_empty() {
+                ranges.push(range);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        BitSet { ranges }
+    }
+
+    /// The bits that are in either set.
+    pub fn union(&self, other: &BitSet) -> BitSet {
+        let mut union = self.clone();
+        for range in &other.ranges {
+            union.insert(range.clone());
+        }
+        union
+    }
+
+    /// The bits of this set that are not in `other`.
+    pub fn difference(&self, other: &BitSet) -> BitSet {
+        let mut ranges = Vec::new();
+        for range in &self.ranges {
+            let mut start = range.start;
+            let first = other.ranges.partition_point(|r| r.end <= range.start);
+            for removed in other.ranges[first..]
+                .iter()
+                .take_while(|r| r.start < range.end)
+            {
+                if removed.start > start {
+                    ranges.push(start..removed.start);
+                }
+                start = removed.end;
+            }
+            if start < range.end {
+                ranges.push(start..range.end);
+            }
+        }
+
+        BitSet { ranges }
+    }
+}
+
+impl FromIterator<Range<usize>> for BitSet {
+    fn from_iter<I: IntoIterator<Item = Range<usize>>>(iter: I) -> Self {
+        let mut set = BitSet::new();
+        for range in iter {
+            set.insert(range);
+        }
+        set
+    }
+}
+
+// The ranges with inclusive ends, as bit ranges are written in bit specifications
+impl Display for BitSet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{{")?;
+        for (i, range) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match range.len() {
+                1 => write!(f, "{}", range.start)?,
+                _ => write!(f, "{}..{}", range.start, range.end - 1)?,
+            }
+        }
+        write!(f, "}}")
+    }
+}
+
+impl BitSpec {
+    /// The absolute bits used by all repetitions with words of `word_bits` bits.
+    ///
+    /// Variable repeats give the bits of the maximum number of repetitions.
+    /// The bits of an indirect specification are relative to the word pointed to.
+    /// Words of 0 bits give [`Error::InvalidWordSize`].
+    pub fn bit_set(&self, word_bits: usize) -> Result<BitSet, Error> {
+        check_word_bits(word_bits)?;
+        let offset = self.bit_offset(word_bits);
+        let width = self.bit_width(word_bits);
+        let stride = self.word_count() * word_bits;
+
+        Ok((0..self.repeat.max_repeats())
+            .map(|element| {
+                let start = offset + element * stride;
+                start..start + width
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    #[cfg(not(feature = "std"))]
+    use std::prelude::rust_2021::*;
+
+    #[test]
+    fn test_insert() {
+        let mut set = BitSet::new();
+        set.insert(8..12);
+        set.insert(0..4);
+        set.insert(20..24);
+        set.insert(5..5);
+        assert_eq!(set.ranges(), [0..4, 8..12, 20..24]);
+
+        set.insert(4..8);
+        assert_eq!(set.ranges(), [0..12, 20..24]);
+        set.insert(10..30);
+        assert_eq!(set.to_string(), "{0..29}");
+        assert_eq!(set.len(), 30);
+        assert!(set.contains_bit(29));
+        assert!(!set.contains_bit(30));
+    }
+
+    #[test]
+    fn test_bit_set() {
+        let set = parse("1[4]..2[3];3").unwrap().bit_set(8).unwrap();
+        assert_eq!(set.ranges(), [12..20, 28..36, 44..52]);
+        assert_eq!(set.to_string(), "{12..19, 28..35, 44..51}");
+
+        let set = parse("2[];(0[])<=3").unwrap().bit_set(16).unwrap();
+        assert_eq!(set.to_string(), "{32..79}");
+
+        assert_eq!(parse("[5]").unwrap().bit_set(8).unwrap().to_string(), "{5}");
+        assert_eq!(parse("[]").unwrap().bit_set(0), Err(Error::InvalidWordSize));
+    }
+
+    #[test]
+    fn test_operations() {
+        let a: BitSet = [0..8, 16..24].into_iter().collect();
+        let mut b = BitSet::new();
+        b.insert(4..20);
+        let mut gap = BitSet::new();
+        gap.insert(8..16);
+
+        assert!(a.overlaps(&b));
+        assert!(!a.overlaps(&gap));
+        assert!(a.contains(&[2..6, 18..20].into_iter().collect()));
+        assert!(!a.contains(&b));
+        assert!(a.contains(&BitSet::new()));
+
+        assert_eq!(a.intersection(&b).ranges(), [4..8, 16..20]);
+        assert_eq!(a.union(&b).to_string(), "{0..23}");
+        assert_eq!(a.difference(&b).ranges(), [0..4, 20..24]);
+        assert_eq!(b.difference(&a), gap);
+        assert!(a.difference(&a).is_empty());
+    }
+}
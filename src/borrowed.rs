@@ -0,0 +1,282 @@
+//! Bit specifications that borrow from the text they were parsed from.
+//!
+//! Parsing a [`BitSpecRef`] does not allocate: the names of an enumeration
+//! refer to the input instead of being copied into strings. This makes it
+//! suitable for parsing many specifications, and for targets without
+//! allocation. With the `alloc` feature [`BitSpecRef::to_bit_spec`] converts it
+//! to a [`BitSpec`]:
+//! ```
+//! let text = String::from("0[0..1]:{0=Off,1=On}");
+//! let bit_spec = bit_lang::parse_ref(&text).unwrap();
+//! assert_eq!(bit_spec.to_string(), "0[0..1]:{0=Off,1=On}");
+//! #[cfg(feature = "alloc")]
+//! assert_eq!(bit_spec.to_bit_spec(), bit_lang::parse(&text).unwrap());
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::fmt::Display;
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, opt, value},
+    sequence::{delimited, preceded, tuple},
+    IResult,
+};
+
+use crate::parser::{self, Repeat, Word};
+#[cfg(feature = "alloc")]
+use crate::parser::{BitSpec, FieldType};
+
+/// The variants of an enumeration as written in the bit specification,
+/// e.g. `0=Off,1=On`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Variants<'a> {
+    text: &'a str,
+}
+
+impl<'a> Variants<'a> {
+    // The text must have been recognised by `parser::variants`
+    pub(crate) fn new(text: &'a str) -> Self {
+        Variants { text }
+    }
+
+    /// The variants as written, without the braces.
+    pub fn as_str(&self) -> &'a str {
+        self.text
+    }
+
+    /// The value and name of each variant in the order written.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &'a str)> {
+        let mut remaining = self.text;
+        core::iter::from_fn(move || {
+            let (rest, variant) = parser::enum_variant(remaining).ok()?;
+            remaining = opt(parser::variant_separator)(rest).map_or(rest, |(rest, _)| rest);
+            Some(variant)
+        })
+    }
+
+    /// The value of the variant called `name`.
+    pub fn value(&self, name: &str) -> Option<u64> {
+        self.iter()
+            .find(|(_, variant)| *variant == name)
+            .map(|(value, _)| value)
+    }
+
+    /// The name of the variant with `value`.
+    pub fn name(&self, value: u64) -> Option<&'a str> {
+        self.iter()
+            .find(|(variant, _)| *variant == value)
+            .map(|(_, name)| name)
+    }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn to_vec(self) -> Vec<(u64, String)> {
+        self.iter()
+            .map(|(value, name)| (value, name.into()))
+            .collect()
+    }
+}
+
+/// A field type whose enumeration borrows from the input.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FieldTypeRef<'a> {
+    Bool,
+    F16,
+    F32,
+    Fixed {
+        integer_bits: u8,
+        fractional_bits: u8,
+    },
+    Enum(Variants<'a>),
+    Unsigned,
+}
+
+impl FieldTypeRef<'_> {
+    /// Convert to a field type that owns the names of an enumeration.
+    #[cfg(feature = "alloc")]
+    pub fn to_field_type(&self) -> FieldType {
+        match *self {
+            FieldTypeRef::Bool => FieldType::Bool,
+            FieldTypeRef::F16 => FieldType::F16,
+            FieldTypeRef::F32 => FieldType::F32,
+            FieldTypeRef::Fixed {
+                integer_bits,
+                fractional_bits,
+            } => FieldType::Fixed {
+                integer_bits,
+                fractional_bits,
+            },
+            FieldTypeRef::Enum(variants) => FieldType::Enum(variants.to_vec()),
+            FieldTypeRef::Unsigned => FieldType::Unsigned,
+        }
+    }
+}
+
+/// A bit specification that borrows from the input it was parsed from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BitSpecRef<'a> {
+    pub pointer: Option<Word>,
+    pub start: Word,
+    pub end: Option<Word>,
+    pub repeat: Repeat,
+    pub field_type: FieldTypeRef<'a>,
+}
+
+impl BitSpecRef<'_> {
+    /// Convert to a bit specification that does not borrow from the input.
+    #[cfg(feature = "alloc")]
+    pub fn to_bit_spec(&self) -> BitSpec {
+        BitSpec {
+            pointer: self.pointer.clone(),
+            start: self.start.clone(),
+            end: self.end.clone(),
+            repeat: self.repeat.clone(),
+            field_type: self.field_type.to_field_type(),
+        }
+    }
+}
+
+impl Display for FieldTypeRef<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FieldTypeRef::Bool => write!(f, ":bool"),
+            FieldTypeRef::F16 => write!(f, ":f16"),
+            FieldTypeRef::F32 => write!(f, ":f32"),
+            FieldTypeRef::Fixed {
+                integer_bits,
+                fractional_bits,
+            } => write!(f, ":q{integer_bits}.{fractional_bits}"),
+            FieldTypeRef::Enum(variants) => {
+                write!(f, ":{{")?;
+                for (i, (value, name)) in variants.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}={name}")?;
+                }
+                write!(f, "}}")
+            }
+            FieldTypeRef::Unsigned => Ok(()),
+        }
+    }
+}
+
+impl Display for BitSpecRef<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.pointer {
+            Some(pointer) if self.start.index == 0 => {
+                write!(f, "@({pointer})[{}]", self.start.bit_range)?
+            }
+            Some(pointer) => write!(f, "@({pointer}){}", self.start)?,
+            None => write!(f, "{}", self.start)?,
+        }
+        if let Some(end) = &self.end {
+            write!(f, "..{end}")?;
+        }
+        write!(f, "{}{}", self.repeat, self.field_type)
+    }
+}
+
+// field_type = ":" ("bool" | "f16" | "f32" | fixed_point | enumeration) ;
+fn field_type(input: &str) -> IResult<&str, FieldTypeRef<'_>> {
+    preceded(
+        tag(":"),
+        alt((
+            value(FieldTypeRef::Bool, tag("bool")),
+            value(FieldTypeRef::F16, tag("f16")),
+            value(FieldTypeRef::F32, tag("f32")),
+            map(parser::fixed_point, |(integer_bits, fractional_bits)| {
+                FieldTypeRef::Fixed {
+                    integer_bits,
+                    fractional_bits,
+                }
+            }),
+            map(
+                delimited(char('{'), parser::variants, char('}')),
+                |variants| FieldTypeRef::Enum(Variants::new(variants)),
+            ),
+        )),
+    )(input)
+}
+
+/// Parse a bit specification that borrows from `input`.
+pub fn bit_spec_ref(input: &str) -> IResult<&str, BitSpecRef<'_>> {
+    let (remaining, ((pointer, start), end, repeat, field_type)) = tuple((
+        parser::start_word,
+        opt(preceded(tag(".."), parser::word)),
+        map(opt(parser::repeat), |r| r.unwrap_or(Repeat::None)),
+        map(opt(field_type), |t| t.unwrap_or(FieldTypeRef::Unsigned)),
+    ))(input)?;
+
+    Ok((
+        remaining,
+        BitSpecRef {
+            pointer,
+            start,
+            end,
+            repeat,
+            field_type,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::BitRange;
+    #[cfg(not(feature = "std"))]
+    use std::prelude::rust_2021::*;
+
+    #[test]
+    fn test_bit_spec_ref() {
+        let text = "1[4..7]..2[0];(0[])<4:{0=Off, 1=On,2=Auto_1}";
+        let (remaining, r) = bit_spec_ref(text).unwrap();
+        assert_eq!(remaining, "");
+        assert_eq!(
+            r.start,
+            Word {
+                index: 1,
+                bit_range: BitRange::Range(4, 7)
+            }
+        );
+
+        let FieldTypeRef::Enum(variants) = r.field_type else {
+            core::panic!("not an enumeration");
+        };
+        assert!(text.contains(variants.as_str()));
+        assert_eq!(
+            variants.iter().collect::<Vec<_>>(),
+            [(0, "Off"), (1, "On"), (2, "Auto_1")]
+        );
+        assert_eq!(variants.value("On"), Some(1));
+        assert_eq!(variants.name(2), Some("Auto_1"));
+        assert_eq!(variants.name(3), None);
+
+        // The names refer to the input rather than a copy
+        let name = variants.name(0).unwrap();
+        assert!(text.as_bytes().as_ptr_range().contains(&name.as_ptr()));
+
+        assert_eq!(r.to_string(), "1[4..7]..2[0];(0[])<4:{0=Off,1=On,2=Auto_1}");
+        assert_eq!(bit_spec_ref("0[0..1]:{}").unwrap().0, ":{}");
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_to_bit_spec() {
+        for text in [
+            "5",
+            "3[4..7]..6[0..5];48",
+            "4[]..7[];(3[])<=49",
+            "5[0b1101_0001]",
+            "1[]..2[]:q8.8",
+            "[7]:bool",
+            "[0..1]:{0=Off,1=On}",
+            "@(2[])[0..3]..1[];3",
+        ] {
+            let (_, r) = bit_spec_ref(text).unwrap();
+            assert_eq!(r.to_bit_spec(), crate::parse(text).unwrap());
+        }
+    }
+}
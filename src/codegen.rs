@@ -0,0 +1,128 @@
+//! Generation of source code from layouts.
+
+pub mod c;
+pub mod rust;
+
+// The bits of a value that are in a single word
+#[derive(Debug, PartialEq)]
+pub(crate) struct Part {
+    // The word relative to the first word of the repetition
+    pub word: usize,
+    // The first bit in the word
+    pub shift: usize,
+    pub width: usize,
+    // The first bit in the value
+    pub value_shift: usize,
+}
+
+impl Part {
+    // The bits in the word, before shifting
+    pub fn mask(&self) -> u64 {
+        if self.width >= 64 {
+            u64::MAX
+        } else {
+            (1 << self.width) - 1
+        }
+    }
+}
+
+// The parts of the `width` bits from the absolute bit `offset`, from the
+// least significant bits of the value
+pub(crate) fn parts(offset: usize, width: usize, word_bits: usize) -> Vec<Part> {
+    let end = offset + width;
+    let first_word = offset / word_bits;
+
+    let mut parts = Vec::new();
+    let mut bit = offset;
+    while bit < end {
+        let shift = bit % word_bits;
+        let width = (word_bits - shift).min(end - bit);
+        parts.push(Part {
+            word: bit / word_bits - first_word,
+            shift,
+            width,
+            value_shift: bit - offset,
+        });
+        bit += width;
+    }
+
+    parts
+}
+
+// The number of bits of the smallest unsigned integer type that holds `width` bits
+pub(crate) fn integer_bits(width: usize) -> usize {
+    match width {
+        0..=8 => 8,
+        9..=16 => 16,
+        17..=32 => 32,
+        _ => 64,
+    }
+}
+
+// Convert a name in camel case, such as a layout name, to snake case
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut previous: Option<char> = None;
+
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit()) {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+        previous = Some(c);
+    }
+
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_parts() {
+        let bit_spec = parse("2[6]..4[1];3").unwrap();
+        let width = bit_spec.bit_width(8);
+
+        assert_eq!(
+            parts(bit_spec.bit_offset(8), width, 8),
+            vec![
+                Part {
+                    word: 0,
+                    shift: 6,
+                    width: 2,
+                    value_shift: 0
+                },
+                Part {
+                    word: 1,
+                    shift: 0,
+                    width: 8,
+                    value_shift: 2
+                },
+                Part {
+                    word: 2,
+                    shift: 0,
+                    width: 2,
+                    value_shift: 10
+                },
+            ]
+        );
+        assert_eq!(
+            parts(bit_spec.bit_offset(16), bit_spec.bit_width(16), 16).len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(snake_case("SensorFrame"), "sensor_frame");
+        assert_eq!(snake_case("Frame2Status"), "frame2_status");
+        assert_eq!(snake_case("ADC"), "adc");
+        assert_eq!(snake_case("sample_count"), "sample_count");
+    }
+}
@@ -0,0 +1,265 @@
+//! C headers for reading and writing the fields of a layout.
+//!
+//! For each field `FIELD` of the layout `LAYOUT` the header defines:
+//! * `LAYOUT_FIELD_WORD` the index of the first word,
+//! * `LAYOUT_FIELD_SHIFT` the first bit in that word,
+//! * `LAYOUT_FIELD_MASK` the bits of the field in the first word,
+//! * `LAYOUT_FIELD_WIDTH` the number of bits,
+//! * `LAYOUT_FIELD_WORDS` the number of words covered by the field,
+//!
+//! and static inline `layout_field_get` and `layout_field_set` functions over a
+//! byte buffer, with words in little endian order. Repeated fields also define
+//! `LAYOUT_FIELD_REPEATS`, the maximum number of repetitions, and take the
+//! repetition as an argument. Fields with a variable number of repeats have a
+//! `layout_field_count` function, and literals define `LAYOUT_FIELD_VALUE`.
+//! `LAYOUT_MAX_SIZE` is the number of bytes needed for all the fields. Layout
+//! and field names are written in snake case, so `sampleCount` in
+//! `SensorFrame` gives `sensor_frame_sample_count_get`.
+
+use std::fmt::Write;
+
+use super::{integer_bits, parts, snake_case, Part};
+use crate::layout::{Field, Layout};
+use crate::parser::Repeat;
+use crate::Error;
+
+/// Generate a C header for the layout.
+pub fn header(layout: &Layout) -> Result<String, Error> {
+    let word_bits = layout.word_bits;
+    if !matches!(word_bits, 8 | 16 | 32 | 64) {
+        return Err(Error::InvalidWordSize);
+    }
+    layout.check_supported()?;
+    for field in &layout.fields {
+        field.bit_spec.validate(word_bits)?;
+        field.bit_spec.check_direct()?;
+    }
+
+    let prefix = snake_case(&layout.name);
+    let upper = prefix.to_ascii_uppercase();
+    let word_type = format!("uint{word_bits}_t");
+    let mut c = String::new();
+
+    writeln!(
+        c,
+        "/* Generated by bit-lang from the layout {}. */",
+        layout.name
+    )
+    .unwrap();
+    writeln!(c, "#ifndef {upper}_H").unwrap();
+    writeln!(c, "#define {upper}_H").unwrap();
+    writeln!(c).unwrap();
+    writeln!(c, "#include <stddef.h>").unwrap();
+    writeln!(c, "#include <stdint.h>").unwrap();
+    writeln!(c).unwrap();
+    writeln!(c, "#define {upper}_MAX_SIZE {}", layout.max_size()).unwrap();
+    writeln!(c).unwrap();
+
+    // Words are stored in little endian order
+    let word_bytes = word_bits / 8;
+    writeln!(
+        c,
+        "static inline {word_type} {prefix}_load(const uint8_t *data, size_t word)\n{{\n    \
+         {word_type} value = 0;\n    \
+         for (size_t i = 0; i < {word_bytes}; i++) {{\n        \
+         value |= ({word_type})data[word * {word_bytes} + i] << (8 * i);\n    \
+         }}\n    \
+         return value;\n}}\n"
+    )
+    .unwrap();
+    writeln!(
+        c,
+        "static inline void {prefix}_store(uint8_t *data, size_t word, {word_type} value)\n{{\n    \
+         for (size_t i = 0; i < {word_bytes}; i++) {{\n        \
+         data[word * {word_bytes} + i] = (uint8_t)(value >> (8 * i));\n    \
+         }}\n}}"
+    )
+    .unwrap();
+
+    for field in &layout.fields {
+        writeln!(c).unwrap();
+        field_definitions(&mut c, field, &prefix, word_bits);
+    }
+
+    writeln!(c).unwrap();
+    writeln!(c, "#endif /* {upper}_H */").unwrap();
+    Ok(c)
+}
+
+fn field_definitions(c: &mut String, field: &Field, prefix: &str, word_bits: usize) {
+    let word_type = format!("uint{word_bits}_t");
+    let bit_spec = &field.bit_spec;
+    let name = format!("{prefix}_{}", snake_case(&field.name).to_ascii_lowercase());
+    let upper = name.to_ascii_uppercase();
+    let offset = bit_spec.bit_offset(word_bits);
+    let width = bit_spec.bit_width(word_bits);
+    let value_parts = parts(offset, width, word_bits);
+    let suffix = suffix(word_bits);
+
+    writeln!(c, "/* {}: {bit_spec} */", field.name).unwrap();
+    writeln!(c, "#define {upper}_WORD {}", offset / word_bits).unwrap();
+    writeln!(c, "#define {upper}_SHIFT {}", value_parts[0].shift).unwrap();
+    writeln!(
+        c,
+        "#define {upper}_MASK {:#X}{suffix}",
+        value_parts[0].mask() << value_parts[0].shift
+    )
+    .unwrap();
+    writeln!(c, "#define {upper}_WIDTH {width}").unwrap();
+    writeln!(c, "#define {upper}_WORDS {}", bit_spec.word_count()).unwrap();
+    let repeated = bit_spec.repeat != Repeat::None;
+    if repeated {
+        writeln!(
+            c,
+            "#define {upper}_REPEATS {}",
+            bit_spec.repeat.max_repeats()
+        )
+        .unwrap();
+    }
+    if let Some(value) = bit_spec.literal().map(|literal| literal.value()) {
+        writeln!(c, "#define {upper}_VALUE {value:#X}ULL").unwrap();
+    }
+    writeln!(c).unwrap();
+
+    if let Repeat::Variable { word, .. } = &bit_spec.repeat {
+        let width = word.bit_width(word_bits);
+        let value_type = format!("uint{}_t", integer_bits(width));
+        let word_index = word.bit_offset(word_bits) / word_bits;
+        let count_parts = parts(word.bit_offset(word_bits), width, word_bits);
+        writeln!(
+            c,
+            "static inline size_t {name}_count(const uint8_t *data)\n{{\n    \
+             size_t word = {word_index};\n    \
+             {value_type} value = 0;"
+        )
+        .unwrap();
+        write_loads(c, &count_parts, prefix, &value_type, suffix);
+        writeln!(c, "    return value;\n}}\n").unwrap();
+    }
+
+    let value_type = format!("uint{}_t", integer_bits(width));
+    let element = if repeated { ", size_t element" } else { "" };
+    let first_word = if repeated {
+        format!("{upper}_WORD + element * {upper}_WORDS")
+    } else {
+        format!("{upper}_WORD")
+    };
+
+    writeln!(
+        c,
+        "static inline {value_type} {name}_get(const uint8_t *data{element})\n{{\n    \
+         size_t word = {first_word};\n    \
+         {value_type} value = 0;"
+    )
+    .unwrap();
+    write_loads(c, &value_parts, prefix, &value_type, suffix);
+    writeln!(c, "    return value;\n}}\n").unwrap();
+
+    writeln!(
+        c,
+        "static inline void {name}_set(uint8_t *data{element}, {value_type} value)\n{{\n    \
+         size_t word = {first_word};"
+    )
+    .unwrap();
+    for part in &value_parts {
+        let word = word_expression(part);
+        writeln!(
+            c,
+            "    {prefix}_store(data, {word}, ({word_type})(({prefix}_load(data, {word}) & ~({mask:#X}{suffix} << {shift})) \
+             | ((value >> {value_shift}) & {mask:#X}{suffix}) << {shift}));",
+            mask = part.mask(),
+            shift = part.shift,
+            value_shift = part.value_shift
+        )
+        .unwrap();
+    }
+    writeln!(c, "}}").unwrap();
+}
+
+// Assemble `value` from the parts in the words from `word`
+fn write_loads(c: &mut String, parts: &[Part], prefix: &str, value_type: &str, suffix: &str) {
+    for part in parts {
+        writeln!(
+            c,
+            "    value |= ({value_type})(({prefix}_load(data, {}) >> {}) & {:#X}{suffix}) << {};",
+            word_expression(part),
+            part.shift,
+            part.mask(),
+            part.value_shift
+        )
+        .unwrap();
+    }
+}
+
+fn word_expression(part: &Part) -> String {
+    match part.word {
+        0 => "word".to_string(),
+        n => format!("word + {n}"),
+    }
+}
+
+// The suffix of constants of the word type
+fn suffix(word_bits: usize) -> &'static str {
+    if word_bits == 64 {
+        "ULL"
+    } else {
+        "u"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn test_header() {
+        let layouts = layout::parse(
+            "layout SensorFrame {
+                sync: [0xA5]
+                mode: 1[4..7]
+                samples: 2[6]..3[1];(1[0..1])<3
+            }",
+        )
+        .unwrap();
+
+        let header = header(&layouts[0]).unwrap();
+        assert!(header.contains("#ifndef SENSOR_FRAME_H\n"));
+        assert!(header.contains("#define SENSOR_FRAME_MAX_SIZE 6\n"));
+        assert!(header.contains("#define SENSOR_FRAME_SYNC_VALUE 0xA5ULL\n"));
+        assert!(header.contains(
+            "#define SENSOR_FRAME_MODE_WORD 1
+#define SENSOR_FRAME_MODE_SHIFT 4
+#define SENSOR_FRAME_MODE_MASK 0xF0u
+#define SENSOR_FRAME_MODE_WIDTH 4
+#define SENSOR_FRAME_MODE_WORDS 1
+"
+        ));
+        assert!(header.contains("#define SENSOR_FRAME_SAMPLES_REPEATS 2\n"));
+        assert!(header.contains(
+            "static inline uint8_t sensor_frame_samples_get(const uint8_t *data, size_t element)
+{
+    size_t word = SENSOR_FRAME_SAMPLES_WORD + element * SENSOR_FRAME_SAMPLES_WORDS;
+    uint8_t value = 0;
+    value |= (uint8_t)((sensor_frame_load(data, word) >> 6) & 0x3u) << 0;
+    value |= (uint8_t)((sensor_frame_load(data, word + 1) >> 0) & 0x3u) << 2;
+    return value;
+}
+"
+        ));
+        assert!(
+            header.contains("static inline size_t sensor_frame_samples_count(const uint8_t *data)")
+        );
+
+        // Field names are in snake case like the layout name
+        let layouts = layout::parse("layout Counts { sampleCount: 0[] }").unwrap();
+        let header = super::header(&layouts[0]).unwrap();
+        assert!(header.contains("#define COUNTS_SAMPLE_COUNT_WORD 0\n"));
+        assert!(
+            header.contains("static inline uint8_t counts_sample_count_get(const uint8_t *data)")
+        );
+
+        let layouts = layout::parse("layout Odd word_bits=24 { a: 0[] }").unwrap();
+        assert_eq!(super::header(&layouts[0]), Err(Error::InvalidWordSize));
+    }
+}
@@ -0,0 +1,796 @@
+//! Rust modules for reading and writing the fields of layouts, for use from
+//! build scripts as an alternative to the procedural macros.
+//!
+//! Each layout becomes a struct wrapping a buffer, `SensorFrame<T>`, with a
+//! getter and a `set_` method for each field. The getters need `T: AsRef<[u8]>`
+//! and the setters `T: AsMut<[u8]>`, so the same struct works over `&[u8]`,
+//! `&mut [u8]`, arrays and vectors. The types of the values follow the field
+//! types: `bool`, `f32`, `f64` for fixed point and the smallest unsigned integer
+//! otherwise. Repeated fields take the repetition as an argument.
+//!
+//! The struct also has a `SIZE` constant from [`Layout::max_size`], a constant for
+//! each literal, a `validate` method checking the literals and repeat counts,
+//! and a `set_literals` method.
+//!
+//! From `build.rs`:
+//! ```no_run
+//! let out_dir = std::env::var("OUT_DIR").unwrap();
+//! bit_lang::codegen::rust::generate_file("frames.layout", format!("{out_dir}/frames.rs")).unwrap();
+//! println!("cargo:rerun-if-changed=frames.layout");
+//! ```
+//! and then in the crate:
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/frames.rs"));
+//! ```
+
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+use super::{integer_bits, parts, snake_case, Part};
+use crate::layout::{self, Field, Layout};
+use crate::parser::{FieldType, Repeat};
+use crate::Error;
+
+const KEYWORDS: [&str; 42] = [
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+    "try", "type", "unsafe", "use", "where", "while", "yield", "macro",
+];
+
+// Keywords that cannot be raw identifiers
+const PATH_KEYWORDS: [&str; 4] = ["crate", "self", "Self", "super"];
+
+/// Generate a Rust module with a struct for each layout.
+///
+/// Getters for fields named after keywords are raw identifiers, such as
+/// `r#type`, or have an underscore after them for `self`, `Self`, `super` and
+/// `crate`. Fields that would give two items of the same name, such as a literal
+/// `size` and the `SIZE` constant, give [`Error::DuplicateField`].
+pub fn module(layouts: &[Layout]) -> Result<String, Error> {
+    let mut rust = String::from("// Generated by bit-lang from a layout file. Do not edit.\n");
+
+    for layout in layouts {
+        if !matches!(layout.word_bits, 8 | 16 | 32 | 64) {
+            return Err(Error::InvalidWordSize);
+        }
+        layout.check_supported()?;
+        for field in &layout.fields {
+            field.bit_spec.validate(layout.word_bits)?;
+            field.bit_spec.check_direct()?;
+        }
+        check_names(layout)?;
+        writeln!(rust).unwrap();
+        layout_struct(&mut rust, layout);
+    }
+
+    rust.push_str(
+        "
+#[allow(dead_code)]
+fn load_word(data: &[u8], word: usize, word_bytes: usize) -> u64 {
+    data[word * word_bytes..(word + 1) * word_bytes]
+        .iter()
+        .rev()
+        .fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+#[allow(dead_code)]
+fn store_word(data: &mut [u8], word: usize, word_bytes: usize, value: u64) {
+    for (i, byte) in data[word * word_bytes..(word + 1) * word_bytes]
+        .iter_mut()
+        .enumerate()
+    {
+        *byte = (value >> (8 * i)) as u8;
+    }
+}
+",
+    );
+
+    Ok(rust)
+}
+
+/// Generate a Rust module from the layout file at `input`, writing it to `output`.
+///
+/// Errors in the layout file are returned as [`io::ErrorKind::InvalidData`].
+pub fn generate_file(input: impl AsRef<Path>, output: impl AsRef<Path>) -> io::Result<()> {
+    let input = input.as_ref();
+    let text = std::fs::read_to_string(input)?;
+    let invalid = |error: Error| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {error}", input.display()),
+        )
+    };
+
+    let layouts = layout::parse(&text).map_err(invalid)?;
+    let rust = module(&layouts).map_err(invalid)?;
+
+    std::fs::write(output, rust)
+}
+
+// Check that no two fields give items of the same name, such as a literal
+// `size` and the `SIZE` constant, or `a_count` and the count of `a`
+fn check_names(layout: &Layout) -> Result<(), Error> {
+    let mut constants = vec!["SIZE".to_string()];
+    let mut methods: Vec<String> = ["new", "into_inner", "validate"].map(String::from).into();
+    if layout.fields.iter().any(|f| f.bit_spec.literal().is_some()) {
+        methods.push("set_literals".to_string());
+    }
+
+    for field in &layout.fields {
+        let bit_spec = &field.bit_spec;
+        let upper = snake_case(&field.name).to_ascii_uppercase();
+        if bit_spec.literal().is_some() {
+            constants.push(upper.clone());
+        } else {
+            methods.push(format!("set_{}", snake_case(&field.name)));
+        }
+        if bit_spec.repeat != Repeat::None {
+            constants.push(format!("{upper}_REPEATS"));
+        }
+        if let FieldType::Enum(variants) = &bit_spec.field_type {
+            for (_, variant) in variants {
+                constants.push(format!(
+                    "{upper}_{}",
+                    snake_case(variant).to_ascii_uppercase()
+                ));
+            }
+        }
+        if let Repeat::Variable { .. } = bit_spec.repeat {
+            methods.push(format!("{}_count", snake_case(&field.name)));
+        }
+        let method = method_name(&field.name);
+        methods.push(method.trim_start_matches("r#").to_string());
+    }
+
+    for names in [&mut constants, &mut methods] {
+        names.sort();
+        if names.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(Error::DuplicateField);
+        }
+    }
+
+    Ok(())
+}
+
+fn layout_struct(rust: &mut String, layout: &Layout) {
+    let name = identifier(&layout.name);
+
+    writeln!(
+        rust,
+        "/// The `{}` layout over a buffer.
+///
+/// The accessors panic if the buffer is too small for the bits they use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct {name}<T> {{
+    buffer: T,
+}}
+
+impl<T> {name}<T> {{
+    /// The number of bytes needed to hold all the fields.
+    pub const SIZE: usize = {};",
+        layout.name,
+        layout.max_size()
+    )
+    .unwrap();
+    for field in &layout.fields {
+        field_constants(rust, field, layout.word_bits);
+    }
+    writeln!(
+        rust,
+        "
+    pub const fn new(buffer: T) -> Self {{
+        Self {{ buffer }}
+    }}
+
+    pub fn into_inner(self) -> T {{
+        self.buffer
+    }}
+}}
+
+impl<T: AsRef<[u8]>> {name}<T> {{"
+    )
+    .unwrap();
+
+    validate(rust, layout);
+    for field in &layout.fields {
+        getters(rust, field, layout.word_bits);
+    }
+    writeln!(
+        rust,
+        "}}\n\nimpl<T: AsRef<[u8]> + AsMut<[u8]>> {name}<T> {{"
+    )
+    .unwrap();
+    set_literals(rust, layout);
+    for field in &layout.fields {
+        if field.bit_spec.literal().is_none() {
+            setter(rust, field, layout.word_bits);
+        }
+    }
+    writeln!(rust, "}}").unwrap();
+}
+
+fn field_constants(rust: &mut String, field: &Field, word_bits: usize) {
+    let upper = snake_case(&field.name).to_ascii_uppercase();
+    let bit_spec = &field.bit_spec;
+
+    if let Some(value) = bit_spec.literal().map(|literal| literal.value()) {
+        let integer = integer_type(bit_spec.bit_width(word_bits));
+        writeln!(
+            rust,
+            "    /// The value of the `{}` literal.\n    pub const {upper}: {integer} = {value:#x};",
+            field.name
+        )
+        .unwrap();
+    }
+    if bit_spec.repeat != Repeat::None {
+        writeln!(
+            rust,
+            "    /// The maximum number of repetitions of `{}`.\n    pub const {upper}_REPEATS: usize = {};",
+            field.name,
+            bit_spec.repeat.max_repeats()
+        )
+        .unwrap();
+    }
+    if let FieldType::Enum(variants) = &bit_spec.field_type {
+        let integer = integer_type(bit_spec.bit_width(word_bits));
+        for (value, variant) in variants {
+            writeln!(
+                rust,
+                "    pub const {upper}_{}: {integer} = {value};",
+                snake_case(variant).to_ascii_uppercase()
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn validate(rust: &mut String, layout: &Layout) {
+    writeln!(
+        rust,
+        "    /// Check the literals and the number of repeats, returning the name
+    /// of the first field that is not valid.
+    pub fn validate(&self) -> Result<(), &'static str> {{"
+    )
+    .unwrap();
+
+    for field in &layout.fields {
+        let method = method_name(&field.name);
+        let upper = snake_case(&field.name).to_ascii_uppercase();
+        if let Repeat::Variable { .. } = field.bit_spec.repeat {
+            writeln!(
+                rust,
+                "        if self.{}_count() > Self::{upper}_REPEATS {{\n            return Err(\"{}\");\n        }}",
+                snake_case(&field.name),
+                field.name
+            )
+            .unwrap();
+        }
+        if field.bit_spec.literal().is_none() {
+            continue;
+        }
+        match field.bit_spec.repeat {
+            Repeat::None => writeln!(
+                rust,
+                "        if self.{method}() != Self::{upper} {{\n            return Err(\"{}\");\n        }}",
+                field.name
+            ),
+            _ => writeln!(
+                rust,
+                "        if (0..{}).any(|element| self.{method}(element) != Self::{upper}) {{\n            \
+                 return Err(\"{}\");\n        }}",
+                repeats_expression(field),
+                field.name
+            ),
+        }
+        .unwrap();
+    }
+
+    writeln!(rust, "        Ok(())\n    }}").unwrap();
+}
+
+fn set_literals(rust: &mut String, layout: &Layout) {
+    if layout.fields.iter().all(|f| f.bit_spec.literal().is_none()) {
+        return;
+    }
+    writeln!(
+        rust,
+        "    /// Write the values of the literals, for all possible repetitions.
+    pub fn set_literals(&mut self) {{
+        let word_bytes = {};
+        let data = self.buffer.as_mut();",
+        layout.word_bits / 8
+    )
+    .unwrap();
+
+    for field in &layout.fields {
+        if field.bit_spec.literal().is_none() {
+            continue;
+        }
+        let upper = snake_case(&field.name).to_ascii_uppercase();
+        let raw = match integer_bits(field.bit_spec.bit_width(layout.word_bits)) {
+            64 => format!("Self::{upper}"),
+            _ => format!("u64::from(Self::{upper})"),
+        };
+        let repeated = field.bit_spec.repeat != Repeat::None;
+        if repeated {
+            let repeats = field.bit_spec.repeat.max_repeats();
+            writeln!(rust, "        for element in 0..{repeats} {{").unwrap();
+        } else {
+            writeln!(rust, "        {{").unwrap();
+        }
+        writeln!(
+            rust,
+            "            let raw = {raw};\n            let word = {};",
+            first_word(field, layout.word_bits, repeated)
+        )
+        .unwrap();
+        write_stores(rust, field, layout.word_bits, "            ");
+        writeln!(rust, "        }}").unwrap();
+    }
+
+    writeln!(rust, "    }}").unwrap();
+}
+
+fn getters(rust: &mut String, field: &Field, word_bits: usize) {
+    let bit_spec = &field.bit_spec;
+    let method = method_name(&field.name);
+    let width = bit_spec.bit_width(word_bits);
+    let repeated = bit_spec.repeat != Repeat::None;
+    let upper = snake_case(&field.name).to_ascii_uppercase();
+
+    if let Repeat::Variable { word, .. } = &bit_spec.repeat {
+        let count_parts = parts(
+            word.bit_offset(word_bits),
+            word.bit_width(word_bits),
+            word_bits,
+        );
+        writeln!(
+            rust,
+            "
+    /// The number of repetitions of `{}`, from `{word}`.
+    pub fn {}_count(&self) -> usize {{
+        let data = self.buffer.as_ref();
+        let word = {};",
+            field.name,
+            snake_case(&field.name),
+            word.index
+        )
+        .unwrap();
+        writeln!(
+            rust,
+            "        {} as usize\n    }}",
+            load_expression(&count_parts, word_bits).operand()
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        rust,
+        "\n    /// `{}`: `{bit_spec}`{}",
+        field.name,
+        unit_note(field)
+    )
+    .unwrap();
+    let element = if repeated { ", element: usize" } else { "" };
+    writeln!(
+        rust,
+        "    pub fn {method}(&self{element}) -> {} {{",
+        value_type(&bit_spec.field_type, width)
+    )
+    .unwrap();
+    if repeated {
+        writeln!(rust, "        assert!(element < Self::{upper}_REPEATS);").unwrap();
+    }
+    writeln!(
+        rust,
+        "        let data = self.buffer.as_ref();\n        let word = {};",
+        first_word(field, word_bits, repeated)
+    )
+    .unwrap();
+    let value_parts = parts(bit_spec.bit_offset(word_bits), width, word_bits);
+    let raw = load_expression(&value_parts, word_bits).text;
+    match from_raw(&bit_spec.field_type, width).as_str() {
+        "raw" => writeln!(rust, "        {raw}\n    }}"),
+        value => writeln!(rust, "        let raw = {raw};\n        {value}\n    }}"),
+    }
+    .unwrap();
+}
+
+fn setter(rust: &mut String, field: &Field, word_bits: usize) {
+    let bit_spec = &field.bit_spec;
+    let width = bit_spec.bit_width(word_bits);
+    let repeated = bit_spec.repeat != Repeat::None;
+    let upper = snake_case(&field.name).to_ascii_uppercase();
+
+    writeln!(
+        rust,
+        "\n    /// Set `{}`, ignoring any bits of the value that do not fit.",
+        field.name
+    )
+    .unwrap();
+    let element = if repeated { ", element: usize" } else { "" };
+    writeln!(
+        rust,
+        "    pub fn set_{}(&mut self{element}, value: {}) {{",
+        snake_case(&field.name),
+        value_type(&bit_spec.field_type, width)
+    )
+    .unwrap();
+    if repeated {
+        writeln!(rust, "        assert!(element < Self::{upper}_REPEATS);").unwrap();
+    }
+    writeln!(
+        rust,
+        "        let word_bytes = {};\n        let data = self.buffer.as_mut();\n        \
+         let word = {};\n        let raw = {};",
+        word_bits / 8,
+        first_word(field, word_bits, repeated),
+        to_raw(&bit_spec.field_type, width)
+    )
+    .unwrap();
+    write_stores(rust, field, word_bits, "        ");
+    writeln!(rust, "    }}").unwrap();
+}
+
+// Write `raw` into the words of a repetition starting at `word`
+fn write_stores(rust: &mut String, field: &Field, word_bits: usize, indent: &str) {
+    let bit_spec = &field.bit_spec;
+    let value_parts = parts(
+        bit_spec.bit_offset(word_bits),
+        bit_spec.bit_width(word_bits),
+        word_bits,
+    );
+
+    for part in &value_parts {
+        let word = word_expression(part);
+        // Bits beyond the word are ignored when storing a whole word
+        let mut bits = Expr::atom("raw").shift_right(part.value_shift);
+        if part.width < word_bits {
+            bits = bits.and(part.mask());
+        }
+        let bits = bits.shift_left(part.shift);
+
+        if part.width == word_bits {
+            writeln!(
+                rust,
+                "{indent}store_word(data, {word}, word_bytes, {});",
+                bits.text
+            )
+            .unwrap();
+        } else {
+            let mask = Expr::atom(&format!("{:#x}", part.mask())).shift_left(part.shift);
+            writeln!(
+                rust,
+                "{indent}let kept = load_word(data, {word}, word_bytes) & !{};\n\
+                 {indent}store_word(data, {word}, word_bytes, kept | {});",
+                mask.operand(),
+                bits.operand()
+            )
+            .unwrap();
+        }
+    }
+}
+
+// The raw bits from the parts in the words from `word`
+fn load_expression(parts: &[Part], word_bits: usize) -> Expr {
+    let terms: Vec<Expr> = parts
+        .iter()
+        .map(|part| {
+            let word = format!(
+                "load_word(data, {}, {})",
+                word_expression(part),
+                word_bits / 8
+            );
+            let mut bits = Expr::atom(&word).shift_right(part.shift);
+            if part.width < word_bits {
+                bits = bits.and(part.mask());
+            }
+            bits.shift_left(part.value_shift)
+        })
+        .collect();
+
+    match terms.as_slice() {
+        [term] => Expr {
+            text: term.text.clone(),
+            atomic: term.atomic,
+        },
+        _ => Expr {
+            text: terms
+                .iter()
+                .map(Expr::operand)
+                .collect::<Vec<_>>()
+                .join(" | "),
+            atomic: false,
+        },
+    }
+}
+
+// Generated code for an expression, tracking whether it needs parentheses
+// when used as an operand so that no unnecessary ones are generated
+struct Expr {
+    text: String,
+    atomic: bool,
+}
+
+impl Expr {
+    fn atom(text: &str) -> Expr {
+        Expr {
+            text: text.to_string(),
+            atomic: true,
+        }
+    }
+
+    fn operand(&self) -> String {
+        if self.atomic {
+            self.text.clone()
+        } else {
+            format!("({})", self.text)
+        }
+    }
+
+    fn binary(&self, operator: &str, value: String) -> Expr {
+        Expr {
+            text: format!("{} {operator} {value}", self.operand()),
+            atomic: false,
+        }
+    }
+
+    fn shift_left(self, shift: usize) -> Expr {
+        match shift {
+            0 => self,
+            _ => self.binary("<<", shift.to_string()),
+        }
+    }
+
+    fn shift_right(self, shift: usize) -> Expr {
+        match shift {
+            0 => self,
+            _ => self.binary(">>", shift.to_string()),
+        }
+    }
+
+    fn and(self, mask: u64) -> Expr {
+        self.binary("&", format!("{mask:#x}"))
+    }
+}
+
+fn word_expression(part: &Part) -> String {
+    match part.word {
+        0 => "word".to_string(),
+        n => format!("word + {n}"),
+    }
+}
+
+// The index of the first word of the repetition `element`
+fn first_word(field: &Field, word_bits: usize, repeated: bool) -> String {
+    let index = field.bit_spec.bit_offset(word_bits) / word_bits;
+    let element = match field.bit_spec.word_count() {
+        1 => "element".to_string(),
+        words => format!("element * {words}"),
+    };
+
+    match (repeated, index) {
+        (false, _) => index.to_string(),
+        (true, 0) => element,
+        (true, _) => format!("{index} + {element}"),
+    }
+}
+
+fn repeats_expression(field: &Field) -> String {
+    match field.bit_spec.repeat {
+        Repeat::Variable { .. } => format!("self.{}_count()", snake_case(&field.name)),
+        _ => field.bit_spec.repeat.max_repeats().to_string(),
+    }
+}
+
+fn integer_type(width: usize) -> String {
+    format!("u{}", integer_bits(width))
+}
+
+fn value_type(field_type: &FieldType, width: usize) -> String {
+    match field_type {
+        FieldType::Bool => "bool".to_string(),
+        FieldType::F32 => "f32".to_string(),
+        FieldType::Fixed { .. } => "f64".to_string(),
+        FieldType::F16 | FieldType::Enum(_) | FieldType::Unsigned => integer_type(width),
+    }
+}
+
+// Convert the u64 `raw` to the value type
+fn from_raw(field_type: &FieldType, width: usize) -> String {
+    match field_type {
+        FieldType::Bool => "raw != 0".to_string(),
+        FieldType::F32 => "f32::from_bits(raw as u32)".to_string(),
+        FieldType::Fixed {
+            fractional_bits, ..
+        } => {
+            let shift = 64 - width;
+            format!(
+                "((raw << {shift}) as i64 >> {shift}) as f64 / {:?}",
+                (1u64 << fractional_bits) as f64
+            )
+        }
+        FieldType::F16 | FieldType::Enum(_) | FieldType::Unsigned => match integer_bits(width) {
+            64 => "raw".to_string(),
+            bits => format!("raw as u{bits}"),
+        },
+    }
+}
+
+// Convert `value` to the u64 raw bits
+fn to_raw(field_type: &FieldType, width: usize) -> String {
+    match field_type {
+        FieldType::Bool => "u64::from(value)".to_string(),
+        FieldType::F32 => "u64::from(value.to_bits())".to_string(),
+        FieldType::Fixed {
+            fractional_bits, ..
+        } => format!(
+            "(value * {:?}).round() as i64 as u64",
+            (1u64 << fractional_bits) as f64
+        ),
+        FieldType::F16 | FieldType::Enum(_) | FieldType::Unsigned => match integer_bits(width) {
+            64 => "value".to_string(),
+            _ => "u64::from(value)".to_string(),
+        },
+    }
+}
+
+fn unit_note(field: &Field) -> String {
+    match field
+        .scaling
+        .as_ref()
+        .and_then(|scaling| scaling.unit.as_ref())
+    {
+        Some(unit) => format!(", raw value of {unit}"),
+        None => String::new(),
+    }
+}
+
+// The getter, which is never a keyword
+fn method_name(name: &str) -> String {
+    identifier(&snake_case(name))
+}
+
+// A raw identifier for a keyword, or one with an underscore after it for the
+// keywords that cannot be raw
+fn identifier(name: &str) -> String {
+    if PATH_KEYWORDS.contains(&name) {
+        format!("{name}_")
+    } else if KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    // The module generated from `testdata/frames.layout`, compiled with the tests
+    #[allow(dead_code)]
+    mod frames {
+        include!("testdata/frames.rs");
+    }
+    use frames::{SensorFrame, Telemetry};
+
+    #[test]
+    fn test_module() {
+        let layouts = layout::parse(
+            "layout SensorFrame {
+                sync: [0xA5]
+                mode: 1[4..7]:{0=Off,1=On}
+                type: 1[0]:bool
+                samples: 2[6]..3[1];(1[1..3])<3
+            }",
+        )
+        .unwrap();
+
+        let rust = module(&layouts).unwrap();
+        assert!(rust.contains("pub struct SensorFrame<T> {"));
+        assert!(rust.contains("pub const SIZE: usize = 6;"));
+        assert!(rust.contains("pub const SYNC: u8 = 0xa5;"));
+        assert!(rust.contains("pub const MODE_ON: u8 = 1;"));
+        assert!(rust.contains("pub fn r#type(&self) -> bool {"));
+        assert!(rust.contains("pub fn set_type(&mut self, value: bool) {"));
+        assert!(rust.contains(
+            "    pub fn samples(&self, element: usize) -> u8 {
+        assert!(element < Self::SAMPLES_REPEATS);
+        let data = self.buffer.as_ref();
+        let word = 2 + element * 2;
+        let raw = ((load_word(data, word, 1) >> 6) & 0x3) | ((load_word(data, word + 1, 1) & 0x3) << 2);
+        raw as u8
+    }"
+        ));
+        assert!(rust.contains("if self.samples_count() > Self::SAMPLES_REPEATS {"));
+        assert!(!rust.contains("fn set_sync"));
+
+        let layouts = layout::parse("layout Odd word_bits=24 { a: 0[] }").unwrap();
+        assert_eq!(module(&layouts), Err(Error::InvalidWordSize));
+    }
+
+    #[test]
+    fn test_names() {
+        let layouts = layout::parse("layout Self { self: 0[] crate: 1[] mod: 2[] }").unwrap();
+        let rust = module(&layouts).unwrap();
+        assert!(rust.contains("pub struct Self_<T> {"));
+        assert!(rust.contains("pub fn self_(&self) -> u8 {"));
+        assert!(rust.contains("pub fn crate_(&self) -> u8 {"));
+        assert!(rust.contains("pub fn r#mod(&self) -> u8 {"));
+
+        for text in [
+            "layout A { size: 0[0x1] }",
+            "layout A { a: [];2 a_repeats: 2[0x1] }",
+            "layout A { a: 1[];(0[])<3 a_count: 4[] }",
+            "layout A { mode: 0[0..1]:{1=On} mode_on: 1[0x1] }",
+            "layout A { sync: [0xA5] literals: 1[] }",
+            "layout A { validate: 0[] }",
+        ] {
+            let layouts = layout::parse(text).unwrap();
+            assert_eq!(module(&layouts), Err(Error::DuplicateField), "{text}");
+        }
+    }
+
+    #[test]
+    fn test_generated() {
+        let layouts = layout::parse(include_str!("testdata/frames.layout")).unwrap();
+        assert_eq!(
+            module(&layouts).unwrap(),
+            include_str!("testdata/frames.rs"),
+            "testdata/frames.rs needs to be generated again"
+        );
+
+        // The generated accessors agree with encoding and decoding the layouts
+        let samples = ("samples", vec![Value::Unsigned(5), Value::Unsigned(10)]);
+        let values = [
+            ("mode", vec![Value::Enum("On".to_string())]),
+            ("type", vec![Value::Bool(true)]),
+            samples.clone(),
+            ("self", vec![Value::Unsigned(0x42)]),
+        ];
+        let data = layouts[0].encode(&values).unwrap();
+        let frame = SensorFrame::new(data.as_slice());
+        assert_eq!(frame.validate(), Ok(()));
+        assert_eq!(frame.mode(), SensorFrame::<()>::MODE_ON);
+        assert!(frame.r#type());
+        assert_eq!(frame.samples_count(), 2);
+        assert_eq!([frame.samples(0), frame.samples(1)], [5, 10]);
+        assert_eq!(frame.self_(), 0x42);
+
+        // The count of a variable repeat has no setter
+        let mut frame = SensorFrame::new(layouts[0].encode(&[samples]).unwrap());
+        frame.set_literals();
+        frame.set_mode(SensorFrame::<()>::MODE_ON);
+        frame.set_type(true);
+        frame.set_self(0x42);
+        assert_eq!(frame.into_inner(), data);
+
+        let mut frame = Telemetry::new([0u8; Telemetry::<()>::SIZE]);
+        frame.set_counter(0x12345678);
+        frame.set_temperature(-1.5);
+        frame.set_pressure(1013.25);
+        for element in 0..Telemetry::<()>::FLAGS_REPEATS {
+            frame.set_flags(element, element as u8 + 1);
+        }
+        let data = frame.into_inner();
+        let values: Vec<Vec<Value>> = layouts[1]
+            .fields
+            .iter()
+            .map(|field| field.decode(&data, 16).unwrap())
+            .collect();
+        assert_eq!(
+            values,
+            [
+                vec![Value::Unsigned(0x12345678)],
+                vec![Value::Float(-1.5)],
+                vec![Value::Float(1013.25)],
+                (1..=4).map(Value::Unsigned).collect(),
+            ]
+        );
+        assert_eq!(frame.temperature(), -1.5);
+        assert_eq!(frame.pressure(), 1013.25);
+    }
+}
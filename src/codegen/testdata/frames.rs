@@ -0,0 +1,270 @@
+// Generated by bit-lang from a layout file. Do not edit.
+
+/// The `SensorFrame` layout over a buffer.
+///
+/// The accessors panic if the buffer is too small for the bits they use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SensorFrame<T> {
+    buffer: T,
+}
+
+impl<T> SensorFrame<T> {
+    /// The number of bytes needed to hold all the fields.
+    pub const SIZE: usize = 7;
+    /// The value of the `sync` literal.
+    pub const SYNC: u8 = 0xa5;
+    pub const MODE_OFF: u8 = 0;
+    pub const MODE_ON: u8 = 1;
+    /// The maximum number of repetitions of `samples`.
+    pub const SAMPLES_REPEATS: usize = 2;
+
+    pub const fn new(buffer: T) -> Self {
+        Self { buffer }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+}
+
+impl<T: AsRef<[u8]>> SensorFrame<T> {
+    /// Check the literals and the number of repeats, returning the name
+    /// of the first field that is not valid.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.sync() != Self::SYNC {
+            return Err("sync");
+        }
+        if self.samples_count() > Self::SAMPLES_REPEATS {
+            return Err("samples");
+        }
+        Ok(())
+    }
+
+    /// `sync`: `0[0xA5]`
+    pub fn sync(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        let word = 0;
+        let raw = load_word(data, word, 1);
+        raw as u8
+    }
+
+    /// `mode`: `1[4..7]:{0=Off,1=On}`
+    pub fn mode(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        let word = 1;
+        let raw = (load_word(data, word, 1) >> 4) & 0xf;
+        raw as u8
+    }
+
+    /// `type`: `1[0]:bool`
+    pub fn r#type(&self) -> bool {
+        let data = self.buffer.as_ref();
+        let word = 1;
+        let raw = load_word(data, word, 1) & 0x1;
+        raw != 0
+    }
+
+    /// The number of repetitions of `samples`, from `1[1..3]`.
+    pub fn samples_count(&self) -> usize {
+        let data = self.buffer.as_ref();
+        let word = 1;
+        ((load_word(data, word, 1) >> 1) & 0x7) as usize
+    }
+
+    /// `samples`: `2[6]..3[1];(1[1..3])<3`
+    pub fn samples(&self, element: usize) -> u8 {
+        assert!(element < Self::SAMPLES_REPEATS);
+        let data = self.buffer.as_ref();
+        let word = 2 + element * 2;
+        let raw = ((load_word(data, word, 1) >> 6) & 0x3) | ((load_word(data, word + 1, 1) & 0x3) << 2);
+        raw as u8
+    }
+
+    /// `self`: `6[]`
+    pub fn self_(&self) -> u8 {
+        let data = self.buffer.as_ref();
+        let word = 6;
+        let raw = load_word(data, word, 1);
+        raw as u8
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> SensorFrame<T> {
+    /// Write the values of the literals, for all possible repetitions.
+    pub fn set_literals(&mut self) {
+        let word_bytes = 1;
+        let data = self.buffer.as_mut();
+        {
+            let raw = u64::from(Self::SYNC);
+            let word = 0;
+            store_word(data, word, word_bytes, raw);
+        }
+    }
+
+    /// Set `mode`, ignoring any bits of the value that do not fit.
+    pub fn set_mode(&mut self, value: u8) {
+        let word_bytes = 1;
+        let data = self.buffer.as_mut();
+        let word = 1;
+        let raw = u64::from(value);
+        let kept = load_word(data, word, word_bytes) & !(0xf << 4);
+        store_word(data, word, word_bytes, kept | ((raw & 0xf) << 4));
+    }
+
+    /// Set `type`, ignoring any bits of the value that do not fit.
+    pub fn set_type(&mut self, value: bool) {
+        let word_bytes = 1;
+        let data = self.buffer.as_mut();
+        let word = 1;
+        let raw = u64::from(value);
+        let kept = load_word(data, word, word_bytes) & !0x1;
+        store_word(data, word, word_bytes, kept | (raw & 0x1));
+    }
+
+    /// Set `samples`, ignoring any bits of the value that do not fit.
+    pub fn set_samples(&mut self, element: usize, value: u8) {
+        assert!(element < Self::SAMPLES_REPEATS);
+        let word_bytes = 1;
+        let data = self.buffer.as_mut();
+        let word = 2 + element * 2;
+        let raw = u64::from(value);
+        let kept = load_word(data, word, word_bytes) & !(0x3 << 6);
+        store_word(data, word, word_bytes, kept | ((raw & 0x3) << 6));
+        let kept = load_word(data, word + 1, word_bytes) & !0x3;
+        store_word(data, word + 1, word_bytes, kept | ((raw >> 2) & 0x3));
+    }
+
+    /// Set `self`, ignoring any bits of the value that do not fit.
+    pub fn set_self(&mut self, value: u8) {
+        let word_bytes = 1;
+        let data = self.buffer.as_mut();
+        let word = 6;
+        let raw = u64::from(value);
+        store_word(data, word, word_bytes, raw);
+    }
+}
+
+/// The `Telemetry` layout over a buffer.
+///
+/// The accessors panic if the buffer is too small for the bits they use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Telemetry<T> {
+    buffer: T,
+}
+
+impl<T> Telemetry<T> {
+    /// The number of bytes needed to hold all the fields.
+    pub const SIZE: usize = 18;
+    /// The maximum number of repetitions of `flags`.
+    pub const FLAGS_REPEATS: usize = 4;
+
+    pub const fn new(buffer: T) -> Self {
+        Self { buffer }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.buffer
+    }
+}
+
+impl<T: AsRef<[u8]>> Telemetry<T> {
+    /// Check the literals and the number of repeats, returning the name
+    /// of the first field that is not valid.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    /// `counter`: `0[]..1[]`
+    pub fn counter(&self) -> u32 {
+        let data = self.buffer.as_ref();
+        let word = 0;
+        let raw = load_word(data, word, 2) | (load_word(data, word + 1, 2) << 16);
+        raw as u32
+    }
+
+    /// `temperature`: `2[]:q8.8`
+    pub fn temperature(&self) -> f64 {
+        let data = self.buffer.as_ref();
+        let word = 2;
+        let raw = load_word(data, word, 2);
+        ((raw << 48) as i64 >> 48) as f64 / 256.0
+    }
+
+    /// `pressure`: `3[]..4[]:f32`
+    pub fn pressure(&self) -> f32 {
+        let data = self.buffer.as_ref();
+        let word = 3;
+        let raw = load_word(data, word, 2) | (load_word(data, word + 1, 2) << 16);
+        f32::from_bits(raw as u32)
+    }
+
+    /// `flags`: `5[0..3];4`
+    pub fn flags(&self, element: usize) -> u8 {
+        assert!(element < Self::FLAGS_REPEATS);
+        let data = self.buffer.as_ref();
+        let word = 5 + element;
+        let raw = load_word(data, word, 2) & 0xf;
+        raw as u8
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Telemetry<T> {
+
+    /// Set `counter`, ignoring any bits of the value that do not fit.
+    pub fn set_counter(&mut self, value: u32) {
+        let word_bytes = 2;
+        let data = self.buffer.as_mut();
+        let word = 0;
+        let raw = u64::from(value);
+        store_word(data, word, word_bytes, raw);
+        store_word(data, word + 1, word_bytes, raw >> 16);
+    }
+
+    /// Set `temperature`, ignoring any bits of the value that do not fit.
+    pub fn set_temperature(&mut self, value: f64) {
+        let word_bytes = 2;
+        let data = self.buffer.as_mut();
+        let word = 2;
+        let raw = (value * 256.0).round() as i64 as u64;
+        store_word(data, word, word_bytes, raw);
+    }
+
+    /// Set `pressure`, ignoring any bits of the value that do not fit.
+    pub fn set_pressure(&mut self, value: f32) {
+        let word_bytes = 2;
+        let data = self.buffer.as_mut();
+        let word = 3;
+        let raw = u64::from(value.to_bits());
+        store_word(data, word, word_bytes, raw);
+        store_word(data, word + 1, word_bytes, raw >> 16);
+    }
+
+    /// Set `flags`, ignoring any bits of the value that do not fit.
+    pub fn set_flags(&mut self, element: usize, value: u8) {
+        assert!(element < Self::FLAGS_REPEATS);
+        let word_bytes = 2;
+        let data = self.buffer.as_mut();
+        let word = 5 + element;
+        let raw = u64::from(value);
+        let kept = load_word(data, word, word_bytes) & !0xf;
+        store_word(data, word, word_bytes, kept | (raw & 0xf));
+    }
+}
+
+#[allow(dead_code)]
+fn load_word(data: &[u8], word: usize, word_bytes: usize) -> u64 {
+    data[word * word_bytes..(word + 1) * word_bytes]
+        .iter()
+        .rev()
+        .fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+#[allow(dead_code)]
+fn store_word(data: &mut [u8], word: usize, word_bytes: usize, value: u64) {
+    for (i, byte) in data[word * word_bytes..(word + 1) * word_bytes]
+        .iter_mut()
+        .enumerate()
+    {
+        *byte = (value >> (8 * i)) as u8;
+    }
+}
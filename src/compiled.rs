@@ -0,0 +1,384 @@
+//! Bit specifications compiled into precomputed plans for fast access.
+//!
+//! Compiling a [`BitSpec`] for a word size works out, for each word the bits
+//! are in, the byte offset of the word, the mask and the shifts needed. Reading
+//! or writing a value is then a loop over these steps without any allocation or
+//! matching on the bit specification.
+
+use crate::parser::{BitSpec, Repeat, Word};
+use crate::Error;
+
+// A value of up to 64 bits can start part way through a word so
+// covers at most 9 words of 8 bits.
+const MAX_STEPS: usize = 9;
+
+// The bits of a value that are in a single word
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Step {
+    byte_offset: usize,
+    mask: u64,
+    shift: u32,
+    value_shift: u32,
+}
+
+const EMPTY_STEP: Step = Step {
+    byte_offset: 0,
+    mask: 0,
+    shift: 0,
+    value_shift: 0,
+};
+
+// The steps needed to access a value
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Plan {
+    steps: [Step; MAX_STEPS],
+    len: usize,
+    word_bytes: usize,
+    // The number of bytes needed in the buffer
+    size: usize,
+}
+
+impl Plan {
+    // Plan the access to the bits from the absolute bit `offset` up to,
+    // but not including, the absolute bit `end`.
+    const fn new(offset: usize, end: usize, word_bits: usize) -> Result<Plan, Error> {
+        if end <= offset {
+            return Err(Error::InvalidBitSpec);
+        }
+        if end - offset > 64 {
+            return Err(Error::TooWide);
+        }
+
+        let word_bytes = word_bits / 8;
+        let mut steps = [EMPTY_STEP; MAX_STEPS];
+        let mut len = 0;
+        let mut bit = offset;
+        while bit < end {
+            let index = bit / word_bits;
+            let shift = bit % word_bits;
+            let n = if word_bits - shift < end - bit {
+                word_bits - shift
+            } else {
+                end - bit
+            };
+            steps[len] = Step {
+                byte_offset: index * word_bytes,
+                mask: mask(n),
+                shift: shift as u32,
+                value_shift: (bit - offset) as u32,
+            };
+            len += 1;
+            bit += n;
+        }
+
+        Ok(Plan {
+            steps,
+            len,
+            word_bytes,
+            size: end.div_ceil(word_bits) * word_bytes,
+        })
+    }
+
+    #[inline]
+    fn extract(&self, data: &[u8], base: usize) -> Result<u64, Error> {
+        if base + self.size > data.len() {
+            return Err(Error::OutOfBounds);
+        }
+
+        let mut value = 0;
+        for step in &self.steps[..self.len] {
+            let word = load(data, base + step.byte_offset, self.word_bytes);
+            value |= ((word >> step.shift) & step.mask) << step.value_shift;
+        }
+
+        Ok(value)
+    }
+
+    #[inline]
+    fn insert(&self, data: &mut [u8], base: usize, value: u64) -> Result<(), Error> {
+        if base + self.size > data.len() {
+            return Err(Error::OutOfBounds);
+        }
+
+        for step in &self.steps[..self.len] {
+            let offset = base + step.byte_offset;
+            let word = load(data, offset, self.word_bytes);
+            let bits = (value >> step.value_shift) & step.mask;
+            let word = (word & !(step.mask << step.shift)) | (bits << step.shift);
+            store(data, offset, self.word_bytes, word);
+        }
+
+        Ok(())
+    }
+}
+
+/// A [`BitSpec`] compiled for a word size.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CompiledSpec {
+    value: Plan,
+    width: usize,
+    // The number of bytes between repetitions
+    stride: usize,
+    // The fixed number of repetitions, or the limit if variable
+    repeats: usize,
+    // Where to read a variable number of repetitions from
+    count: Option<Plan>,
+}
+
+impl CompiledSpec {
+    /// Compile the bit specification for words of `word_bits` bits. The word
+    /// size needs to be a multiple of 8 up to 64. Indirect bit specifications
+    /// cannot be compiled, as their position is only known when decoding, and
+    /// neither can conditional repeats.
+    pub fn new(bit_spec: &BitSpec, word_bits: usize) -> Result<CompiledSpec, Error> {
+        check_word_size(word_bits)?;
+        bit_spec.validate(word_bits)?;
+        bit_spec.check_direct()?;
+        let last = bit_spec.end.as_ref().unwrap_or(&bit_spec.start);
+        let count = match &bit_spec.repeat {
+            Repeat::Variable { word, .. } => Some(bit_span(word, word, word_bits)),
+            Repeat::Conditional { .. } => return Err(Error::Unsupported),
+            Repeat::None | Repeat::Fixed(_) => None,
+        };
+        let (offset, end) = bit_span(&bit_spec.start, last, word_bits);
+
+        CompiledSpec::from_offsets(
+            word_bits,
+            offset,
+            end,
+            bit_spec.word_count(),
+            bit_spec.repeat.max_repeats(),
+            count,
+        )
+    }
+
+    /// Compile from the absolute bit offsets of the value and the repeat count.
+    /// The `end` offsets are exclusive.
+    ///
+    /// This is used by the `bit_spec!` macro to create constants.
+    #[doc(hidden)]
+    pub const fn from_offsets(
+        word_bits: usize,
+        offset: usize,
+        end: usize,
+        word_count: usize,
+        repeats: usize,
+        count: Option<(usize, usize)>,
+    ) -> Result<CompiledSpec, Error> {
+        if let Err(error) = check_word_size(word_bits) {
+            return Err(error);
+        }
+
+        let value = match Plan::new(offset, end, word_bits) {
+            Ok(plan) => plan,
+            Err(error) => return Err(error),
+        };
+        let count = match count {
+            Some((offset, end)) => match Plan::new(offset, end, word_bits) {
+                Ok(plan) => Some(plan),
+                Err(error) => return Err(error),
+            },
+            None => None,
+        };
+
+        Ok(CompiledSpec {
+            value,
+            width: end - offset,
+            stride: word_count * word_bits / 8,
+            repeats,
+            count,
+        })
+    }
+
+    /// The number of bits in a single repetition.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The number of repetitions in `data`.
+    pub fn repeats(&self, data: &[u8]) -> Result<usize, Error> {
+        match &self.count {
+            None => Ok(self.repeats),
+            Some(count) => {
+                let count = count.extract(data, 0)? as usize;
+                if count > self.repeats {
+                    return Err(Error::RepeatOutOfRange);
+                }
+                Ok(count)
+            }
+        }
+    }
+
+    /// Read the raw bits of the repetition `element`.
+    #[inline]
+    pub fn extract(&self, data: &[u8], element: usize) -> Result<u64, Error> {
+        self.value.extract(data, element * self.stride)
+    }
+
+    /// Write the raw bits of the repetition `element`.
+    #[inline]
+    pub fn insert(&self, data: &mut [u8], element: usize, value: u64) -> Result<(), Error> {
+        if value & !mask(self.width) != 0 {
+            return Err(Error::ValueOutOfRange);
+        }
+        self.value.insert(data, element * self.stride, value)
+    }
+}
+
+impl BitSpec {
+    /// Compile the bit specification for words of `word_bits` bits.
+    pub fn compile(&self, word_bits: usize) -> Result<CompiledSpec, Error> {
+        CompiledSpec::new(self, word_bits)
+    }
+}
+
+// The absolute offsets of the first bit and just after the last bit
+fn bit_span(first: &Word, last: &Word, word_bits: usize) -> (usize, usize) {
+    (
+        first.bit_offset(word_bits),
+        last.bit_offset(word_bits) + last.bit_width(word_bits),
+    )
+}
+
+// Words are loaded as whole bytes into a u64
+const fn check_word_size(word_bits: usize) -> Result<(), Error> {
+    if word_bits == 0 || !word_bits.is_multiple_of(8) || word_bits > 64 {
+        Err(Error::InvalidWordSize)
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+const fn mask(width: usize) -> u64 {
+    if width >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << width) - 1
+    }
+}
+
+#[inline]
+fn load(data: &[u8], offset: usize, word_bytes: usize) -> u64 {
+    match word_bytes {
+        1 => data[offset] as u64,
+        2 => u16::from_le_bytes([data[offset], data[offset + 1]]) as u64,
+        _ => {
+            let mut word = 0;
+            for (i, byte) in data[offset..offset + word_bytes].iter().enumerate() {
+                word |= (*byte as u64) << (8 * i);
+            }
+            word
+        }
+    }
+}
+
+#[inline]
+fn store(data: &mut [u8], offset: usize, word_bytes: usize, word: u64) {
+    match word_bytes {
+        1 => data[offset] = word as u8,
+        _ => {
+            for (i, byte) in data[offset..offset + word_bytes].iter_mut().enumerate() {
+                *byte = (word >> (8 * i)) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    #[cfg(not(feature = "std"))]
+    use std::{prelude::rust_2021::*, vec};
+
+    const SPECS: [&str; 6] = [
+        "5",
+        "1[2..6]",
+        "1[4..7]..2[0..3]",
+        "[]..7[]",
+        "1[3]..9[2];2",
+        "2[]..3[];(0[0..3])<3",
+    ];
+
+    #[test]
+    fn test_extract_matches_bit_spec() {
+        let mut data: Vec<u8> = (0..64).map(|i| (i * 37 + 11) as u8).collect();
+        data[0] = 0x02;
+
+        for word_bits in [8, 16, 32] {
+            for spec in SPECS {
+                let bit_spec = parse(spec).unwrap();
+                let Ok(compiled) = bit_spec.compile(word_bits) else {
+                    assert!(bit_spec.bit_width(word_bits) > 64);
+                    continue;
+                };
+                let repeats = bit_spec.repeats(&data, word_bits).unwrap();
+                assert_eq!(compiled.repeats(&data).unwrap(), repeats);
+                for element in 0..repeats {
+                    assert_eq!(
+                        compiled.extract(&data, element),
+                        bit_spec.extract(&data, word_bits, element),
+                        "{spec} with {word_bits} bit words"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_matches_bit_spec() {
+        for word_bits in [8, 16, 64] {
+            for spec in SPECS {
+                let bit_spec = parse(spec).unwrap();
+                let Ok(compiled) = bit_spec.compile(word_bits) else {
+                    assert!(bit_spec.bit_width(word_bits) > 64);
+                    continue;
+                };
+                let value = 0x5A5A_5A5A_5A5A_5A5A & mask(compiled.width());
+
+                let mut expected = vec![0xC3u8; 160];
+                let mut data = expected.clone();
+                bit_spec.insert(&mut expected, word_bits, 1, value).unwrap();
+                compiled.insert(&mut data, 1, value).unwrap();
+                assert_eq!(data, expected, "{spec} with {word_bits} bit words");
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let bit_spec = parse("1[]..2[]").unwrap();
+        assert_eq!(bit_spec.compile(12), Err(Error::InvalidWordSize));
+        assert_eq!(bit_spec.compile(0), Err(Error::InvalidWordSize));
+        assert_eq!(
+            parse("[9]").unwrap().compile(0),
+            Err(Error::InvalidWordSize)
+        );
+
+        let compiled = bit_spec.compile(8).unwrap();
+        assert_eq!(compiled.extract(&[0, 0], 0), Err(Error::OutOfBounds));
+        assert_eq!(
+            compiled.insert(&mut [0, 0, 0], 0, 0x10000),
+            Err(Error::ValueOutOfRange)
+        );
+
+        let bit_spec = parse("[]..8[]").unwrap();
+        assert_eq!(bit_spec.compile(8), Err(Error::TooWide));
+
+        let bit_spec = parse("1[];if 0[7]==1").unwrap();
+        assert_eq!(bit_spec.compile(8), Err(Error::Unsupported));
+
+        // Bit 9 is not in an 8 bit word
+        let bit_spec = parse("[9]").unwrap();
+        assert_eq!(bit_spec.compile(8), Err(Error::InvalidBitSpec));
+        assert!(bit_spec.compile(16).is_ok());
+
+        let bit_spec = parse("1[];(0[])<3").unwrap();
+        let compiled = bit_spec.compile(8).unwrap();
+        assert_eq!(
+            compiled.repeats(&[3, 0, 0, 0]),
+            Err(Error::RepeatOutOfRange)
+        );
+    }
+}
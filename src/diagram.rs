@@ -0,0 +1,321 @@
+//! Register diagrams showing which bits of each word are used.
+//!
+//! A diagram is a grid with a row for each word, the most significant bit on
+//! the left. Each field is given a letter that is drawn in the bits it occupies,
+//! except for literals where the expected bit values are drawn instead. Bits used
+//! by more than one field are drawn as `!`. A legend after the grid gives the
+//! bit specification of each field and any repeats:
+//! ```text
+//!       7   6   5   4   3   2   1   0
+//!     +---+---+---+---+---+---+---+---+
+//!   0 | 1 | 0 | 1 | 0 | 0 | 1 | 0 | 1 | sync
+//!     +---+---+---+---+---+---+---+---+
+//!   1 | B | B | B | B |   |   |   | C | mode, flag
+//!     +---+---+---+---+---+---+---+---+
+//!
+//! A sync: 0[0xA5]
+//! B mode: 1[4..7]
+//! C flag: 1[0]:bool
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::layout::Layout;
+use crate::parser::{BitSpec, Repeat};
+use crate::Error;
+
+/// The characters used to draw the grid.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Style {
+    #[default]
+    Ascii,
+    Unicode,
+}
+
+// The characters for the left, middle and right of a border
+struct Border {
+    left: char,
+    middle: char,
+    right: char,
+}
+
+impl Style {
+    fn horizontal(&self) -> char {
+        match self {
+            Style::Ascii => '-',
+            Style::Unicode => '─',
+        }
+    }
+
+    fn vertical(&self) -> char {
+        match self {
+            Style::Ascii => '|',
+            Style::Unicode => '│',
+        }
+    }
+
+    // The marker for words that are skipped
+    fn gap(&self) -> &'static str {
+        match self {
+            Style::Ascii => "...",
+            Style::Unicode => "⋮",
+        }
+    }
+
+    fn top(&self) -> Border {
+        match self {
+            Style::Ascii => Border {
+                left: '+',
+                middle: '+',
+                right: '+',
+            },
+            Style::Unicode => Border {
+                left: '┌',
+                middle: '┬',
+                right: '┐',
+            },
+        }
+    }
+
+    fn middle(&self) -> Border {
+        match self {
+            Style::Ascii => self.top(),
+            Style::Unicode => Border {
+                left: '├',
+                middle: '┼',
+                right: '┤',
+            },
+        }
+    }
+
+    fn bottom(&self) -> Border {
+        match self {
+            Style::Ascii => self.top(),
+            Style::Unicode => Border {
+                left: '└',
+                middle: '┴',
+                right: '┘',
+            },
+        }
+    }
+}
+
+const LABELS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const OVERLAP: char = '!';
+
+/// Draw the named bit specifications in words of `word_bits` bits.
+pub fn render(
+    fields: &[(&str, &BitSpec)],
+    word_bits: usize,
+    style: Style,
+) -> Result<String, Error> {
+    if word_bits == 0 || word_bits > 64 {
+        return Err(Error::InvalidWordSize);
+    }
+
+    // The character drawn for each absolute bit and the names shown for each word
+    let mut bits: BTreeMap<usize, char> = BTreeMap::new();
+    let mut names: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    let mut legend = Vec::new();
+
+    for (i, (name, bit_spec)) in fields.iter().enumerate() {
+        match bit_spec.validate(word_bits) {
+            Ok(()) | Err(Error::TooWide) => {}
+            Err(error) => return Err(error),
+        }
+        bit_spec.check_direct()?;
+        let label = LABELS.chars().nth(i).unwrap_or('?');
+        let literal = bit_spec.literal().map(|l| l.value());
+        let repeats = bit_spec.repeat.max_repeats();
+        let offset = bit_spec.bit_offset(word_bits);
+        let width = bit_spec.bit_width(word_bits);
+        let stride = bit_spec.word_count() * word_bits;
+
+        for element in 0..repeats {
+            let start = offset + element * stride;
+            for bit in 0..width {
+                let mark = match literal {
+                    Some(value) if bit < 64 => char::from(b'0' + (value >> bit & 1) as u8),
+                    _ => label,
+                };
+                bits.entry(start + bit)
+                    .and_modify(|c| *c = OVERLAP)
+                    .or_insert(mark);
+            }
+
+            let name = match bit_spec.repeat {
+                Repeat::None | Repeat::Conditional { .. } => name.to_string(),
+                _ => format!("{name}[{element}]"),
+            };
+            for word in start / word_bits..=(start + width - 1) / word_bits {
+                names.entry(word).or_default().push(name.clone());
+            }
+        }
+
+        legend.push(format!(
+            "{label} {name}: {bit_spec}{}",
+            repeat_note(bit_spec)
+        ));
+    }
+
+    let words: Vec<usize> = names.keys().copied().collect();
+    let index_width = words.last().map_or(1, |w| w.to_string().len()).max(3);
+    let mut lines = Vec::new();
+
+    let mut header = " ".repeat(index_width + 2);
+    for bit in (0..word_bits).rev() {
+        header.push_str(&format!("{bit:^3} "));
+    }
+    lines.push(header.trim_end().to_string());
+
+    for (row, word) in words.iter().enumerate() {
+        let previous = row.checked_sub(1).map(|r| words[r]);
+        match previous {
+            None => lines.push(border(&style.top(), style, index_width, word_bits)),
+            Some(previous) if previous + 1 != *word => {
+                lines.push(border(&style.bottom(), style, index_width, word_bits));
+                lines.push(format!("{:>index_width$}", style.gap()));
+                lines.push(border(&style.top(), style, index_width, word_bits));
+            }
+            Some(_) => lines.push(border(&style.middle(), style, index_width, word_bits)),
+        }
+
+        let mut line = format!("{word:>index_width$} {}", style.vertical());
+        for bit in (0..word_bits).rev() {
+            let mark = bits.get(&(word * word_bits + bit)).copied().unwrap_or(' ');
+            line.push_str(&format!(" {mark} {}", style.vertical()));
+        }
+        let mut row_names = names[word].clone();
+        row_names.dedup();
+        line.push(' ');
+        line.push_str(&row_names.join(", "));
+        lines.push(line);
+    }
+    if !words.is_empty() {
+        lines.push(border(&style.bottom(), style, index_width, word_bits));
+    }
+
+    lines.push(String::new());
+    lines.extend(legend);
+
+    Ok(lines.join("\n") + "\n")
+}
+
+fn border(border: &Border, style: Style, index_width: usize, word_bits: usize) -> String {
+    let cell: String = std::iter::repeat_n(style.horizontal(), 3).collect();
+    let cells = vec![cell; word_bits].join(&border.middle.to_string());
+
+    format!(
+        "{} {}{cells}{}",
+        " ".repeat(index_width),
+        border.left,
+        border.right
+    )
+}
+
+fn repeat_note(bit_spec: &BitSpec) -> String {
+    match &bit_spec.repeat {
+        Repeat::None => String::new(),
+        Repeat::Fixed(number) => format!(", repeated {number} times"),
+        Repeat::Variable { word, .. } => format!(
+            ", repeated up to {} times with the count in {word}",
+            bit_spec.repeat.max_repeats()
+        ),
+        Repeat::Conditional { word, value } => format!(", present if {word} is {value}"),
+    }
+}
+
+impl BitSpec {
+    /// Draw a diagram of the bits in words of `word_bits` bits.
+    pub fn diagram(&self, word_bits: usize, style: Style) -> Result<String, Error> {
+        render(&[("bits", self)], word_bits, style)
+    }
+}
+
+impl Layout {
+    /// Draw a diagram of all the fields.
+    pub fn diagram(&self, style: Style) -> Result<String, Error> {
+        self.check_supported()?;
+        let fields: Vec<(&str, &BitSpec)> = self
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), &field.bit_spec))
+            .collect();
+
+        render(&fields, self.word_bits, style)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{layout, parse};
+
+    #[test]
+    fn test_layout_diagram() {
+        let layouts = layout::parse(
+            "layout Status {
+                sync: [0xA5]
+                mode: 1[4..7]
+                flag: 1[0]:bool
+            }",
+        )
+        .unwrap();
+
+        let expected = "      7   6   5   4   3   2   1   0
+    +---+---+---+---+---+---+---+---+
+  0 | 1 | 0 | 1 | 0 | 0 | 1 | 0 | 1 | sync
+    +---+---+---+---+---+---+---+---+
+  1 | B | B | B | B |   |   |   | C | mode, flag
+    +---+---+---+---+---+---+---+---+
+
+A sync: 0[0xA5]
+B mode: 1[4..7]
+C flag: 1[0]:bool
+";
+        assert_eq!(layouts[0].diagram(Style::Ascii).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_repeats_and_gaps() {
+        let count = parse("0[0..1]").unwrap();
+        let samples = parse("2[2]..3[1];(0[0..1])<3").unwrap();
+
+        let diagram = render(
+            &[("count", &count), ("samples", &samples)],
+            4,
+            Style::Unicode,
+        )
+        .unwrap();
+        let expected = "      3   2   1   0
+    ┌───┬───┬───┬───┐
+  0 │   │   │ A │ A │ count
+    └───┴───┴───┴───┘
+  ⋮
+    ┌───┬───┬───┬───┐
+  2 │ B │ B │   │   │ samples[0]
+    ├───┼───┼───┼───┤
+  3 │   │   │ B │ B │ samples[0]
+    ├───┼───┼───┼───┤
+  4 │ B │ B │   │   │ samples[1]
+    ├───┼───┼───┼───┤
+  5 │   │   │ B │ B │ samples[1]
+    └───┴───┴───┴───┘
+
+A count: 0[0..1]
+B samples: 2[2]..3[1];(0[0..1])<3, repeated up to 2 times with the count in 0[0..1]
+";
+        assert_eq!(diagram, expected);
+
+        assert_eq!(samples.diagram(2, Style::Ascii), Err(Error::InvalidBitSpec));
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let low = parse("0[0..3]").unwrap();
+        let high = parse("0[3..7]").unwrap();
+
+        let diagram = render(&[("low", &low), ("high", &high)], 8, Style::Ascii).unwrap();
+        assert!(diagram.contains("| B | B | B | B | ! | A | A | A | low, high"));
+    }
+}
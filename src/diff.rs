@@ -0,0 +1,646 @@
+//! Differences between two versions of a layout.
+//!
+//! Fields are matched by name and compared by their absolute bits, so a change
+//! of word size only shows up where it moves or resizes a field. Each change
+//! says whether decoders built for the old layout can still read frames of the
+//! new layout:
+//! - Added fields are compatible, as old decoders ignore their bits.
+//! - Removed, moved and resized fields, and changed pointers, types and
+//!   scaling, are not. The offsets of an indirect field are relative to the
+//!   word its pointer points to.
+//! - A literal is compatible if the old layout did not have one.
+//! - A variable repeat is compatible if only its limit is lowered, and an
+//!   enumeration if it only loses variants.
+//!
+//! The fields of a group are named `group.field` and the group itself is
+//! compared like a field. The fields and groups of a variant of a match are
+//! named `Variant.field`, and a variant that is added, removed or matches
+//! another value is not compatible, as old decoders would read its frames as
+//! another variant or not at all.
+//!
+//! The report lists the changes, marking those that are not compatible with `!`:
+//! ```text
+//! Frame: older decoders cannot read new frames
+//! ! mode: moved from bit 8 to bit 12
+//! ! samples: repeat changed from ;(1[0..3])<4 to ;(1[0..3])<8
+//!   flags: added
+//! ```
+
+use std::fmt::Display;
+
+use crate::layout::{Field, Group, Layout, Scaling};
+use crate::parser::{BitSpec, FieldType, Repeat, Word};
+
+/// A change to a field.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Change {
+    Added,
+    Removed,
+    // The word holding the index of the start word
+    Pointer {
+        old: Option<Word>,
+        new: Option<Word>,
+    },
+    // The absolute bit offset of the first bit
+    Moved {
+        old: usize,
+        new: usize,
+    },
+    // The number of bits in each repetition
+    Resized {
+        old: usize,
+        new: usize,
+    },
+    Literal {
+        old: Option<u64>,
+        new: Option<u64>,
+    },
+    Repeat {
+        old: Repeat,
+        new: Repeat,
+    },
+    Type {
+        old: FieldType,
+        new: FieldType,
+    },
+    Scaling {
+        old: Option<Scaling>,
+        new: Option<Scaling>,
+    },
+    // The value a variant matches, or none for any other value
+    Variant {
+        old: Option<u64>,
+        new: Option<u64>,
+    },
+}
+
+/// A change to a named field.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldChange {
+    pub field: String,
+    pub change: Change,
+    // Whether decoders for the old layout can still read the field
+    pub compatible: bool,
+}
+
+/// The changes between two versions of a layout.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LayoutDiff {
+    pub old: String,
+    pub new: String,
+    pub changes: Vec<FieldChange>,
+}
+
+impl LayoutDiff {
+    /// Whether decoders for the old layout can read frames of the new layout.
+    pub fn is_compatible(&self) -> bool {
+        self.changes.iter().all(|change| change.compatible)
+    }
+}
+
+/// Compare the fields of two versions of a layout.
+pub fn diff(old: &Layout, new: &Layout) -> LayoutDiff {
+    let mut changes = Vec::new();
+    let (old_fields, new_fields) = (fields(old), fields(new));
+
+    for old_field in &old_fields {
+        let Some(new_field) = find(&new_fields, &old_field.name) else {
+            changes.push(FieldChange {
+                field: old_field.name.clone(),
+                change: Change::Removed,
+                compatible: false,
+            });
+            continue;
+        };
+        for (change, compatible) in compare(old_field, old.word_bits, new_field, new.word_bits) {
+            changes.push(FieldChange {
+                field: old_field.name.clone(),
+                change,
+                compatible,
+            });
+        }
+    }
+    for new_field in &new_fields {
+        if find(&old_fields, &new_field.name).is_none() {
+            changes.push(FieldChange {
+                field: new_field.name.clone(),
+                change: Change::Added,
+                compatible: true,
+            });
+        }
+    }
+
+    let variants = |layout: &Layout| {
+        let variants = layout
+            .variants
+            .iter()
+            .flat_map(|variants| &variants.variants);
+        variants
+            .map(|variant| (variant.name.clone(), variant.value))
+            .collect::<Vec<_>>()
+    };
+    let (old_variants, new_variants) = (variants(old), variants(new));
+    for (name, old_value) in &old_variants {
+        let change = match new_variants.iter().find(|(new_name, _)| new_name == name) {
+            Some((_, new_value)) if new_value == old_value => continue,
+            Some((_, new_value)) => Change::Variant {
+                old: *old_value,
+                new: *new_value,
+            },
+            None => Change::Removed,
+        };
+        changes.push(FieldChange {
+            field: name.clone(),
+            change,
+            compatible: false,
+        });
+    }
+    for (name, _) in &new_variants {
+        if !old_variants.iter().any(|(old_name, _)| old_name == name) {
+            changes.push(FieldChange {
+                field: name.clone(),
+                change: Change::Added,
+                compatible: false,
+            });
+        }
+    }
+
+    LayoutDiff {
+        old: old.name.clone(),
+        new: new.name.clone(),
+        changes,
+    }
+}
+
+// The fields of a layout followed by each group and its fields, named
+// `group.field`, then those of each variant named `Variant.field`. A group is
+// compared like a field, so that changes to its words and repeat are found,
+// and the offsets of its fields are from the start of each element.
+fn fields(layout: &Layout) -> Vec<Field> {
+    let mut fields = items(&layout.fields, &layout.groups, "");
+    for variant in layout
+        .variants
+        .iter()
+        .flat_map(|variants| &variants.variants)
+    {
+        let prefix = format!("{}.", variant.name);
+        fields.extend(items(&variant.fields, &variant.groups, &prefix));
+    }
+
+    fields
+}
+
+// The fields and groups with their names after `prefix`
+fn items(fields: &[Field], groups: &[Group], prefix: &str) -> Vec<Field> {
+    let named = |field: &Field, name: String| Field {
+        name: format!("{prefix}{name}"),
+        ..field.clone()
+    };
+
+    let mut items: Vec<Field> = fields
+        .iter()
+        .map(|field| named(field, field.name.clone()))
+        .collect();
+    for group in groups {
+        items.push(Field {
+            name: format!("{prefix}{}", group.name),
+            bit_spec: group.bit_spec.clone(),
+            scaling: None,
+        });
+        items.extend(
+            group
+                .fields
+                .iter()
+                .map(|field| named(field, format!("{}.{}", group.name, field.name))),
+        );
+    }
+
+    items
+}
+
+fn find<'a>(fields: &'a [Field], name: &str) -> Option<&'a Field> {
+    fields.iter().find(|field| field.name == name)
+}
+
+// The changes to a field that is in both layouts, with whether each is compatible
+fn compare(old: &Field, old_bits: usize, new: &Field, new_bits: usize) -> Vec<(Change, bool)> {
+    let mut changes = Vec::new();
+    let (old_spec, new_spec) = (&old.bit_spec, &new.bit_spec);
+
+    let bits = |word: &Option<Word>, word_bits| {
+        word.as_ref()
+            .map(|word| (word.bit_offset(word_bits), word.bit_width(word_bits)))
+    };
+    if bits(&old_spec.pointer, old_bits) != bits(&new_spec.pointer, new_bits) {
+        let change = Change::Pointer {
+            old: old_spec.pointer.clone(),
+            new: new_spec.pointer.clone(),
+        };
+        changes.push((change, false));
+    }
+
+    let (old_offset, new_offset) = (old_spec.bit_offset(old_bits), new_spec.bit_offset(new_bits));
+    if old_offset != new_offset {
+        let change = Change::Moved {
+            old: old_offset,
+            new: new_offset,
+        };
+        changes.push((change, false));
+    }
+
+    let (old_width, new_width) = (old_spec.bit_width(old_bits), new_spec.bit_width(new_bits));
+    if old_width != new_width {
+        let change = Change::Resized {
+            old: old_width,
+            new: new_width,
+        };
+        changes.push((change, false));
+    }
+
+    let old_literal = old_spec.literal().map(|literal| literal.value());
+    let new_literal = new_spec.literal().map(|literal| literal.value());
+    if old_literal != new_literal {
+        let change = Change::Literal {
+            old: old_literal,
+            new: new_literal,
+        };
+        changes.push((change, old_literal.is_none()));
+    }
+
+    let old_repeat = RepeatShape::new(old_spec, old_bits);
+    let new_repeat = RepeatShape::new(new_spec, new_bits);
+    // A repeat written the same way only changes with the words it repeats,
+    // which are already reported as moved or resized
+    let rewritten = old_spec.repeat != new_spec.repeat || old_bits != new_bits;
+    if old_repeat != new_repeat && rewritten {
+        let compatible = match (&old_repeat, &new_repeat) {
+            (
+                RepeatShape::Variable {
+                    stride,
+                    count,
+                    max_repeats,
+                },
+                RepeatShape::Variable {
+                    stride: new_stride,
+                    count: new_count,
+                    max_repeats: new_max_repeats,
+                },
+            ) => stride == new_stride && count == new_count && new_max_repeats <= max_repeats,
+            _ => false,
+        };
+        let change = Change::Repeat {
+            old: old_spec.repeat.clone(),
+            new: new_spec.repeat.clone(),
+        };
+        changes.push((change, compatible));
+    }
+
+    let (old_type, new_type) = (sorted(&old_spec.field_type), sorted(&new_spec.field_type));
+    if old_type != new_type {
+        let compatible = match (&old_type, &new_type) {
+            (FieldType::Enum(old_variants), FieldType::Enum(new_variants)) => new_variants
+                .iter()
+                .all(|variant| old_variants.contains(variant)),
+            _ => false,
+        };
+        let change = Change::Type {
+            old: old_spec.field_type.clone(),
+            new: new_spec.field_type.clone(),
+        };
+        changes.push((change, compatible));
+    }
+
+    if old.scaling != new.scaling {
+        let change = Change::Scaling {
+            old: old.scaling.clone(),
+            new: new.scaling.clone(),
+        };
+        changes.push((change, false));
+    }
+
+    changes
+}
+
+// The repeat of a field in absolute bits, so that it does not depend on how
+// the repeat is written or on the word size
+#[derive(PartialEq)]
+enum RepeatShape {
+    None,
+    Fixed {
+        stride: usize,
+        count: usize,
+    },
+    Variable {
+        stride: usize,
+        // The absolute offset and width of the count
+        count: (usize, usize),
+        max_repeats: usize,
+    },
+    Conditional {
+        // The absolute offset and width of the word with the condition
+        word: (usize, usize),
+        value: u64,
+    },
+}
+
+impl RepeatShape {
+    fn new(bit_spec: &BitSpec, word_bits: usize) -> Self {
+        let stride = bit_spec.word_count() * word_bits;
+        match &bit_spec.repeat {
+            Repeat::None | Repeat::Fixed(1) => RepeatShape::None,
+            Repeat::Fixed(count) => RepeatShape::Fixed {
+                stride,
+                count: *count,
+            },
+            Repeat::Variable { word, .. } => RepeatShape::Variable {
+                stride,
+                count: (word.bit_offset(word_bits), word.bit_width(word_bits)),
+                max_repeats: bit_spec.repeat.max_repeats(),
+            },
+            Repeat::Conditional { word, value } => RepeatShape::Conditional {
+                word: (word.bit_offset(word_bits), word.bit_width(word_bits)),
+                value: *value,
+            },
+        }
+    }
+}
+
+// The field type with any enumeration ordered by value
+fn sorted(field_type: &FieldType) -> FieldType {
+    let mut field_type = field_type.clone();
+    if let FieldType::Enum(variants) = &mut field_type {
+        variants.sort();
+    }
+    field_type
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added => write!(f, "added"),
+            Change::Removed => write!(f, "removed"),
+            Change::Pointer { old, new } => {
+                let pointer = |word: &Option<Word>| {
+                    word.as_ref()
+                        .map_or("none".to_string(), |word| word.to_string())
+                };
+                write!(
+                    f,
+                    "pointer changed from {} to {}",
+                    pointer(old),
+                    pointer(new)
+                )
+            }
+            Change::Moved { old, new } => write!(f, "moved from bit {old} to bit {new}"),
+            Change::Resized { old, new } => {
+                write!(f, "width changed from {old} to {new} bits")
+            }
+            Change::Literal { old, new } => {
+                let literal = |value: &Option<u64>| {
+                    value.map_or("none".to_string(), |value| format!("{value:#x}"))
+                };
+                write!(
+                    f,
+                    "literal changed from {} to {}",
+                    literal(old),
+                    literal(new)
+                )
+            }
+            Change::Repeat { old, new } => write!(
+                f,
+                "repeat changed from {} to {}",
+                repeat_text(old),
+                repeat_text(new)
+            ),
+            Change::Type { old, new } => write!(
+                f,
+                "type changed from {} to {}",
+                type_text(old),
+                type_text(new)
+            ),
+            Change::Scaling { old, new } => write!(
+                f,
+                "scaling changed from {} to {}",
+                scaling_text(old),
+                scaling_text(new)
+            ),
+            Change::Variant { old, new } => {
+                let value =
+                    |value: &Option<u64>| value.map_or("_".to_string(), |value| value.to_string());
+                write!(f, "value changed from {} to {}", value(old), value(new))
+            }
+        }
+    }
+}
+
+pub(crate) fn repeat_text(repeat: &Repeat) -> String {
+    match repeat {
+        Repeat::None => "none".to_string(),
+        repeat => repeat.to_string(),
+    }
+}
+
+pub(crate) fn scaling_text(scaling: &Option<Scaling>) -> String {
+    let Some(scaling) = scaling else {
+        return "none".to_string();
+    };
+    let mut text = format!("scale={} offset={}", scaling.scale, scaling.offset);
+    if let Some(unit) = &scaling.unit {
+        text.push_str(&format!(" unit={unit}"));
+    }
+    text
+}
+
+pub(crate) fn type_text(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::Unsigned => "unsigned".to_string(),
+        field_type => field_type.to_string()[1..].to_string(),
+    }
+}
+
+// The report with a line for each change
+impl Display for LayoutDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = if self.old == self.new {
+            self.old.clone()
+        } else {
+            format!("{} -> {}", self.old, self.new)
+        };
+        let compatible = match (self.changes.is_empty(), self.is_compatible()) {
+            (true, _) => "no changes",
+            (false, true) => "older decoders can read new frames",
+            (false, false) => "older decoders cannot read new frames",
+        };
+        writeln!(f, "{name}: {compatible}")?;
+
+        for change in &self.changes {
+            let mark = if change.compatible { ' ' } else { '!' };
+            writeln!(f, "{mark} {}: {}", change.field, change.change)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn test_diff() {
+        let old = layout::parse(
+            "layout Frame {
+                sync: [0xA5]
+                mode: 1[0..3]:{0=Off,1=On,2=Auto}
+                count: 1[4..7]
+                samples: 2[];(1[4..7])<8
+                status: 10[]
+                level: 11[] scale=0.5
+            }",
+        )
+        .unwrap();
+        let new = layout::parse(
+            "layout Frame {
+                sync: [0xA6]
+                mode: 1[0..3]:{1=On,0=Off}
+                count: 1[4..7]
+                samples: 2[];(1[4..7])<=4
+                level: 11[] scale=0.25
+                flags: 12[0..3]
+            }",
+        )
+        .unwrap();
+
+        let diff = diff(&old[0], &new[0]);
+        assert!(!diff.is_compatible());
+        assert_eq!(
+            diff.to_string(),
+            "Frame: older decoders cannot read new frames
+! sync: literal changed from 0xa5 to 0xa6
+  mode: type changed from {0=Off,1=On,2=Auto} to {1=On,0=Off}
+  samples: repeat changed from ;(1[4..7])<8 to ;(1[4..7])<=4
+! status: removed
+! level: scaling changed from scale=0.5 offset=0 to scale=0.25 offset=0
+  flags: added
+"
+        );
+    }
+
+    #[test]
+    fn test_groups() {
+        let old = layout::parse(
+            "layout Log {
+                count: 0[]
+                records: 1[]..2[];(0[])<8 {
+                    id: 0[]
+                    level: 1[]
+                }
+            }",
+        )
+        .unwrap();
+        let new = layout::parse(
+            "layout Log {
+                count: 0[]
+                records: 1[]..3[];(0[])<8 {
+                    id: 0[]
+                    level: 2[]
+                    flags: 1[0..3]
+                }
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(
+            diff(&old[0], &new[0]).to_string(),
+            "Log: older decoders cannot read new frames
+! records: width changed from 16 to 24 bits
+! records.level: moved from bit 8 to bit 16
+  records.flags: added
+"
+        );
+    }
+
+    #[test]
+    fn test_variants() {
+        let old = layout::parse(
+            "layout Message {
+                kind: 0[]
+                match kind {
+                    1 => Ping {
+                        id: 1[]
+                    }
+                    2 => Data {
+                        length: 1[]
+                    }
+                    3 => Reset {
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let new = layout::parse(
+            "layout Message {
+                kind: 0[]
+                match kind {
+                    1 => Ping {
+                        id: 1[]
+                        flags: 2[]
+                    }
+                    4 => Data {
+                        length: 1[]..2[]
+                    }
+                    _ => Other {
+                    }
+                }
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(
+            diff(&old[0], &new[0]).to_string(),
+            "Message: older decoders cannot read new frames
+! Data.length: width changed from 8 to 16 bits
+  Ping.flags: added
+! Data: value changed from 2 to 4
+! Reset: removed
+! Other: added
+"
+        );
+    }
+
+    #[test]
+    fn test_compatible() {
+        let old = layout::parse("layout A { value: 1[]..2[] }").unwrap();
+        let moved = layout::parse("layout B word_bits=16 { value: 0[8]..1[7] }").unwrap();
+        let diff = diff(&old[0], &moved[0]);
+        assert_eq!(diff.to_string(), "A -> B: no changes\n");
+        assert!(diff.is_compatible());
+
+        let resized = layout::parse("layout A word_bits=16 { value: 0[8]..1[] }").unwrap();
+        assert_eq!(
+            super::diff(&old[0], &resized[0]).changes,
+            vec![FieldChange {
+                field: "value".to_string(),
+                change: Change::Resized { old: 16, new: 24 },
+                compatible: false,
+            }]
+        );
+
+        let indirect = layout::parse("layout A { value: @(0[])[]..1[] }").unwrap();
+        assert_eq!(
+            super::diff(&old[0], &indirect[0]).to_string(),
+            "A: older decoders cannot read new frames
+! value: pointer changed from none to 0[]
+! value: moved from bit 8 to bit 0
+"
+        );
+
+        let repeated = layout::parse("layout A { value: 1[]..2[];(0[])<3 }").unwrap();
+        let diff = super::diff(&old[0], &repeated[0]);
+        assert!(!diff.is_compatible());
+        assert_eq!(
+            diff.to_string(),
+            "A: older decoders cannot read new frames\n! value: repeat changed from none to ;(0[])<3\n"
+        );
+    }
+}
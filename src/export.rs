@@ -0,0 +1,169 @@
+//! Export of layouts to the formats of other tools.
+
+pub mod kaitai;
+pub mod svg;
+pub mod wavedrom;
+pub mod wireshark;
+
+use crate::layout::{Field, Layout};
+use crate::parser::Repeat;
+use crate::Error;
+
+// The bits of a single repetition of a field
+pub(crate) struct Segment<'a> {
+    pub field: &'a Field,
+    // The repetition, if the field repeats
+    pub element: Option<usize>,
+    // The absolute offset of the first bit
+    pub offset: usize,
+    pub width: usize,
+}
+
+impl Segment<'_> {
+    pub fn name(&self) -> String {
+        match self.element {
+            Some(element) => format!("{}[{element}]", self.field.name),
+            None => self.field.name.clone(),
+        }
+    }
+
+    // The value of a literal field
+    pub fn literal(&self) -> Option<u64> {
+        self.field.bit_spec.literal().map(|literal| literal.value())
+    }
+
+    // A short note of how the field repeats. Segments are never conditional,
+    // as their layouts cannot have conditions.
+    pub fn repeat_note(&self) -> Option<String> {
+        match &self.field.bit_spec.repeat {
+            Repeat::None | Repeat::Conditional { .. } => None,
+            Repeat::Fixed(number) => Some(format!("x{number}")),
+            Repeat::Variable { .. } => Some(format!(
+                "up to {}",
+                self.field.bit_spec.repeat.max_repeats()
+            )),
+        }
+    }
+}
+
+// All repetitions of all fields ordered by offset. Variable repeats
+// give the maximum number of repetitions.
+pub(crate) fn segments(layout: &Layout) -> Result<Vec<Segment<'_>>, Error> {
+    layout.check_supported()?;
+    let mut segments = Vec::new();
+
+    for field in &layout.fields {
+        let bit_spec = &field.bit_spec;
+        match bit_spec.validate(layout.word_bits) {
+            Ok(()) | Err(Error::TooWide) => {}
+            Err(error) => return Err(error),
+        }
+        bit_spec.check_direct()?;
+
+        let offset = bit_spec.bit_offset(layout.word_bits);
+        let stride = bit_spec.word_count() * layout.word_bits;
+        for element in 0..bit_spec.repeat.max_repeats() {
+            segments.push(Segment {
+                field,
+                element: match bit_spec.repeat {
+                    Repeat::None => None,
+                    _ => Some(element),
+                },
+                offset: offset + element * stride,
+                width: bit_spec.bit_width(layout.word_bits),
+            });
+        }
+    }
+
+    segments.sort_by_key(|segment| segment.offset);
+    for pair in segments.windows(2) {
+        if pair[0].offset + pair[0].width > pair[1].offset {
+            return Err(Error::OverlappingBits);
+        }
+    }
+
+    Ok(segments)
+}
+
+// The number of words needed for all the segments
+pub(crate) fn word_count(segments: &[Segment], word_bits: usize) -> usize {
+    segments
+        .iter()
+        .map(|segment| (segment.offset + segment.width).div_ceil(word_bits))
+        .max()
+        .unwrap_or(0)
+}
+
+// The bit specification of a field followed by any scaling
+pub(crate) fn description(field: &Field) -> String {
+    let mut description = field.bit_spec.to_string();
+    if let Some(scaling) = &field.scaling {
+        description.push_str(&format!(
+            " scale={} offset={}",
+            scaling.scale, scaling.offset
+        ));
+        if let Some(unit) = &scaling.unit {
+            description.push_str(&format!(" unit={unit}"));
+        }
+    }
+    description
+}
+
+// A JSON string with any special characters escaped
+pub(crate) fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn test_segments() {
+        let layouts = layout::parse(
+            "layout Frame {
+                samples: 2[];(1[])<3
+                sync: [0xA5]
+                count: 1[]
+            }",
+        )
+        .unwrap();
+
+        let segments = segments(&layouts[0]).unwrap();
+        let names: Vec<(String, usize)> = segments.iter().map(|s| (s.name(), s.offset)).collect();
+        assert_eq!(
+            names,
+            vec![
+                ("sync".to_string(), 0),
+                ("count".to_string(), 8),
+                ("samples[0]".to_string(), 16),
+                ("samples[1]".to_string(), 24),
+            ]
+        );
+        assert_eq!(segments[2].repeat_note(), Some("up to 2".to_string()));
+        assert_eq!(word_count(&segments, 8), 4);
+
+        let layouts = layout::parse("layout Overlapping { low: 0[0..3]\n high: 0[3..7] }").unwrap();
+        assert!(matches!(
+            super::segments(&layouts[0]),
+            Err(Error::OverlappingBits)
+        ));
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a \"b\"\\\n"), "\"a \\\"b\\\"\\\\\\n\"");
+    }
+}
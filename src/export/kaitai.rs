@@ -0,0 +1,393 @@
+//! Kaitai Struct `.ksy` definitions.
+//!
+//! Kaitai reads fields in sequence, so the fields are ordered by their bits and
+//! any unused bits become `reserved` fields. With `bit-endian: le` the bits are
+//! read from the least significant bit of each byte, matching bit-lang words in
+//! little endian order, so ranges map to `bX` fields. Literals that are whole
+//! bytes become `contents`.
+//!
+//! Repeated fields are read as `repeat: expr` over whole words, with a type for
+//! the element if it does not use all the bits of its words. A variable repeat
+//! uses the field holding the count in `repeat-expr`, adding a field for the
+//! count if there is not one. So that later fields are at the right place, a
+//! variable repeat that is not at the end is read from a substream the size of
+//! the maximum number of repetitions.
+
+use std::fmt::Write;
+
+use super::{description, json_string};
+use crate::codegen::snake_case;
+use crate::layout::{Field, Layout};
+use crate::parser::{FieldType, Repeat, Word};
+use crate::Error;
+
+// A field in the sequence
+enum Item<'a> {
+    Field(&'a Field),
+    // A field with repeats, over all the words of the repetitions
+    Repeated(&'a Field),
+    // The count of a variable repeat that is not a field of the layout
+    Count { name: String, width: usize },
+}
+
+struct Entry<'a> {
+    item: Item<'a>,
+    offset: usize,
+    width: usize,
+}
+
+/// Export the layout as a Kaitai Struct definition.
+pub fn render(layout: &Layout) -> Result<String, Error> {
+    let word_bits = layout.word_bits;
+    if word_bits == 0 || !word_bits.is_multiple_of(8) {
+        return Err(Error::InvalidWordSize);
+    }
+
+    let entries = entries(layout)?;
+    let mut ksy = String::new();
+    let mut types = String::new();
+    let mut enums = String::new();
+
+    writeln!(ksy, "meta:").unwrap();
+    writeln!(ksy, "  id: {}", snake_case(&layout.name)).unwrap();
+    writeln!(ksy, "  title: {}", json_string(&layout.name)).unwrap();
+    writeln!(ksy, "  endian: le").unwrap();
+    writeln!(ksy, "  bit-endian: le").unwrap();
+    writeln!(ksy, "seq:").unwrap();
+
+    let mut bit = 0;
+    let mut reserved = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        while bit < entry.offset {
+            let width = (entry.offset - bit).min(64);
+            writeln!(ksy, "  - id: reserved{reserved}\n    type: b{width}").unwrap();
+            reserved += 1;
+            bit += width;
+        }
+        bit = entry.offset + entry.width;
+
+        match &entry.item {
+            Item::Field(field) => {
+                writeln!(ksy, "  - id: {}", id(&field.name)).unwrap();
+                if !literal_contents(&mut ksy, field, entry, word_bits) {
+                    value_type(&mut ksy, field, entry.offset, entry.width, "    ");
+                    literal_valid(&mut ksy, field, "    ");
+                }
+                doc(&mut ksy, field);
+            }
+            Item::Count { name, width } => {
+                writeln!(ksy, "  - id: {name}\n    type: b{width}").unwrap();
+            }
+            Item::Repeated(field) => {
+                let element = element_type(&mut types, field, word_bits);
+                let count = match &field.bit_spec.repeat {
+                    Repeat::Variable { word, .. } => Some(count_name(layout, field, word)),
+                    _ => None,
+                };
+                let is_last = i + 1 == entries.len();
+
+                writeln!(ksy, "  - id: {}", id(&field.name)).unwrap();
+                match count {
+                    // Read from a substream so that later fields are in place
+                    Some(count) if !is_last => {
+                        let list = format!("{}_list", id(&field.name));
+                        writeln!(ksy, "    size: {}\n    type: {list}", entry.width / 8).unwrap();
+                        writeln!(types, "  {list}:\n    seq:\n      - id: items").unwrap();
+                        write_element(&mut types, field, &element, entry.offset, "        ");
+                        writeln!(
+                            types,
+                            "        repeat: expr\n        repeat-expr: _parent.{count}"
+                        )
+                        .unwrap();
+                    }
+                    _ => {
+                        write_element(&mut ksy, field, &element, entry.offset, "    ");
+                        let repeats = count
+                            .unwrap_or_else(|| field.bit_spec.repeat.max_repeats().to_string());
+                        writeln!(ksy, "    repeat: expr\n    repeat-expr: {repeats}").unwrap();
+                    }
+                }
+                doc(&mut ksy, field);
+            }
+        }
+
+        if let Item::Field(field) | Item::Repeated(field) = &entry.item {
+            if let FieldType::Enum(variants) = &field.bit_spec.field_type {
+                writeln!(enums, "  {}:", id(&field.name)).unwrap();
+                for (value, variant) in variants {
+                    writeln!(enums, "    {value}: {}", id(variant)).unwrap();
+                }
+            }
+        }
+    }
+
+    if !types.is_empty() {
+        write!(ksy, "types:\n{types}").unwrap();
+    }
+    if !enums.is_empty() {
+        write!(ksy, "enums:\n{enums}").unwrap();
+    }
+
+    Ok(ksy)
+}
+
+// The fields in order, with the count of any variable repeats
+fn entries(layout: &Layout) -> Result<Vec<Entry<'_>>, Error> {
+    layout.check_supported()?;
+    let word_bits = layout.word_bits;
+    let mut entries = Vec::new();
+
+    for field in &layout.fields {
+        let bit_spec = &field.bit_spec;
+        bit_spec.validate(word_bits)?;
+        bit_spec.check_direct()?;
+
+        match &bit_spec.repeat {
+            Repeat::None => entries.push(Entry {
+                item: Item::Field(field),
+                offset: bit_spec.bit_offset(word_bits),
+                width: bit_spec.bit_width(word_bits),
+            }),
+            repeat => {
+                entries.push(Entry {
+                    item: Item::Repeated(field),
+                    offset: bit_spec.start.index * word_bits,
+                    width: bit_spec.word_count() * word_bits * repeat.max_repeats(),
+                });
+                if let Repeat::Variable { word, .. } = repeat {
+                    if count_field(layout, word).is_none() {
+                        entries.push(Entry {
+                            item: Item::Count {
+                                name: format!("{}_count", id(&field.name)),
+                                width: word.bit_width(word_bits),
+                            },
+                            offset: word.bit_offset(word_bits),
+                            width: word.bit_width(word_bits),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    entries.sort_by_key(|entry| entry.offset);
+    for pair in entries.windows(2) {
+        if pair[0].offset + pair[0].width > pair[1].offset {
+            return Err(Error::OverlappingBits);
+        }
+    }
+
+    Ok(entries)
+}
+
+// The field that is exactly the count of a variable repeat
+fn count_field<'a>(layout: &'a Layout, word: &Word) -> Option<&'a Field> {
+    layout.fields.iter().find(|field| {
+        field.bit_spec.start == *word
+            && field.bit_spec.end.is_none()
+            && field.bit_spec.repeat == Repeat::None
+    })
+}
+
+fn count_name(layout: &Layout, field: &Field, word: &Word) -> String {
+    match count_field(layout, word) {
+        Some(count) => id(&count.name),
+        None => format!("{}_count", id(&field.name)),
+    }
+}
+
+// The element of a repeated field: its type, if a user type is needed for
+// unused bits in its words, and the width of the value.
+struct Element {
+    user_type: Option<String>,
+    width: usize,
+}
+
+fn element_type(types: &mut String, field: &Field, word_bits: usize) -> Element {
+    let bit_spec = &field.bit_spec;
+    let words_bits = bit_spec.word_count() * word_bits;
+    let before = bit_spec.bit_offset(word_bits) - bit_spec.start.index * word_bits;
+    let width = bit_spec.bit_width(word_bits);
+    let after = words_bits - before - width;
+
+    if before == 0 && after == 0 {
+        return Element {
+            user_type: None,
+            width,
+        };
+    }
+
+    let name = format!("{}_element", id(&field.name));
+    writeln!(types, "  {name}:\n    seq:").unwrap();
+    if before > 0 {
+        writeln!(types, "      - id: reserved0\n        type: b{before}").unwrap();
+    }
+    writeln!(types, "      - id: value").unwrap();
+    value_type(types, field, before, width, "        ");
+    literal_valid(types, field, "        ");
+    if after > 0 {
+        writeln!(types, "      - id: reserved1\n        type: b{after}").unwrap();
+    }
+
+    Element {
+        user_type: Some(name),
+        width,
+    }
+}
+
+fn write_element(ksy: &mut String, field: &Field, element: &Element, offset: usize, indent: &str) {
+    match &element.user_type {
+        Some(user_type) => writeln!(ksy, "{indent}type: {user_type}").unwrap(),
+        None => {
+            value_type(ksy, field, offset, element.width, indent);
+            literal_valid(ksy, field, indent);
+        }
+    }
+}
+
+// Write the type of a value at the absolute bit `offset`
+fn value_type(ksy: &mut String, field: &Field, offset: usize, width: usize, indent: &str) {
+    let aligned = offset.is_multiple_of(8);
+
+    match &field.bit_spec.field_type {
+        FieldType::F32 if aligned => writeln!(ksy, "{indent}type: f4").unwrap(),
+        FieldType::Enum(_) => writeln!(
+            ksy,
+            "{indent}type: b{width}\n{indent}enum: {}",
+            id(&field.name)
+        )
+        .unwrap(),
+        _ => writeln!(ksy, "{indent}type: b{width}").unwrap(),
+    }
+}
+
+// Write whole bytes of a literal as contents, returning whether it was written
+fn literal_contents(ksy: &mut String, field: &Field, entry: &Entry, word_bits: usize) -> bool {
+    let Some(literal) = field.bit_spec.literal() else {
+        return false;
+    };
+    if !entry.offset.is_multiple_of(8) || !entry.width.is_multiple_of(8) || word_bits > 64 {
+        return false;
+    }
+
+    let value = literal.value();
+    let bytes: Vec<String> = (0..entry.width / 8)
+        .map(|i| {
+            format!(
+                "{:#04x}",
+                value.checked_shr(8 * i as u32).unwrap_or(0) as u8
+            )
+        })
+        .collect();
+    writeln!(ksy, "    contents: [{}]", bytes.join(", ")).unwrap();
+
+    true
+}
+
+fn literal_valid(ksy: &mut String, field: &Field, indent: &str) {
+    if let Some(literal) = field.bit_spec.literal() {
+        writeln!(ksy, "{indent}valid: {:#x}", literal.value()).unwrap();
+    }
+}
+
+// The bit specification, and any scaling, as documentation
+fn doc(ksy: &mut String, field: &Field) {
+    writeln!(ksy, "    doc: {}", json_string(&description(field))).unwrap();
+}
+
+// Kaitai identifiers are lower case
+fn id(name: &str) -> String {
+    snake_case(name).to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn test_render() {
+        let layouts = layout::parse(
+            "layout SensorFrame {
+                sync: [0xA5]
+                mode: 1[0..3]:{0=Off,1=On}
+                count: 1[4..7]
+                flags: 2[1];3
+                samples: 5[]..6[];(1[4..7])<4
+                status: 12[]
+            }",
+        )
+        .unwrap();
+
+        let expected = r#"meta:
+  id: sensor_frame
+  title: "SensorFrame"
+  endian: le
+  bit-endian: le
+seq:
+  - id: sync
+    contents: [0xa5]
+    doc: "0[0xA5]"
+  - id: mode
+    type: b4
+    enum: mode
+    doc: "1[0..3]:{0=Off,1=On}"
+  - id: count
+    type: b4
+    doc: "1[4..7]"
+  - id: flags
+    type: flags_element
+    repeat: expr
+    repeat-expr: 3
+    doc: "2[1];3"
+  - id: samples
+    size: 6
+    type: samples_list
+    doc: "5[]..6[];(1[4..7])<4"
+  - id: reserved0
+    type: b8
+  - id: status
+    type: b8
+    doc: "12[]"
+types:
+  flags_element:
+    seq:
+      - id: reserved0
+        type: b1
+      - id: value
+        type: b1
+      - id: reserved1
+        type: b6
+  samples_list:
+    seq:
+      - id: items
+        type: b16
+        repeat: expr
+        repeat-expr: _parent.count
+enums:
+  mode:
+    0: off
+    1: on
+"#;
+        assert_eq!(render(&layouts[0]).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_count_field() {
+        let layouts = layout::parse(
+            "layout Frame {
+                values: 1[];(0[0..3])<3
+            }",
+        )
+        .unwrap();
+
+        let ksy = render(&layouts[0]).unwrap();
+        assert!(
+            ksy.contains("  - id: values_count\n    type: b4\n  - id: reserved0\n    type: b4\n")
+        );
+        assert!(ksy.contains("    repeat-expr: values_count\n"));
+
+        let layouts = layout::parse("layout Frame { a: 0[0..3];2\n b: 0[4..7] }").unwrap();
+        assert_eq!(render(&layouts[0]), Err(Error::OverlappingBits));
+    }
+}
@@ -0,0 +1,167 @@
+//! SVG drawings of layouts for documentation.
+//!
+//! Like [`diagram`](crate::diagram) there is a row for each word with the most
+//! significant bit on the left and the bit numbers above. Each repetition of a
+//! field is drawn as a box with its name, and the number of repeats for repeated
+//! fields, with the bit values of literals below the name.
+
+use std::fmt::Write;
+
+use super::{segments, word_count};
+use crate::layout::Layout;
+use crate::Error;
+
+const CELL_WIDTH: usize = 28;
+const BOX_HEIGHT: usize = 36;
+// The height of a word including its bit numbers
+const ROW_HEIGHT: usize = 60;
+// The space for the word index to the left of each row
+const MARGIN: usize = 40;
+
+/// Export the layout as an SVG drawing.
+pub fn render(layout: &Layout) -> Result<String, Error> {
+    let word_bits = layout.word_bits;
+    let segments = segments(layout)?;
+    let words = word_count(&segments, word_bits);
+
+    let width = MARGIN + word_bits * CELL_WIDTH + 8;
+    let height = words * ROW_HEIGHT + 8;
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\" font-family=\"sans-serif\" text-anchor=\"middle\">"
+    )
+    .unwrap();
+    writeln!(svg, "<title>{}</title>", escape(&layout.name)).unwrap();
+
+    for word in 0..words {
+        let top = 8 + word * ROW_HEIGHT;
+        let box_top = top + 16;
+        writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-size=\"12\" text-anchor=\"end\">{word}</text>",
+            MARGIN - 8,
+            box_top + BOX_HEIGHT / 2 + 4
+        )
+        .unwrap();
+        for bit in 0..word_bits {
+            let x = cell_x(bit, word_bits);
+            writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" font-size=\"10\">{bit}</text>",
+                x + CELL_WIDTH / 2,
+                top + 10
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                "<rect x=\"{x}\" y=\"{box_top}\" width=\"{CELL_WIDTH}\" height=\"{BOX_HEIGHT}\" \
+                 fill=\"none\" stroke=\"#ccc\"/>"
+            )
+            .unwrap();
+        }
+    }
+
+    for segment in &segments {
+        let literal = segment.literal();
+        let name = match segment.repeat_note() {
+            Some(note) => format!("{} ({note})", segment.name()),
+            None => segment.name(),
+        };
+
+        // Draw the part of the repetition in each word
+        let end = segment.offset + segment.width;
+        for word in segment.offset / word_bits..end.div_ceil(word_bits) {
+            let first = segment.offset.max(word * word_bits) - word * word_bits;
+            let last = end.min((word + 1) * word_bits) - word * word_bits - 1;
+            let x = cell_x(last, word_bits);
+            let box_top = 8 + word * ROW_HEIGHT + 16;
+            let box_width = (last - first + 1) * CELL_WIDTH;
+
+            writeln!(
+                svg,
+                "<rect x=\"{x}\" y=\"{box_top}\" width=\"{box_width}\" height=\"{BOX_HEIGHT}\" \
+                 fill=\"none\" stroke=\"#000\" stroke-width=\"1.5\"/>"
+            )
+            .unwrap();
+            writeln!(
+                svg,
+                "<text x=\"{}\" y=\"{}\" font-size=\"11\">{}</text>",
+                x + box_width / 2,
+                box_top + 15,
+                escape(&name)
+            )
+            .unwrap();
+
+            if let Some(value) = literal {
+                for bit in first..=last {
+                    let value_bit = word * word_bits + bit - segment.offset;
+                    let digit = if value_bit < 64 {
+                        value >> value_bit & 1
+                    } else {
+                        0
+                    };
+                    writeln!(
+                        svg,
+                        "<text x=\"{}\" y=\"{}\" font-size=\"10\">{digit}</text>",
+                        cell_x(bit, word_bits) + CELL_WIDTH / 2,
+                        box_top + 29
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+// The left of the cell for a bit in a word
+fn cell_x(bit: usize, word_bits: usize) -> usize {
+    MARGIN + (word_bits - 1 - bit) * CELL_WIDTH
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn test_render() {
+        let layouts = layout::parse(
+            "layout Frame {
+                sync: [0x1F]
+                value: 1[6]..2[1];2
+            }",
+        )
+        .unwrap();
+
+        let svg = render(&layouts[0]).unwrap();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<title>Frame</title>"));
+        // A cell for every bit of the five words
+        assert_eq!(svg.matches("stroke=\"#ccc\"").count(), 40);
+        // Each repetition is split over two words
+        assert_eq!(svg.matches(">value[1] (x2)</text>").count(), 2);
+
+        // The literal bits from the least significant
+        let digits: Vec<&str> = svg
+            .lines()
+            .filter(|line| line.contains("y=\"53\""))
+            .map(|line| &line[line.len() - 8..line.len() - 7])
+            .collect();
+        assert_eq!(digits.concat(), "11111000");
+
+        assert_eq!(escape("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+    }
+}
@@ -0,0 +1,88 @@
+//! WaveDrom `reg` JSON for drawing bit fields.
+//!
+//! Each repetition of a field is a separate entry, from the least significant
+//! bit, with unused bits as entries without a name. Literals are given as their
+//! value so that WaveDrom draws the bits, and repeated fields have a note of
+//! the number of repeats. There is a lane for each word.
+
+use super::{json_string, segments, word_count};
+use crate::layout::Layout;
+use crate::Error;
+
+// The largest integer that JavaScript numbers hold exactly
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Export the layout as WaveDrom `reg` JSON.
+pub fn render(layout: &Layout) -> Result<String, Error> {
+    let segments = segments(layout)?;
+    let words = word_count(&segments, layout.word_bits).max(1);
+
+    let mut entries = Vec::new();
+    let mut bit = 0;
+    for segment in &segments {
+        if segment.offset > bit {
+            entries.push(format!("{{\"bits\": {}}}", segment.offset - bit));
+        }
+
+        let mut attrs = Vec::new();
+        if let Some(value) = segment.literal() {
+            attrs.push(if value <= MAX_SAFE_INTEGER {
+                value.to_string()
+            } else {
+                json_string(&format!("{value:#x}"))
+            });
+        }
+        if let Some(note) = segment.repeat_note() {
+            attrs.push(json_string(&note));
+        }
+        let attr = match attrs.len() {
+            0 => String::new(),
+            1 => format!(", \"attr\": {}", attrs[0]),
+            _ => format!(", \"attr\": [{}]", attrs.join(", ")),
+        };
+
+        entries.push(format!(
+            "{{\"bits\": {}, \"name\": {}{attr}}}",
+            segment.width,
+            json_string(&segment.name())
+        ));
+        bit = segment.offset + segment.width;
+    }
+    let total = words * layout.word_bits;
+    if total > bit {
+        entries.push(format!("{{\"bits\": {}}}", total - bit));
+    }
+
+    Ok(format!(
+        "{{\"reg\": [\n    {}\n], \"config\": {{\"bits\": {total}, \"lanes\": {words}}}}}\n",
+        entries.join(",\n    ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn test_render() {
+        let layouts = layout::parse(
+            "layout Frame {
+                sync: [0xA5]
+                mode: 1[4..7]
+                samples: 2[];2
+            }",
+        )
+        .unwrap();
+
+        let expected = r#"{"reg": [
+    {"bits": 8, "name": "sync", "attr": 165},
+    {"bits": 4},
+    {"bits": 4, "name": "mode"},
+    {"bits": 8, "name": "samples[0]", "attr": "x2"},
+    {"bits": 8, "name": "samples[1]", "attr": "x2"}
+], "config": {"bits": 32, "lanes": 4}}
+"#;
+        assert_eq!(render(&layouts[0]).unwrap(), expected);
+    }
+}
@@ -0,0 +1,418 @@
+//! Wireshark dissectors written in Lua.
+//!
+//! Each field becomes a `ProtoField` over the bytes holding its bits, with a
+//! bitmask when the field does not use all of those bits. Wireshark reads the
+//! bytes in little endian order, matching bit-lang words. Literals are checked
+//! and a mismatch is reported as an expert info, so sync words show up as
+//! malformed packets. Repeated fields are dissected in a loop, using the count
+//! from the packet for a variable repeat.
+//!
+//! The dissector is registered with the UDP port table for "Decode As...":
+//! ```text
+//! wireshark -X lua_script:sensor.lua
+//! ```
+
+use std::fmt::Write;
+
+use super::{description, json_string, segments};
+use crate::codegen::snake_case;
+use crate::layout::{Field, Layout};
+use crate::parser::{FieldType, Repeat, Word};
+use crate::Error;
+
+// The bytes holding a value and the bits of the value in them
+struct Bytes {
+    start: usize,
+    len: usize,
+    shift: usize,
+    width: usize,
+}
+
+impl Bytes {
+    fn new(offset: usize, width: usize) -> Result<Self, Error> {
+        let start = offset / 8;
+        let len = (offset + width).div_ceil(8) - start;
+        if len > 8 {
+            return Err(Error::TooWide);
+        }
+
+        Ok(Bytes {
+            start,
+            len,
+            shift: offset - start * 8,
+            width,
+        })
+    }
+
+    // The bits of the value, if it does not use all the bytes
+    fn mask(&self) -> Option<u64> {
+        if self.width == self.len * 8 {
+            return None;
+        }
+        Some((u64::MAX >> (64 - self.width)) << self.shift)
+    }
+
+    fn proto_field(&self) -> &'static str {
+        match self.len {
+            1 => "uint8",
+            2 => "uint16",
+            3 => "uint24",
+            4 => "uint32",
+            _ => "uint64",
+        }
+    }
+
+    // An expression reading the value from the buffer range `range`. Values
+    // of more than four bytes are read as a `UInt64`.
+    fn read(&self, range: &str) -> String {
+        if self.len > 4 {
+            let mut read = format!("{range}:le_uint64()");
+            if self.shift > 0 {
+                write!(read, ":rshift({})", self.shift).unwrap();
+            }
+            if self.width < 64 {
+                write!(read, ":band({})", uint64(u64::MAX >> (64 - self.width))).unwrap();
+            }
+            return read;
+        }
+
+        let mut read = format!("{range}:le_uint()");
+        if self.shift > 0 {
+            read = format!("math.floor({read} / {:#x})", 1u64 << self.shift);
+        }
+        if self.width < self.len * 8 {
+            read = format!("{read} % {:#x}", 1u64 << self.width);
+        }
+        read
+    }
+
+    // A constant compared with the value from `read`
+    fn constant(&self, value: u64) -> String {
+        if self.len > 4 {
+            uint64(value)
+        } else {
+            format!("{value:#x}")
+        }
+    }
+}
+
+/// Export the layout as a Wireshark dissector in Lua.
+pub fn render(layout: &Layout) -> Result<String, Error> {
+    let word_bits = layout.word_bits;
+    if word_bits == 0 || !word_bits.is_multiple_of(8) {
+        return Err(Error::InvalidWordSize);
+    }
+    // Check the fields are valid and do not overlap
+    segments(layout)?;
+    for field in &layout.fields {
+        field.bit_spec.validate(word_bits)?;
+    }
+
+    let abbrev = snake_case(&layout.name).to_ascii_lowercase();
+    let mut lua = String::new();
+
+    writeln!(lua, "-- Wireshark dissector for the {} layout", layout.name).unwrap();
+    writeln!(
+        lua,
+        "local proto = Proto({}, {})\n",
+        json_string(&abbrev),
+        json_string(&layout.name)
+    )
+    .unwrap();
+
+    let mut names = Vec::new();
+    for field in &layout.fields {
+        let name = local(&field.name);
+        let bytes = value_bytes(field, word_bits)?;
+        writeln!(
+            lua,
+            "local {name} = {}",
+            proto_field(field, &bytes, &abbrev)?
+        )
+        .unwrap();
+        names.push(name);
+    }
+    writeln!(lua, "proto.fields = {{ {} }}\n", names.join(", ")).unwrap();
+
+    writeln!(
+        lua,
+        "local bad_literal = ProtoExpert.new({}, \"Literal does not match\", expert.group.MALFORMED, expert.severity.ERROR)",
+        json_string(&format!("{abbrev}.bad_literal"))
+    )
+    .unwrap();
+    writeln!(
+        lua,
+        "local bad_repeat = ProtoExpert.new({}, \"Too many repeats\", expert.group.MALFORMED, expert.severity.ERROR)",
+        json_string(&format!("{abbrev}.bad_repeat"))
+    )
+    .unwrap();
+    writeln!(lua, "proto.experts = {{ bad_literal, bad_repeat }}\n").unwrap();
+
+    writeln!(lua, "function proto.dissector(buffer, pinfo, tree)").unwrap();
+    writeln!(lua, "    pinfo.cols.protocol = proto.name").unwrap();
+    writeln!(lua, "    local subtree = tree:add(proto, buffer())").unwrap();
+    for field in &layout.fields {
+        writeln!(lua).unwrap();
+        dissect(&mut lua, field, word_bits)?;
+    }
+    writeln!(lua, "end\n").unwrap();
+
+    writeln!(
+        lua,
+        "DissectorTable.get(\"udp.port\"):add_for_decode_as(proto)"
+    )
+    .unwrap();
+
+    Ok(lua)
+}
+
+// The bytes of the first repetition of the field
+fn value_bytes(field: &Field, word_bits: usize) -> Result<Bytes, Error> {
+    let bit_spec = &field.bit_spec;
+    Bytes::new(
+        bit_spec.bit_offset(word_bits),
+        bit_spec.bit_width(word_bits),
+    )
+}
+
+fn proto_field(field: &Field, bytes: &Bytes, abbrev: &str) -> Result<String, Error> {
+    let abbrev = json_string(&format!("{abbrev}.{}", local_name(&field.name)));
+    let name = json_string(&field.name);
+    let description = json_string(&description(field));
+    let mask = bytes.mask();
+    let mask_text = mask.map_or("nil".to_string(), uint64_or_number);
+
+    Ok(match &field.bit_spec.field_type {
+        FieldType::Bool => format!(
+            "ProtoField.bool({abbrev}, {name}, {}, nil, {mask_text}, {description})",
+            bytes.len * 8
+        ),
+        FieldType::F32 if mask.is_none() && bytes.len == 4 => {
+            format!("ProtoField.float({abbrev}, {name}, nil, {description})")
+        }
+        FieldType::Enum(variants) => {
+            let variants: Vec<String> = variants
+                .iter()
+                .map(|(value, variant)| format!("[{value}] = {}", json_string(variant)))
+                .collect();
+            format!(
+                "ProtoField.{}({abbrev}, {name}, base.DEC, {{ {} }}, {mask_text}, {description})",
+                bytes.proto_field(),
+                variants.join(", ")
+            )
+        }
+        _ => {
+            let base = match field.bit_spec.literal() {
+                Some(_) => "base.HEX",
+                None => "base.DEC",
+            };
+            format!(
+                "ProtoField.{}({abbrev}, {name}, {base}, nil, {mask_text}, {description})",
+                bytes.proto_field()
+            )
+        }
+    })
+}
+
+// Write the statements adding the field to the tree
+fn dissect(lua: &mut String, field: &Field, word_bits: usize) -> Result<(), Error> {
+    let bit_spec = &field.bit_spec;
+    let name = local(&field.name);
+    let bytes = value_bytes(field, word_bits)?;
+    let literal = bit_spec.literal().map(|l| l.value());
+    let stride = bit_spec.word_count() * word_bits / 8;
+
+    writeln!(lua, "    -- {}: {bit_spec}", field.name).unwrap();
+    let (indent, range) = match &bit_spec.repeat {
+        Repeat::None => ("    ", format!("buffer({}, {})", bytes.start, bytes.len)),
+        Repeat::Fixed(number) => {
+            writeln!(lua, "    for element = 0, {number} - 1 do").unwrap();
+            writeln!(
+                lua,
+                "        local offset = {} + element * {stride}",
+                bytes.start
+            )
+            .unwrap();
+            ("        ", format!("buffer(offset, {})", bytes.len))
+        }
+        Repeat::Variable { word, .. } => {
+            let count = format!("{}_count", local_name(&field.name));
+            let max = bit_spec.repeat.max_repeats();
+            writeln!(lua, "    local {count} = {}", read_count(word, word_bits)?).unwrap();
+            writeln!(lua, "    if {count} > {max} then").unwrap();
+            writeln!(
+                lua,
+                "        subtree:add_proto_expert_info(bad_repeat, {})",
+                json_string(&format!("{} has more than {max} repeats", field.name))
+            )
+            .unwrap();
+            writeln!(lua, "        {count} = {max}\n    end").unwrap();
+            writeln!(lua, "    for element = 0, {count} - 1 do").unwrap();
+            writeln!(
+                lua,
+                "        local offset = {} + element * {stride}",
+                bytes.start
+            )
+            .unwrap();
+            ("        ", format!("buffer(offset, {})", bytes.len))
+        }
+        // Layouts with conditional fields are not exported
+        Repeat::Conditional { .. } => return Err(Error::Unsupported),
+    };
+
+    let item = format!("subtree:add_le({name}, {range})");
+    match literal {
+        Some(value) => {
+            writeln!(lua, "{indent}local item = {item}").unwrap();
+            if bit_spec.repeat != Repeat::None {
+                writeln!(lua, "{indent}item:append_text(\" [\" .. element .. \"]\")").unwrap();
+            }
+            writeln!(
+                lua,
+                "{indent}if {} ~= {} then",
+                bytes.read(&range),
+                bytes.constant(value)
+            )
+            .unwrap();
+            writeln!(
+                lua,
+                "{indent}    item:add_proto_expert_info(bad_literal, {})",
+                json_string(&format!("{} is not {value:#x}", field.name))
+            )
+            .unwrap();
+            writeln!(lua, "{indent}end").unwrap();
+        }
+        None if bit_spec.repeat != Repeat::None => {
+            writeln!(
+                lua,
+                "{indent}{item}:append_text(\" [\" .. element .. \"]\")"
+            )
+            .unwrap();
+        }
+        None => writeln!(lua, "{indent}{item}").unwrap(),
+    }
+    if bit_spec.repeat != Repeat::None {
+        writeln!(lua, "    end").unwrap();
+    }
+
+    Ok(())
+}
+
+// An expression reading the count of a variable repeat as a number
+fn read_count(word: &Word, word_bits: usize) -> Result<String, Error> {
+    let bytes = Bytes::new(word.bit_offset(word_bits), word.bit_width(word_bits))?;
+    let read = bytes.read(&format!("buffer({}, {})", bytes.start, bytes.len));
+    Ok(match bytes.len {
+        1..=4 => read,
+        _ => format!("{read}:tonumber()"),
+    })
+}
+
+// A constant as a `UInt64`
+fn uint64(value: u64) -> String {
+    format!("UInt64({:#x}, {:#x})", value as u32, value >> 32)
+}
+
+// A constant as a number if it can be held exactly by a Lua number
+fn uint64_or_number(value: u64) -> String {
+    if value < 1 << 53 {
+        format!("{value:#x}")
+    } else {
+        uint64(value)
+    }
+}
+
+fn local_name(name: &str) -> String {
+    snake_case(name).to_ascii_lowercase()
+}
+
+// The Lua local holding the field. The prefix keeps it from being a keyword.
+fn local(name: &str) -> String {
+    format!("f_{}", local_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn test_render() {
+        let layouts = layout::parse(
+            "layout SensorFrame {
+                sync: [0xA5]
+                mode: 1[0..3]:{0=Off,1=On}
+                count: 1[4..7]
+                flag: 2[7]:bool
+                samples: 3[]..4[];(1[4..7])<4
+            }",
+        )
+        .unwrap();
+
+        let expected = r#"-- Wireshark dissector for the SensorFrame layout
+local proto = Proto("sensor_frame", "SensorFrame")
+
+local f_sync = ProtoField.uint8("sensor_frame.sync", "sync", base.HEX, nil, nil, "0[0xA5]")
+local f_mode = ProtoField.uint8("sensor_frame.mode", "mode", base.DEC, { [0] = "Off", [1] = "On" }, 0xf, "1[0..3]:{0=Off,1=On}")
+local f_count = ProtoField.uint8("sensor_frame.count", "count", base.DEC, nil, 0xf0, "1[4..7]")
+local f_flag = ProtoField.bool("sensor_frame.flag", "flag", 8, nil, 0x80, "2[7]:bool")
+local f_samples = ProtoField.uint16("sensor_frame.samples", "samples", base.DEC, nil, nil, "3[]..4[];(1[4..7])<4")
+proto.fields = { f_sync, f_mode, f_count, f_flag, f_samples }
+
+local bad_literal = ProtoExpert.new("sensor_frame.bad_literal", "Literal does not match", expert.group.MALFORMED, expert.severity.ERROR)
+local bad_repeat = ProtoExpert.new("sensor_frame.bad_repeat", "Too many repeats", expert.group.MALFORMED, expert.severity.ERROR)
+proto.experts = { bad_literal, bad_repeat }
+
+function proto.dissector(buffer, pinfo, tree)
+    pinfo.cols.protocol = proto.name
+    local subtree = tree:add(proto, buffer())
+
+    -- sync: 0[0xA5]
+    local item = subtree:add_le(f_sync, buffer(0, 1))
+    if buffer(0, 1):le_uint() ~= 0xa5 then
+        item:add_proto_expert_info(bad_literal, "sync is not 0xa5")
+    end
+
+    -- mode: 1[0..3]:{0=Off,1=On}
+    subtree:add_le(f_mode, buffer(1, 1))
+
+    -- count: 1[4..7]
+    subtree:add_le(f_count, buffer(1, 1))
+
+    -- flag: 2[7]:bool
+    subtree:add_le(f_flag, buffer(2, 1))
+
+    -- samples: 3[]..4[];(1[4..7])<4
+    local samples_count = math.floor(buffer(1, 1):le_uint() / 0x10) % 0x10
+    if samples_count > 3 then
+        subtree:add_proto_expert_info(bad_repeat, "samples has more than 3 repeats")
+        samples_count = 3
+    end
+    for element = 0, samples_count - 1 do
+        local offset = 3 + element * 2
+        subtree:add_le(f_samples, buffer(offset, 2)):append_text(" [" .. element .. "]")
+    end
+end
+
+DissectorTable.get("udp.port"):add_for_decode_as(proto)
+"#;
+        assert_eq!(render(&layouts[0]).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let bytes = Bytes::new(12, 8).unwrap();
+        assert_eq!((bytes.start, bytes.len, bytes.mask()), (1, 2, Some(0x0ff0)));
+        assert_eq!(
+            bytes.read("buffer(1, 2)"),
+            "math.floor(buffer(1, 2):le_uint() / 0x10) % 0x100"
+        );
+
+        let bytes = Bytes::new(4, 60).unwrap();
+        assert_eq!(
+            bytes.read("r"),
+            "r:le_uint64():rshift(4):band(UInt64(0xffffffff, 0xfffffff))"
+        );
+        assert_eq!(Bytes::new(4, 64).err(), Some(Error::TooWide));
+    }
+}
@@ -0,0 +1,528 @@
+//! Hexdumps of a buffer annotated with the fields of a layout.
+//!
+//! There is a row for each word giving its index, byte offset and bytes in
+//! buffer order, followed by the fields that cover it. A field is shown with
+//! its value on its first word, and with `(cont.)` on the other words. The bits
+//! of the word that the field covers are given if it does not cover all of them:
+//! ```text
+//!   word  offset  data  fields
+//! !    0  0x0000  5a    sync = 0x5a, expected 0xa5
+//!      1  0x0001  24    mode bits 0..3 = Run, count bits 4..7 = 2, samples count = 2
+//!      2  0x0002  07    samples[0] = 7 (1 of 2)
+//!      3  0x0003  08    samples[1] = 8 (2 of 2)
+//!      4  0x0004  --
+//! !    5  0x0005  --    status (missing)
+//! ```
+//! Rows marked with `!` have a problem: a literal that does not match, a repeat
+//! count over the limit, a pointer into the header or a field that is past the
+//! end of the buffer. The count of a variable repeat is shown on its word and
+//! each repetition shows how many there are, so the words consumed by the
+//! repeat can be seen. The pointer of an indirect field is shown on its word in
+//! the same way, as is whether a conditional field is present. The fields after
+//! an absent one are shown where they have moved to.
+//!
+//! The variant selected by a match is shown on the first word of the field
+//! matched on, followed by the fields of that variant. A value that no variant
+//! has is a problem, and only the fields outside the match are shown.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::layout::{Field, Group, Layout};
+use crate::parser::{BitSpec, Repeat};
+use crate::Error;
+
+// The notes for each word and the words with problems
+#[derive(Default)]
+struct Notes {
+    words: BTreeMap<usize, Vec<String>>,
+    problems: BTreeSet<usize>,
+}
+
+impl Notes {
+    fn add(&mut self, word: usize, note: String) {
+        self.words.entry(word).or_default().push(note);
+    }
+}
+
+/// Annotate the words of `data` with the fields of the layout.
+pub fn render(layout: &Layout, data: &[u8]) -> Result<String, Error> {
+    let word_bits = layout.word_bits;
+    if word_bits == 0 || !word_bits.is_multiple_of(8) {
+        return Err(Error::InvalidWordSize);
+    }
+    let word_bytes = word_bits / 8;
+
+    // The condition of a field past the end is shown as missing, so the
+    // fields after it are not moved
+    let absent = |_: &str, bit_spec: &BitSpec| Ok(bit_spec.repeats(data, word_bits) == Ok(0));
+
+    let mut notes = Notes::default();
+    let layout = match variant(&mut notes, layout, data, absent)? {
+        Some(name) => layout.with_variant(&name)?.resolve_with(absent)?,
+        None => layout.resolve_with(absent)?,
+    };
+    for field in &layout.fields {
+        field.bit_spec.validate(word_bits)?;
+        annotate(&mut notes, field, data, word_bits)?;
+    }
+    for group in &layout.groups {
+        for field in &group.fields {
+            field.bit_spec.validate(word_bits)?;
+        }
+        annotate_group(&mut notes, group, data, word_bits)?;
+    }
+
+    let words = data
+        .len()
+        .div_ceil(word_bytes)
+        .max(notes.words.keys().last().map_or(0, |word| word + 1));
+    let index_width = (words.max(1) - 1).to_string().len().max(4);
+    let data_width = (word_bytes * 3 - 1).max(4);
+
+    let mut lines = vec![format!(
+        "  {:>index_width$}  offset  {:data_width$}  fields",
+        "word", "data"
+    )];
+    for word in 0..words {
+        let start = word * word_bytes;
+        let bytes = match data.get(start..) {
+            Some(rest) if !rest.is_empty() => {
+                let bytes: Vec<String> = rest
+                    .iter()
+                    .take(word_bytes)
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect();
+                bytes.join(" ")
+            }
+            _ => "--".to_string(),
+        };
+        let mark = if notes.problems.contains(&word) {
+            '!'
+        } else {
+            ' '
+        };
+        let fields = notes
+            .words
+            .get(&word)
+            .map_or(String::new(), |n| n.join(", "));
+
+        let line =
+            format!("{mark} {word:>index_width$}  {start:#06x}  {bytes:data_width$}  {fields}");
+        lines.push(line.trim_end().to_string());
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+// The name of the variant that the field matched on selects, adding a note
+// for it on the first word of the field. There is none if the layout has no
+// match, the field is missing or no variant has its value.
+fn variant(
+    notes: &mut Notes,
+    layout: &Layout,
+    data: &[u8],
+    absent: impl FnMut(&str, &BitSpec) -> Result<bool, Error>,
+) -> Result<Option<String>, Error> {
+    let Some(variants) = &layout.variants else {
+        return Ok(None);
+    };
+    let word_bits = layout.word_bits;
+    let resolved = layout.resolve_with(absent)?;
+    let Some(field) = resolved.field(&variants.field) else {
+        return Ok(None);
+    };
+
+    // A missing field is noted with the other fields
+    let bit_spec = &field.bit_spec;
+    let Ok(value) = bit_spec.extract(data, word_bits, 0) else {
+        return Ok(None);
+    };
+    let base = bit_spec.base_offset(data, word_bits)?;
+    let word = (base + bit_spec.bit_offset(word_bits)) / word_bits;
+
+    match variants.select(value) {
+        Some(variant) => {
+            notes.add(word, format!("{} => {}", field.name, variant.name));
+            Ok(Some(variant.name.clone()))
+        }
+        None => {
+            notes.add(word, format!("{} => no variant", field.name));
+            notes.problems.insert(word);
+            Ok(None)
+        }
+    }
+}
+
+// Add the notes for all the repetitions of a field
+fn annotate(notes: &mut Notes, field: &Field, data: &[u8], word_bits: usize) -> Result<(), Error> {
+    let bit_spec = &field.bit_spec;
+    let available = data.len() * 8;
+
+    let Some(base) = base_offset(notes, &field.name, bit_spec, data, word_bits)? else {
+        return Ok(());
+    };
+    let Some((repeats, count)) = repeats(notes, &field.name, bit_spec, data, word_bits)? else {
+        return Ok(());
+    };
+
+    let width = bit_spec.bit_width(word_bits);
+    let stride = bit_spec.word_count() * word_bits;
+    for element in 0..repeats {
+        let offset = base + bit_spec.bit_offset(word_bits) + element * stride;
+        let name = match bit_spec.repeat {
+            Repeat::None | Repeat::Conditional { .. } => field.name.clone(),
+            _ => format!("{}[{element}]", field.name),
+        };
+        let first = offset / word_bits;
+        let last = (offset + width - 1) / word_bits;
+
+        if offset + width > available {
+            for word in first..=last {
+                notes.add(word, format!("{name} (missing)"));
+                notes.problems.insert(word);
+            }
+            continue;
+        }
+
+        let (value, problem) = value(field, data, word_bits, element)?;
+        if problem {
+            notes.problems.insert(first);
+        }
+        let of = count.map_or(String::new(), |count| {
+            format!(" ({} of {count})", element + 1)
+        });
+
+        for word in first..=last {
+            let bits = word_range(offset, width, word, word_bits);
+            if word == first {
+                notes.add(word, format!("{name}{bits} = {value}{of}"));
+            } else {
+                notes.add(word, format!("{name}{bits} (cont.)"));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Add the notes for all the repetitions of a group, naming the fields of each
+// repetition `group[element].field`
+fn annotate_group(
+    notes: &mut Notes,
+    group: &Group,
+    data: &[u8],
+    word_bits: usize,
+) -> Result<(), Error> {
+    let bit_spec = &group.bit_spec;
+    if base_offset(notes, &group.name, bit_spec, data, word_bits)?.is_none() {
+        return Ok(());
+    }
+    let Some((repeats, _)) = repeats(notes, &group.name, bit_spec, data, word_bits)? else {
+        return Ok(());
+    };
+
+    for element in 0..repeats {
+        for field in group.element_fields(data, word_bits, element)? {
+            let name = match bit_spec.repeat {
+                Repeat::None | Repeat::Conditional { .. } => {
+                    format!("{}.{}", group.name, field.name)
+                }
+                _ => format!("{}[{element}].{}", group.name, field.name),
+            };
+            let field = Field { name, ..field };
+            annotate(notes, &field, data, word_bits)?;
+        }
+    }
+
+    Ok(())
+}
+
+// The number of repetitions to show and, for a variable repeat, the count
+// to show with each, adding a note for the count. There are none if the
+// count is missing.
+fn repeats(
+    notes: &mut Notes,
+    name: &str,
+    bit_spec: &BitSpec,
+    data: &[u8],
+    word_bits: usize,
+) -> Result<Option<(usize, Option<usize>)>, Error> {
+    let word = match &bit_spec.repeat {
+        Repeat::None => return Ok(Some((1, None))),
+        Repeat::Fixed(number) => return Ok(Some((*number, None))),
+        Repeat::Variable { word, .. } | Repeat::Conditional { word, .. } => word,
+    };
+
+    let index = word.index;
+    if word.bit_offset(word_bits) + word.bit_width(word_bits) > data.len() * 8 {
+        let what = match bit_spec.repeat {
+            Repeat::Conditional { .. } => "condition",
+            _ => "count",
+        };
+        notes.add(index, format!("{name} {what} (missing)"));
+        notes.problems.insert(index);
+        return Ok(None);
+    }
+    if let Repeat::Conditional { value, .. } = bit_spec.repeat {
+        let present = word.extract(data, word_bits)? == value;
+        let what = if present { "present" } else { "absent" };
+        notes.add(index, format!("{name} {what}"));
+        return Ok(Some((usize::from(present), None)));
+    }
+
+    let count = word.extract(data, word_bits)? as usize;
+    if bit_spec.check_repeats(count).is_err() {
+        notes.add(
+            index,
+            format!(
+                "{name} count = {count}, over the limit of {}",
+                bit_spec.repeat.max_repeats()
+            ),
+        );
+        notes.problems.insert(index);
+    } else {
+        notes.add(index, format!("{name} count = {count}"));
+    }
+    let repeats = count.min(bit_spec.repeat.max_repeats());
+
+    Ok(Some((repeats, Some(repeats))))
+}
+
+// The offset that an indirect field is relative to, adding a note for the
+// pointer. There is none if the pointer is missing or points outside the data.
+fn base_offset(
+    notes: &mut Notes,
+    name: &str,
+    bit_spec: &BitSpec,
+    data: &[u8],
+    word_bits: usize,
+) -> Result<Option<usize>, Error> {
+    let Some(pointer) = &bit_spec.pointer else {
+        return Ok(Some(0));
+    };
+    let index = pointer.index;
+    if pointer.bit_offset(word_bits) + pointer.bit_width(word_bits) > data.len() * 8 {
+        notes.add(index, format!("{name} pointer (missing)"));
+        notes.problems.insert(index);
+        return Ok(None);
+    }
+
+    let value = pointer.extract(data, word_bits)?;
+    let problem = match bit_spec.base_offset(data, word_bits) {
+        Ok(base) => {
+            notes.add(index, format!("{name} pointer = {value}"));
+            return Ok(Some(base));
+        }
+        Err(Error::InvalidPointer) => "into the header",
+        Err(Error::OutOfBounds) => "past the end",
+        Err(error) => return Err(error),
+    };
+    notes.add(index, format!("{name} pointer = {value}, {problem}"));
+    notes.problems.insert(index);
+
+    Ok(None)
+}
+
+// The value of a repetition, with whether a literal does not match
+fn value(
+    field: &Field,
+    data: &[u8],
+    word_bits: usize,
+    element: usize,
+) -> Result<(String, bool), Error> {
+    let bit_spec = &field.bit_spec;
+    let raw = bit_spec.extract(data, word_bits, element)?;
+
+    if let Some(literal) = bit_spec.literal() {
+        let expected = literal.value();
+        return Ok(if raw == expected {
+            (format!("{raw:#x}"), false)
+        } else {
+            (format!("{raw:#x}, expected {expected:#x}"), true)
+        });
+    }
+
+    let value = match bit_spec
+        .field_type
+        .decode(raw, bit_spec.bit_width(word_bits))
+    {
+        Ok(value) => value,
+        Err(error) => return Ok((format!("{raw:#x} ({error})"), true)),
+    };
+    Ok(match &field.scaling {
+        Some(scaling) => {
+            let physical = scaling.to_physical(&value)?;
+            match &scaling.unit {
+                Some(unit) => (format!("{physical} {unit}"), false),
+                None => (physical.to_string(), false),
+            }
+        }
+        None => (value.to_string(), false),
+    })
+}
+
+// The bits of `word` covered by `width` bits at `offset`, if not all of them
+fn word_range(offset: usize, width: usize, word: usize, word_bits: usize) -> String {
+    let start = word * word_bits;
+    let low = offset.max(start) - start;
+    let high = (offset + width).min(start + word_bits) - 1 - start;
+
+    match (low, high) {
+        (0, high) if high == word_bits - 1 => String::new(),
+        (low, high) if low == high => format!(" bit {low}"),
+        (low, high) => format!(" bits {low}..{high}"),
+    }
+}
+
+impl Layout {
+    /// A hexdump of `data` annotated with the fields.
+    pub fn hexdump(&self, data: &[u8]) -> Result<String, Error> {
+        render(self, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layout;
+
+    #[test]
+    fn test_render() {
+        let layouts = layout::parse(
+            "layout Frame {
+                sync: [0xA5]
+                mode: 1[0..3]:{4=Run,5=Stop}
+                count: 1[4..7]
+                samples: 2[];(1[4..7])<4
+                status: 5[]
+            }",
+        )
+        .unwrap();
+
+        let dump = layouts[0].hexdump(&[0x5A, 0x24, 7, 8]).unwrap();
+        assert_eq!(
+            dump,
+            "  word  offset  data  fields
+!    0  0x0000  5a    sync = 0x5a, expected 0xa5
+     1  0x0001  24    mode bits 0..3 = Run, count bits 4..7 = 2, samples count = 2
+     2  0x0002  07    samples[0] = 7 (1 of 2)
+     3  0x0003  08    samples[1] = 8 (2 of 2)
+     4  0x0004  --
+!    5  0x0005  --    status (missing)
+"
+        );
+
+        let dump = layouts[0].hexdump(&[0xA5, 0x54, 1, 2, 3, 4]).unwrap();
+        assert!(dump.contains("!    1  0x0001  54    mode bits 0..3 = Run, count bits 4..7 = 5, samples count = 5, over the limit of 3\n"));
+        assert!(dump.contains("     4  0x0004  03    samples[2] = 3 (3 of 3)\n"));
+    }
+
+    #[test]
+    fn test_indirect() {
+        let layouts = layout::parse(
+            "layout Message {
+                offset: 0[]
+                payload: @(0[])[]..1[]
+            }",
+        )
+        .unwrap();
+
+        let dump = layouts[0].hexdump(&[2, 0, 0x34, 0x12]).unwrap();
+        assert_eq!(
+            dump,
+            "  word  offset  data  fields
+     0  0x0000  02    offset = 2, payload pointer = 2
+     1  0x0001  00
+     2  0x0002  34    payload = 4660
+     3  0x0003  12    payload (cont.)
+"
+        );
+
+        let dump = layouts[0].hexdump(&[0, 0]).unwrap();
+        assert!(dump
+            .contains("!    0  0x0000  00    offset = 0, payload pointer = 0, into the header\n"));
+        let dump = layouts[0].hexdump(&[4, 0]).unwrap();
+        assert!(dump.contains("payload pointer = 4, past the end\n"));
+    }
+
+    #[test]
+    fn test_conditional() {
+        let layouts = layout::parse(
+            "layout Packet {
+                flags: 0[]
+                ext: 1[]..2[];if 0[7]==1
+                length: 3[]
+            }",
+        )
+        .unwrap();
+
+        let dump = layouts[0].hexdump(&[0x00, 2]).unwrap();
+        assert_eq!(
+            dump,
+            "  word  offset  data  fields
+     0  0x0000  00    flags = 0, ext absent
+     1  0x0001  02    length = 2
+"
+        );
+
+        let dump = layouts[0].hexdump(&[0x80, 0x34, 0x12, 2]).unwrap();
+        assert!(dump.contains("     0  0x0000  80    flags = 128, ext present\n"));
+        assert!(dump.contains("     1  0x0001  34    ext = 4660\n"));
+        assert!(dump.contains("     3  0x0003  02    length = 2\n"));
+    }
+
+    #[test]
+    fn test_wide_words() {
+        let layouts = layout::parse(
+            "layout Wide word_bits=16 {
+                level: 0[8]..1[3] scale=0.5 unit=\"V\"
+            }",
+        )
+        .unwrap();
+
+        let dump = layouts[0].hexdump(&[0x00, 0x01, 0x02, 0x00, 0xFF]).unwrap();
+        assert_eq!(
+            dump,
+            "  word  offset  data   fields
+     0  0x0000  00 01  level bits 8..15 = 256.5 V
+     1  0x0002  02 00  level bits 0..3 (cont.)
+     2  0x0004  ff
+"
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let layouts = layout::parse(
+            "layout Message {
+                kind: 0[]
+                match kind {
+                    1 => Ping {
+                        id: 1[]
+                    }
+                    2 => Data {
+                        length: 1[]
+                        payload: 2[];(1[])<4
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let layout = &layouts[0];
+
+        assert_eq!(
+            layout.hexdump(&[2, 1, 7]).unwrap(),
+            "  word  offset  data  fields
+     0  0x0000  02    kind => Data, kind = 2
+     1  0x0001  01    length = 1, payload count = 1
+     2  0x0002  07    payload[0] = 7 (1 of 1)
+"
+        );
+        assert_eq!(
+            layout.hexdump(&[3, 1]).unwrap(),
+            "  word  offset  data  fields
+!    0  0x0000  03    kind => no variant, kind = 3
+     1  0x0001  01
+"
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! JSON for bit specifications, layouts and decoded frames.
+//!
+//! Every document is an object with the `version` of the schema, which changes
+//! whenever a member is removed or its meaning changes, and one of `bit_spec`,
+//! `layout`, `frame` or `diff`:
+//! ```text
+//! {"version": 1, "bit_spec": {
+//!     "pointer": null,
+//!     "start": {"index": 1, "bits": {"kind": "range", "start": 4, "end": 7}},
+//!     "end": null,
+//!     "repeat": {"kind": "variable", "word": {"index": 0, "bits": {"kind": "whole_word"}},
+//!                "condition": "<", "limit": 4},
+//!     "type": {"kind": "enum", "variants": [{"value": 0, "name": "Off"}]}}}
+//! ```
+//! - `pointer` is the word holding the index that `start` and `end` are
+//!   relative to, or null if their indexes are absolute.
+//! - `bits` has the `kind` `single` with a `bit`, `range` with a `start` and
+//!   `end`, `whole_word`, or `literal` with a `literal`.
+//! - A literal has its `radix` (2 or 16), its `digits` in that radix and the
+//!   `value`.
+//! - `repeat` has the `kind` `none`, `fixed` with a `count`, `variable`
+//!   with the `word` holding the count, the `condition` (`<` or `<=`) and `limit`,
+//!   or `conditional` with the `word` and the `value` it has when present.
+//! - `type` has the `kind` `unsigned`, `bool`, `f16`, `f32`, `fixed` with
+//!   `integer_bits` and `fractional_bits`, or `enum` with `variants`.
+//!
+//! A layout has its `name`, `word_bits`, `fields`, each with a `name`, a
+//! `bit_spec` and a `scaling` that is null or has a `scale`, `offset` and `unit`,
+//! `groups`, each with a `name`, a `bit_spec` and its `fields`, and `variants`,
+//! which is null or has the `field` matched on and the `variants`, each with a
+//! `name`, the `value` it matches (null for any other value), `fields` and
+//! `groups`.
+//!
+//! A decoded frame has the `layout` name, the name of the `variant` that
+//! matches or null, and `fields`, each with its `name`,
+//! any `unit`, and the `elements` of each repetition with the absolute bit
+//! `offset` and `width`, the `raw` bits and the typed `value`. The value is a
+//! number, a boolean, the name of an enumeration variant, or null for floats
+//! that are not finite. It also has the `groups`, each with its `name` and the
+//! `elements` of each repetition with their decoded `fields`. Absent conditional
+//! fields and groups have no elements, and the offsets of the fields after them
+//! are where they have moved to.
+//!
+//! A diff has the `old` and `new` layout names, whether it is `compatible` and
+//! the `changes`, each with the `field`, the `kind` of change (`added`,
+//! `removed`, `pointer`, `moved`, `resized`, `literal`, `repeat`, `type`,
+//! `scaling` or `variant`), whether it is `compatible`, and for all but added
+//! and removed fields the `old` and `new` pointer word, bit offset, width,
+//! literal value, repeat, type, scaling or variant value.
+
+use std::fmt::Write;
+
+use crate::diff::{Change, LayoutDiff};
+use crate::export::json_string;
+use crate::layout::{Field, Group, Layout, Scaling};
+use crate::parser::{BitRange, BitSpec, FieldType, LiteralType, Repeat, Word};
+use crate::value::Value;
+use crate::Error;
+
+/// The version of the JSON schema.
+pub const SCHEMA_VERSION: u32 = 1;
+
+fn document(member: &str, json: &str) -> String {
+    format!("{{\"version\": {SCHEMA_VERSION}, \"{member}\": {json}}}")
+}
+
+impl BitSpec {
+    /// The bit specification as a JSON document.
+    pub fn to_json(&self) -> String {
+        document("bit_spec", &bit_spec(self))
+    }
+}
+
+impl Layout {
+    /// The layout as a JSON document.
+    pub fn to_json(&self) -> String {
+        document("layout", &layout(self))
+    }
+
+    /// Decode all the fields and groups in `data`, with those of the variant
+    /// that matches, as a JSON document.
+    pub fn decode_json(&self, data: &[u8]) -> Result<String, Error> {
+        let variant = match self.matched(data)? {
+            Some(variant) => json_string(&variant.name),
+            None => "null".to_string(),
+        };
+        let layout = self.select(data)?;
+        let fields = layout
+            .fields
+            .iter()
+            .map(|field| decoded_field(field, data, layout.word_bits))
+            .collect::<Result<Vec<String>, Error>>()?;
+        let groups = layout
+            .groups
+            .iter()
+            .map(|group| decoded_group(group, data, layout.word_bits))
+            .collect::<Result<Vec<String>, Error>>()?;
+
+        Ok(document(
+            "frame",
+            &format!(
+                "{{\"layout\": {}, \"variant\": {variant}, \"fields\": [{}], \"groups\": [{}]}}",
+                json_string(&layout.name),
+                fields.join(", "),
+                groups.join(", ")
+            ),
+        ))
+    }
+}
+
+impl LayoutDiff {
+    /// The changes as a JSON document.
+    pub fn to_json(&self) -> String {
+        let changes: Vec<String> = self
+            .changes
+            .iter()
+            .map(|change| {
+                format!(
+                    "{{\"field\": {}, {}, \"compatible\": {}{}}}",
+                    json_string(&change.field),
+                    change_kind(&change.change),
+                    change.compatible,
+                    change_values(&change.change)
+                )
+            })
+            .collect();
+
+        document(
+            "diff",
+            &format!(
+                "{{\"old\": {}, \"new\": {}, \"compatible\": {}, \"changes\": [{}]}}",
+                json_string(&self.old),
+                json_string(&self.new),
+                self.is_compatible(),
+                changes.join(", ")
+            ),
+        )
+    }
+}
+
+fn change_kind(change: &Change) -> String {
+    let kind = match change {
+        Change::Added => "added",
+        Change::Removed => "removed",
+        Change::Pointer { .. } => "pointer",
+        Change::Moved { .. } => "moved",
+        Change::Resized { .. } => "resized",
+        Change::Literal { .. } => "literal",
+        Change::Repeat { .. } => "repeat",
+        Change::Type { .. } => "type",
+        Change::Scaling { .. } => "scaling",
+        Change::Variant { .. } => "variant",
+    };
+    format!("\"kind\": \"{kind}\"")
+}
+
+// The old and new members of a change, with a leading separator
+fn change_values(change: &Change) -> String {
+    let (old, new) = match change {
+        Change::Added | Change::Removed => return String::new(),
+        Change::Moved { old, new } | Change::Resized { old, new } => {
+            (old.to_string(), new.to_string())
+        }
+        Change::Literal { old, new } => {
+            let value = |value: &Option<u64>| value.map_or("null".to_string(), |v| v.to_string());
+            (value(old), value(new))
+        }
+        Change::Pointer { old, new } => {
+            let pointer =
+                |pointer: &Option<Word>| pointer.as_ref().map_or("null".to_string(), word);
+            (pointer(old), pointer(new))
+        }
+        Change::Repeat { old, new } => (repeat(old), repeat(new)),
+        Change::Type { old, new } => (field_type(old), field_type(new)),
+        Change::Scaling { old, new } => (scaling(old), scaling(new)),
+        Change::Variant { old, new } => (variant_value(old), variant_value(new)),
+    };
+    format!(", \"old\": {old}, \"new\": {new}")
+}
+
+// The value a variant matches, or null for any other value
+fn variant_value(value: &Option<u64>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+fn bit_spec(bit_spec: &BitSpec) -> String {
+    format!(
+        "{{\"pointer\": {}, \"start\": {}, \"end\": {}, \"repeat\": {}, \"type\": {}}}",
+        bit_spec.pointer.as_ref().map_or("null".to_string(), word),
+        word(&bit_spec.start),
+        bit_spec.end.as_ref().map_or("null".to_string(), word),
+        repeat(&bit_spec.repeat),
+        field_type(&bit_spec.field_type)
+    )
+}
+
+fn word(word: &Word) -> String {
+    format!(
+        "{{\"index\": {}, \"bits\": {}}}",
+        word.index,
+        bit_range(&word.bit_range)
+    )
+}
+
+fn bit_range(bit_range: &BitRange) -> String {
+    match bit_range {
+        BitRange::Single(bit) => format!("{{\"kind\": \"single\", \"bit\": {bit}}}"),
+        BitRange::Range(start, end) => {
+            format!("{{\"kind\": \"range\", \"start\": {start}, \"end\": {end}}}")
+        }
+        BitRange::WholeWord => "{\"kind\": \"whole_word\"}".to_string(),
+        BitRange::Literal(value) => {
+            format!("{{\"kind\": \"literal\", \"literal\": {}}}", literal(value))
+        }
+    }
+}
+
+fn literal(literal: &LiteralType) -> String {
+    let (radix, digits) = match literal {
+        LiteralType::Hex(value) => (16, format!("{value:X}")),
+        LiteralType::Bin(value) => (2, format!("{value:b}")),
+    };
+
+    format!(
+        "{{\"radix\": {radix}, \"digits\": \"{digits}\", \"value\": {}}}",
+        literal.value()
+    )
+}
+
+fn repeat(repeat: &Repeat) -> String {
+    match repeat {
+        Repeat::None => "{\"kind\": \"none\"}".to_string(),
+        Repeat::Fixed(count) => format!("{{\"kind\": \"fixed\", \"count\": {count}}}"),
+        Repeat::Variable {
+            word: count,
+            condition,
+            limit,
+        } => format!(
+            "{{\"kind\": \"variable\", \"word\": {}, \"condition\": \"{condition}\", \"limit\": {limit}}}",
+            word(count)
+        ),
+        Repeat::Conditional { word: condition, value } => format!(
+            "{{\"kind\": \"conditional\", \"word\": {}, \"value\": {value}}}",
+            word(condition)
+        ),
+    }
+}
+
+fn field_type(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::Unsigned => "{\"kind\": \"unsigned\"}".to_string(),
+        FieldType::Bool => "{\"kind\": \"bool\"}".to_string(),
+        FieldType::F16 => "{\"kind\": \"f16\"}".to_string(),
+        FieldType::F32 => "{\"kind\": \"f32\"}".to_string(),
+        FieldType::Fixed {
+            integer_bits,
+            fractional_bits,
+        } => format!(
+            "{{\"kind\": \"fixed\", \"integer_bits\": {integer_bits}, \"fractional_bits\": {fractional_bits}}}"
+        ),
+        FieldType::Enum(variants) => {
+            let variants: Vec<String> = variants
+                .iter()
+                .map(|(value, name)| {
+                    format!("{{\"value\": {value}, \"name\": {}}}", json_string(name))
+                })
+                .collect();
+            format!(
+                "{{\"kind\": \"enum\", \"variants\": [{}]}}",
+                variants.join(", ")
+            )
+        }
+    }
+}
+
+fn layout(layout: &Layout) -> String {
+    let variants = match &layout.variants {
+        Some(variants) => {
+            let list: Vec<String> = variants
+                .variants
+                .iter()
+                .map(|variant| {
+                    format!(
+                        "{{\"name\": {}, \"value\": {}, \"fields\": [{}], \"groups\": [{}]}}",
+                        json_string(&variant.name),
+                        variant_value(&variant.value),
+                        fields(&variant.fields),
+                        groups(&variant.groups)
+                    )
+                })
+                .collect();
+            format!(
+                "{{\"field\": {}, \"variants\": [{}]}}",
+                json_string(&variants.field),
+                list.join(", ")
+            )
+        }
+        None => "null".to_string(),
+    };
+
+    format!(
+        "{{\"name\": {}, \"word_bits\": {}, \"fields\": [{}], \"groups\": [{}], \"variants\": {variants}}}",
+        json_string(&layout.name),
+        layout.word_bits,
+        fields(&layout.fields),
+        groups(&layout.groups)
+    )
+}
+
+fn groups(groups: &[Group]) -> String {
+    let groups: Vec<String> = groups
+        .iter()
+        .map(|group| {
+            format!(
+                "{{\"name\": {}, \"bit_spec\": {}, \"fields\": [{}]}}",
+                json_string(&group.name),
+                bit_spec(&group.bit_spec),
+                fields(&group.fields)
+            )
+        })
+        .collect();
+
+    groups.join(", ")
+}
+
+fn fields(fields: &[Field]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            format!(
+                "{{\"name\": {}, \"bit_spec\": {}, \"scaling\": {}}}",
+                json_string(&field.name),
+                bit_spec(&field.bit_spec),
+                scaling(&field.scaling)
+            )
+        })
+        .collect();
+
+    fields.join(", ")
+}
+
+fn scaling(scaling: &Option<Scaling>) -> String {
+    match scaling {
+        Some(scaling) => format!(
+            "{{\"scale\": {}, \"offset\": {}, \"unit\": {}}}",
+            number(scaling.scale),
+            number(scaling.offset),
+            scaling
+                .unit
+                .as_deref()
+                .map_or("null".to_string(), json_string)
+        ),
+        None => "null".to_string(),
+    }
+}
+
+fn decoded_field(field: &Field, data: &[u8], word_bits: usize) -> Result<String, Error> {
+    let bit_spec = &field.bit_spec;
+    let width = bit_spec.bit_width(word_bits);
+
+    let mut elements = Vec::new();
+    for element in 0..bit_spec.repeats(data, word_bits)? {
+        let raw = bit_spec.extract(data, word_bits, element)?;
+        let mut value = bit_spec.field_type.decode(raw, width)?;
+        if let Some(scaling) = &field.scaling {
+            value = scaling.to_physical(&value)?;
+        }
+        elements.push(format!(
+            "{{\"offset\": {}, \"width\": {width}, \"raw\": {raw}, \"value\": {}}}",
+            bit_spec.element_offset(data, word_bits, element)?,
+            value_json(&value)
+        ));
+    }
+
+    let mut json = format!("{{\"name\": {}", json_string(&field.name));
+    if let Some(unit) = field.scaling.as_ref().and_then(|s| s.unit.as_deref()) {
+        write!(json, ", \"unit\": {}", json_string(unit)).unwrap();
+    }
+    write!(json, ", \"elements\": [{}]}}", elements.join(", ")).unwrap();
+
+    Ok(json)
+}
+
+fn decoded_group(group: &Group, data: &[u8], word_bits: usize) -> Result<String, Error> {
+    let mut elements = Vec::new();
+    for element in 0..group.bit_spec.repeats(data, word_bits)? {
+        let fields = group
+            .element_fields(data, word_bits, element)?
+            .iter()
+            .map(|field| decoded_field(field, data, word_bits))
+            .collect::<Result<Vec<String>, Error>>()?;
+        elements.push(format!("{{\"fields\": [{}]}}", fields.join(", ")));
+    }
+
+    Ok(format!(
+        "{{\"name\": {}, \"elements\": [{}]}}",
+        json_string(&group.name),
+        elements.join(", ")
+    ))
+}
+
+fn value_json(value: &Value) -> String {
+    match value {
+        Value::Unsigned(value) => value.to_string(),
+        Value::Bool(value) => value.to_string(),
+        Value::Float(value) => number(*value),
+        Value::Enum(name) => json_string(name),
+    }
+}
+
+// A float as a JSON number, or null if it is not finite
+fn number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{layout, parse};
+
+    #[test]
+    fn test_bit_spec() {
+        let bit_spec = parse("1[4..7]..2[0];(0[])<4:{0=Off,1=On}").unwrap();
+        assert_eq!(
+            bit_spec.to_json(),
+            "{\"version\": 1, \"bit_spec\": {\
+             \"pointer\": null, \
+             \"start\": {\"index\": 1, \"bits\": {\"kind\": \"range\", \"start\": 4, \"end\": 7}}, \
+             \"end\": {\"index\": 2, \"bits\": {\"kind\": \"single\", \"bit\": 0}}, \
+             \"repeat\": {\"kind\": \"variable\", \"word\": {\"index\": 0, \"bits\": {\"kind\": \"whole_word\"}}, \"condition\": \"<\", \"limit\": 4}, \
+             \"type\": {\"kind\": \"enum\", \"variants\": [{\"value\": 0, \"name\": \"Off\"}, {\"value\": 1, \"name\": \"On\"}]}}}"
+        );
+
+        let bit_spec = parse("0[0xA5]").unwrap();
+        assert!(bit_spec.to_json().contains(
+            "{\"kind\": \"literal\", \"literal\": {\"radix\": 16, \"digits\": \"A5\", \"value\": 165}}"
+        ));
+
+        let bit_spec = parse("@(2[0..3])[]").unwrap();
+        assert!(bit_spec.to_json().contains(
+            "\"pointer\": {\"index\": 2, \"bits\": {\"kind\": \"range\", \"start\": 0, \"end\": 3}}, \
+             \"start\": {\"index\": 0, \"bits\": {\"kind\": \"whole_word\"}}"
+        ));
+    }
+
+    #[test]
+    fn test_layout() {
+        let layouts = layout::parse(
+            "layout Sensor {
+                sync: [0xA5]
+                temperature: 1[] scale=0.5 offset=-40 unit=\"degC\"
+                samples: 3[];(2[0..1])<4
+            }",
+        )
+        .unwrap();
+        let layout = &layouts[0];
+
+        assert!(layout.to_json().starts_with(
+            "{\"version\": 1, \"layout\": {\"name\": \"Sensor\", \"word_bits\": 8, \"fields\": ["
+        ));
+        assert!(layout
+            .to_json()
+            .contains("\"scaling\": {\"scale\": 0.5, \"offset\": -40, \"unit\": \"degC\"}"));
+
+        let frame = layout.decode_json(&[0xA5, 100, 2, 7, 8]).unwrap();
+        assert_eq!(
+            frame,
+            "{\"version\": 1, \"frame\": {\"layout\": \"Sensor\", \"variant\": null, \"fields\": [\
+             {\"name\": \"sync\", \"elements\": [{\"offset\": 0, \"width\": 8, \"raw\": 165, \"value\": 165}]}, \
+             {\"name\": \"temperature\", \"unit\": \"degC\", \"elements\": [{\"offset\": 8, \"width\": 8, \"raw\": 100, \"value\": 10}]}, \
+             {\"name\": \"samples\", \"elements\": [\
+             {\"offset\": 24, \"width\": 8, \"raw\": 7, \"value\": 7}, \
+             {\"offset\": 32, \"width\": 8, \"raw\": 8, \"value\": 8}]}], \"groups\": []}}"
+        );
+        assert_eq!(layout.decode_json(&[0xA5]), Err(Error::OutOfBounds));
+    }
+
+    #[test]
+    fn test_group() {
+        let layouts = layout::parse(
+            "layout Log {
+                count: 0[]
+                records: 1[]..2[];(0[])<4 {
+                    id: 0[]
+                    level: 1[] scale=0.5
+                }
+            }",
+        )
+        .unwrap();
+        let layout = &layouts[0];
+
+        assert!(layout.to_json().contains(
+            "\"groups\": [{\"name\": \"records\", \"bit_spec\": {\"pointer\": null, \
+             \"start\": {\"index\": 1, \"bits\": {\"kind\": \"whole_word\"}}"
+        ));
+
+        let frame = layout.decode_json(&[2, 7, 4, 8, 6]).unwrap();
+        assert!(frame.ends_with(
+            "\"groups\": [{\"name\": \"records\", \"elements\": [\
+             {\"fields\": [{\"name\": \"id\", \"elements\": [{\"offset\": 8, \"width\": 8, \"raw\": 7, \"value\": 7}]}, \
+             {\"name\": \"level\", \"elements\": [{\"offset\": 16, \"width\": 8, \"raw\": 4, \"value\": 2}]}]}, \
+             {\"fields\": [{\"name\": \"id\", \"elements\": [{\"offset\": 24, \"width\": 8, \"raw\": 8, \"value\": 8}]}, \
+             {\"name\": \"level\", \"elements\": [{\"offset\": 32, \"width\": 8, \"raw\": 6, \"value\": 3}]}]}]}]}}"
+        ));
+    }
+
+    #[test]
+    fn test_match() {
+        let layouts = layout::parse(
+            "layout Message {
+                kind: 0[]
+                match kind {
+                    1 => Ping {
+                        id: 1[]
+                    }
+                    _ => Other {
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let layout = &layouts[0];
+
+        assert!(layout.to_json().ends_with(
+            "\"variants\": {\"field\": \"kind\", \"variants\": [\
+             {\"name\": \"Ping\", \"value\": 1, \"fields\": [{\"name\": \"id\", \"bit_spec\": {\"pointer\": null, \
+             \"start\": {\"index\": 1, \"bits\": {\"kind\": \"whole_word\"}}, \"end\": null, \
+             \"repeat\": {\"kind\": \"none\"}, \"type\": {\"kind\": \"unsigned\"}}, \"scaling\": null}], \"groups\": []}, \
+             {\"name\": \"Other\", \"value\": null, \"fields\": [], \"groups\": []}]}}}"
+        ));
+
+        let frame = layout.decode_json(&[1, 7]).unwrap();
+        assert_eq!(
+            frame,
+            "{\"version\": 1, \"frame\": {\"layout\": \"Message\", \"variant\": \"Ping\", \"fields\": [\
+             {\"name\": \"kind\", \"elements\": [{\"offset\": 0, \"width\": 8, \"raw\": 1, \"value\": 1}]}, \
+             {\"name\": \"id\", \"elements\": [{\"offset\": 8, \"width\": 8, \"raw\": 7, \"value\": 7}]}], \"groups\": []}}"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = layout::parse("layout Frame { sync: [0xA5]\n count: 1[] }").unwrap();
+        let new = layout::parse("layout Frame { sync: [0xA6]\n count: 1[];2 }").unwrap();
+
+        assert_eq!(
+            crate::diff::diff(&old[0], &new[0]).to_json(),
+            "{\"version\": 1, \"diff\": {\"old\": \"Frame\", \"new\": \"Frame\", \"compatible\": false, \"changes\": [\
+             {\"field\": \"sync\", \"kind\": \"literal\", \"compatible\": false, \"old\": 165, \"new\": 166}, \
+             {\"field\": \"count\", \"kind\": \"repeat\", \"compatible\": false, \"old\": {\"kind\": \"none\"}, \"new\": {\"kind\": \"fixed\", \"count\": 2}}]}}"
+        );
+    }
+}
@@ -0,0 +1,1728 @@
+//! Layout files giving names and metadata to a set of bit specifications.
+//!
+//! A layout file contains one or more layouts, each being a named block of fields:
+//! ```text
+//! # Comments start with a hash
+//! layout Sensor word_bits=8 {
+//!     sync: [0xA5]
+//!     temperature: 1[]..2[]:q8.8 scale=0.5 offset=-40 unit="degC"
+//!     humidity: 3[] scale=0.5 unit="%"
+//! }
+//! ```
+//! `word_bits` is optional, from 1 to 64, and defaults to 8.
+//!
+//! A field can be given a linear scaling so that the decoded value is the
+//! physical value `raw * scale + offset`, optionally with a unit.
+//!
+//! A group is a block of fields that repeat together, such as the records of
+//! a frame. Its bit specification gives the words of the first element and how
+//! they repeat, and the word indexes of its fields are from the first word of
+//! each element:
+//! ```text
+//! layout Log {
+//!     count: 0[]
+//!     records: 1[]..4[];(0[])<8 {
+//!         id: 0[]
+//!         flags: 1[0..3]
+//!         value: 2[]..3[]
+//!     }
+//! }
+//! ```
+//!
+//! A field or group with a conditional repeat, such as `ext: 1[]..2[];if 0[7]==1`,
+//! takes up no words when it is absent, and the fields and groups after it move
+//! back over its words. [`Layout::resolve`] gives where they are in a frame.
+//!
+//! A match gives the rest of a frame as variants, selected by the raw value of
+//! an unsigned or enumeration field. Each arm has the value it matches, or `_`
+//! for any other value, and the name and fields of the variant, with word
+//! indexes from the start of the frame:
+//! ```text
+//! layout Message {
+//!     kind: 0[]
+//!     match kind {
+//!         1 => Ping {
+//!             id: 1[]
+//!         }
+//!         0x2 => Data {
+//!             length: 1[]
+//!             payload: 2[];(1[])<16
+//!         }
+//!         _ => Other {
+//!         }
+//!     }
+//! }
+//! ```
+//! A layout has at most one match, after its own fields and groups.
+//! [`Layout::select`] gives the layout of a frame with the fields of its
+//! variant.
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till},
+    character::complete::{
+        alpha1, alphanumeric1, char, multispace1, not_line_ending, space0, space1,
+    },
+    combinator::{all_consuming, cut, map, opt, recognize, value, verify},
+    multi::many0,
+    number::complete::double,
+    sequence::{delimited, pair, preceded, terminated, tuple},
+    IResult,
+};
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use crate::parser::{bit_spec, number, BitSpec, FieldType, Repeat};
+use crate::value::Value;
+use crate::Error;
+
+/// Linear scaling from the raw value to a physical value.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Scaling {
+    pub scale: f64,
+    pub offset: f64,
+    pub unit: Option<String>,
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Scaling {
+            scale: 1.0,
+            offset: 0.0,
+            unit: None,
+        }
+    }
+}
+
+impl Scaling {
+    /// Convert a raw decoded value to the physical value.
+    pub fn to_physical(&self, raw: &Value) -> Result<Value, Error> {
+        let raw = match raw {
+            Value::Unsigned(value) => *value as f64,
+            Value::Float(value) => *value,
+            Value::Bool(_) | Value::Enum(_) => return Err(Error::TypeMismatch),
+        };
+
+        Ok(Value::Float(raw * self.scale + self.offset))
+    }
+
+    /// Convert a physical value back to the raw value for the field type,
+    /// rounding to the nearest integer for unsigned fields.
+    pub fn to_raw(&self, physical: &Value, field_type: &FieldType) -> Result<Value, Error> {
+        let physical = match physical {
+            Value::Float(value) => *value,
+            Value::Unsigned(value) => *value as f64,
+            Value::Bool(_) | Value::Enum(_) => return Err(Error::TypeMismatch),
+        };
+        let raw = (physical - self.offset) / self.scale;
+
+        match field_type {
+            FieldType::Unsigned => {
+                let raw = raw.round();
+                if !raw.is_finite() || raw < 0.0 || raw > u64::MAX as f64 {
+                    return Err(Error::ValueOutOfRange);
+                }
+                Ok(Value::Unsigned(raw as u64))
+            }
+            FieldType::F16 | FieldType::F32 | FieldType::Fixed { .. } => Ok(Value::Float(raw)),
+            FieldType::Bool | FieldType::Enum(_) => Err(Error::TypeMismatch),
+        }
+    }
+}
+
+/// A named bit specification in a layout.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    pub name: String,
+    pub bit_spec: BitSpec,
+    pub scaling: Option<Scaling>,
+}
+
+impl Field {
+    /// Decode all repetitions of the field, applying any scaling.
+    pub fn decode(&self, data: &[u8], word_bits: usize) -> Result<Vec<Value>, Error> {
+        let values = self.bit_spec.decode(data, word_bits)?;
+
+        match &self.scaling {
+            Some(scaling) => values.iter().map(|v| scaling.to_physical(v)).collect(),
+            None => Ok(values),
+        }
+    }
+
+    /// Encode `values`, one for each repetition, removing any scaling.
+    pub fn encode(&self, data: &mut [u8], word_bits: usize, values: &[Value]) -> Result<(), Error> {
+        match &self.scaling {
+            Some(scaling) => {
+                let raw = values
+                    .iter()
+                    .map(|v| scaling.to_raw(v, &self.bit_spec.field_type))
+                    .collect::<Result<Vec<Value>, Error>>()?;
+                self.bit_spec.encode(data, word_bits, &raw)
+            }
+            None => self.bit_spec.encode(data, word_bits, values),
+        }
+    }
+
+    /// Parse the text of a value for a single repetition of the field.
+    ///
+    /// Scaled and float fields take a decimal number, boolean fields `true`
+    /// or `false`, enumerations a variant name and unsigned fields an integer
+    /// that can also be given in hexadecimal (`0x`) or binary (`0b`).
+    pub fn parse_value(&self, text: &str) -> Result<Value, Error> {
+        let text = text.trim();
+        if self.scaling.is_some() {
+            return text
+                .parse()
+                .map(Value::Float)
+                .map_err(|_| Error::ValueOutOfRange);
+        }
+
+        match &self.bit_spec.field_type {
+            FieldType::Unsigned => {
+                let digits = text.replace('_', "");
+                let value = if let Some(hex) = digits.strip_prefix("0x") {
+                    u64::from_str_radix(hex, 16)
+                } else if let Some(bin) = digits.strip_prefix("0b") {
+                    u64::from_str_radix(bin, 2)
+                } else {
+                    digits.parse()
+                };
+                value
+                    .map(Value::Unsigned)
+                    .map_err(|_| Error::ValueOutOfRange)
+            }
+            FieldType::Bool => match text {
+                "true" | "1" => Ok(Value::Bool(true)),
+                "false" | "0" => Ok(Value::Bool(false)),
+                _ => Err(Error::TypeMismatch),
+            },
+            FieldType::F16 | FieldType::F32 | FieldType::Fixed { .. } => text
+                .parse()
+                .map(Value::Float)
+                .map_err(|_| Error::TypeMismatch),
+            FieldType::Enum(_) => Ok(Value::Enum(text.to_string())),
+        }
+    }
+}
+
+/// Values of the fields of one element of a group, by field name.
+pub type Element<'a> = Vec<(&'a str, Vec<Value>)>;
+
+/// Named fields that repeat together.
+///
+/// The bit specification gives the words of the first element and how they
+/// repeat. The word indexes of the fields are from the first word of each
+/// element.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Group {
+    pub name: String,
+    pub bit_spec: BitSpec,
+    pub fields: Vec<Field>,
+}
+
+impl Group {
+    /// Get a field by name.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// The fields of the repetition `element` in `data`, with their word
+    /// indexes from the start of the frame.
+    pub fn element_fields(
+        &self,
+        data: &[u8],
+        word_bits: usize,
+        element: usize,
+    ) -> Result<Vec<Field>, Error> {
+        let first_word = self.bit_spec.element_offset(data, word_bits, element)? / word_bits;
+
+        Ok(self
+            .fields
+            .iter()
+            .map(|field| Field {
+                bit_spec: shift(&field.bit_spec, first_word),
+                ..field.clone()
+            })
+            .collect())
+    }
+
+    /// Decode the fields of each repetition, applying any scaling.
+    pub fn decode(&self, data: &[u8], word_bits: usize) -> Result<Vec<Element<'_>>, Error> {
+        (0..self.bit_spec.repeats(data, word_bits)?)
+            .map(|element| {
+                let fields = self.element_fields(data, word_bits, element)?;
+                self.fields
+                    .iter()
+                    .zip(&fields)
+                    .map(|(field, element_field)| {
+                        Ok((field.name.as_str(), element_field.decode(data, word_bits)?))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Encode `elements`, one for each repetition, each with the values of
+    /// the named fields. Literals are set.
+    ///
+    /// For a variable repeat the number of elements is also written to the
+    /// repeat word, and for a conditional repeat the word is set to match.
+    pub fn encode(
+        &self,
+        data: &mut [u8],
+        word_bits: usize,
+        elements: &[Element],
+    ) -> Result<(), Error> {
+        self.bit_spec
+            .encode_repeats(data, word_bits, elements.len())?;
+
+        for (element, values) in elements.iter().enumerate() {
+            let fields = self.element_fields(data, word_bits, element)?;
+            for field in &fields {
+                set_literal(field, data, word_bits)?;
+            }
+            for (name, values) in values {
+                let index = self
+                    .fields
+                    .iter()
+                    .position(|field| field.name == *name)
+                    .ok_or(Error::UnknownField)?;
+                fields[index].encode(data, word_bits, values)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Check the words and repeat of the group, which has no value of its own.
+    fn check(&self, word_bits: usize) -> Result<(), Error> {
+        self.bit_spec.check_bits(word_bits)?;
+        if self.bit_spec.literal().is_some() || self.bit_spec.field_type != FieldType::Unsigned {
+            return Err(Error::TypeMismatch);
+        }
+
+        self.bit_spec.check_repeat(word_bits)
+    }
+}
+
+/// The fields and groups of one arm of a match, which are in the frame when
+/// the field matched on has the value of the arm.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Variant {
+    pub name: String,
+    // The raw value of the field matched on, or none for the arm that
+    // matches any other value
+    pub value: Option<u64>,
+    pub fields: Vec<Field>,
+    pub groups: Vec<Group>,
+}
+
+/// Variants of the rest of a frame, selected by the raw value of a field.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Match {
+    pub field: String,
+    pub variants: Vec<Variant>,
+}
+
+impl Match {
+    /// Get a variant by name.
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+
+    /// The variant for the raw `value`: the one with that value, otherwise
+    /// the one for any other value.
+    pub fn select(&self, value: u64) -> Option<&Variant> {
+        let default = || self.variants.iter().find(|variant| variant.value.is_none());
+        self.variants
+            .iter()
+            .find(|variant| variant.value == Some(value))
+            .or_else(default)
+    }
+}
+
+// The bit specification with its word indexes moved on by `words`.
+fn shift(bit_spec: &BitSpec, words: usize) -> BitSpec {
+    move_words(bit_spec, |index| index + words)
+}
+
+// The bit specification with each word index changed by `to`. The words of an
+// indirect specification are relative to its pointer, so do not move.
+fn move_words(bit_spec: &BitSpec, to: impl Fn(usize) -> usize) -> BitSpec {
+    let mut moved = bit_spec.clone();
+    let mut words = vec![moved.pointer.as_mut()];
+    if bit_spec.pointer.is_none() {
+        words.extend([Some(&mut moved.start), moved.end.as_mut()]);
+    }
+    if let Repeat::Variable { word, .. } | Repeat::Conditional { word, .. } = &mut moved.repeat {
+        words.push(Some(word));
+    }
+    for word in words.into_iter().flatten() {
+        word.index = to(word.index);
+    }
+
+    moved
+}
+
+// Set all the repetitions of a literal field. A conditional literal may be
+// absent, so is only set when given.
+fn set_literal(field: &Field, data: &mut [u8], word_bits: usize) -> Result<(), Error> {
+    if let Repeat::Conditional { .. } = field.bit_spec.repeat {
+        return Ok(());
+    }
+    if let Some(literal) = field.bit_spec.literal() {
+        let literal = literal.value();
+        for element in 0..field.bit_spec.repeat.max_repeats() {
+            field.bit_spec.insert(data, word_bits, element, literal)?;
+        }
+    }
+
+    Ok(())
+}
+
+// The words up to the end of the last repetition. The words pointed to by an
+// indirect specification are only known when decoding, so only the words up
+// to its pointer count.
+fn max_words(bit_spec: &BitSpec) -> usize {
+    match &bit_spec.pointer {
+        Some(pointer) => pointer.index + 1,
+        None => bit_spec.start.index.saturating_add(bit_spec.max_size()),
+    }
+}
+
+// The largest frame that encoding grows to for the words pointed to by an
+// indirect specification, so that a large pointer cannot exhaust memory
+const MAX_FRAME_BYTES: usize = 1 << 20;
+
+// Make room in the frame for the words pointed to by an indirect specification.
+fn reserve(data: &mut Vec<u8>, bit_spec: &BitSpec, word_bits: usize) -> Result<(), Error> {
+    if let Some(pointer) = &bit_spec.pointer {
+        let size = usize::try_from(pointer.extract(data, word_bits)?)
+            .ok()
+            .and_then(|index| index.checked_add(bit_spec.start.index))
+            .and_then(|words| words.checked_add(bit_spec.max_size()))
+            .and_then(|words| words.checked_mul(word_bits))
+            .map(|bits| bits.div_ceil(8))
+            .filter(|size| *size <= MAX_FRAME_BYTES)
+            .ok_or(Error::OutOfBounds)?;
+        data.resize(data.len().max(size), 0);
+    }
+
+    Ok(())
+}
+
+// Check the fields, giving each problem with the field name after `prefix`.
+fn check_fields(fields: &[Field], word_bits: usize, prefix: &str) -> Vec<(String, Error)> {
+    let mut problems = Vec::new();
+
+    for (i, field) in fields.iter().enumerate() {
+        let name = format!("{prefix}{}", field.name);
+        if fields[..i].iter().any(|f| f.name == field.name) {
+            problems.push((name.clone(), Error::DuplicateField));
+        }
+        if let Err(error) = field.bit_spec.validate(word_bits) {
+            problems.push((name.clone(), error));
+        }
+        if let Some(scaling) = &field.scaling {
+            if matches!(
+                field.bit_spec.field_type,
+                FieldType::Bool | FieldType::Enum(_)
+            ) {
+                problems.push((name, Error::TypeMismatch));
+            } else if scaling.scale == 0.0 {
+                problems.push((name, Error::ValueOutOfRange));
+            }
+        }
+    }
+
+    problems
+}
+
+// Check the fields and groups, giving each problem with the name after
+// `prefix`. The names in `taken` are already used.
+fn check_items(
+    fields: &[Field],
+    groups: &[Group],
+    taken: &[&str],
+    word_bits: usize,
+    prefix: &str,
+) -> Vec<(String, Error)> {
+    let mut problems = check_fields(fields, word_bits, prefix);
+    for field in fields {
+        if taken.contains(&field.name.as_str()) {
+            problems.push((format!("{prefix}{}", field.name), Error::DuplicateField));
+        }
+    }
+
+    for (i, group) in groups.iter().enumerate() {
+        let name = format!("{prefix}{}", group.name);
+        if taken.contains(&group.name.as_str())
+            || fields.iter().any(|f| f.name == group.name)
+            || groups[..i].iter().any(|g| g.name == group.name)
+        {
+            problems.push((name.clone(), Error::DuplicateField));
+        }
+        if let Err(error) = group.check(word_bits) {
+            problems.push((name.clone(), error));
+        }
+
+        let prefix = format!("{name}.");
+        problems.extend(check_fields(&group.fields, word_bits, &prefix));
+        for field in &group.fields {
+            let name = format!("{prefix}{}", field.name);
+            if max_words(&field.bit_spec) > group.bit_spec.word_count() {
+                problems.push((name.clone(), Error::OutsideGroup));
+            }
+            // The fields of an element do not move, so cannot be conditional
+            if let Repeat::Conditional { .. } = field.bit_spec.repeat {
+                problems.push((name, Error::Unsupported));
+            }
+        }
+    }
+
+    problems
+}
+
+/// A named set of fields and groups, with the variants of the rest of the
+/// frame if it has a match.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Layout {
+    pub name: String,
+    pub word_bits: usize,
+    pub fields: Vec<Field>,
+    pub groups: Vec<Group>,
+    pub variants: Option<Match>,
+}
+
+impl Layout {
+    /// Get a field by name.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    // Check that the layout only has fields at fixed positions, for outputs
+    // that cannot represent groups, variants or the fields moved by a condition.
+    pub(crate) fn check_supported(&self) -> Result<(), Error> {
+        let conditional = self
+            .fields
+            .iter()
+            .any(|field| matches!(field.bit_spec.repeat, Repeat::Conditional { .. }));
+        if self.groups.is_empty() && self.variants.is_none() && !conditional {
+            Ok(())
+        } else {
+            Err(Error::Unsupported)
+        }
+    }
+
+    /// Get a group by name.
+    pub fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// Get the max size in bytes of an array that could contain all the fields
+    /// and groups of any variant.
+    ///
+    /// Indirect fields only count up to their pointer, as the words they point
+    /// to are only known when decoding.
+    pub fn max_size(&self) -> usize {
+        let variants = self.variants.iter().flat_map(|variants| &variants.variants);
+        let fields = self
+            .fields
+            .iter()
+            .chain(variants.clone().flat_map(|variant| &variant.fields))
+            .map(|field| &field.bit_spec);
+        let groups = self
+            .groups
+            .iter()
+            .chain(variants.flat_map(|variant| &variant.groups))
+            .map(|group| &group.bit_spec);
+
+        fields
+            .chain(groups)
+            .map(max_words)
+            .max()
+            .map_or(0, |words| words.saturating_mul(self.word_bits).div_ceil(8))
+    }
+
+    /// Check all the fields, groups and variants, returning the name of each
+    /// with a problem. The fields of a group are named `group.field` and those
+    /// of a variant `Variant.field`.
+    pub fn validate(&self) -> Vec<(String, Error)> {
+        // No field can be checked without the word size
+        if !(1..=64).contains(&self.word_bits) {
+            return vec![("word_bits".to_string(), Error::InvalidWordSize)];
+        }
+
+        let mut problems = check_items(&self.fields, &self.groups, &[], self.word_bits, "");
+        let Some(variants) = &self.variants else {
+            return problems;
+        };
+
+        // The field matched on is read once as a raw value
+        let width = match self.field(&variants.field) {
+            Some(field) => {
+                let bit_spec = &field.bit_spec;
+                if bit_spec.repeat != Repeat::None
+                    || field.scaling.is_some()
+                    || !matches!(
+                        bit_spec.field_type,
+                        FieldType::Unsigned | FieldType::Enum(_)
+                    )
+                {
+                    problems.push((field.name.clone(), Error::TypeMismatch));
+                }
+                bit_spec.bit_width(self.word_bits)
+            }
+            None => {
+                problems.push((variants.field.clone(), Error::UnknownField));
+                64
+            }
+        };
+
+        let fields = self.fields.iter().map(|field| field.name.as_str());
+        let taken: Vec<&str> = fields
+            .chain(self.groups.iter().map(|group| group.name.as_str()))
+            .collect();
+        for (i, variant) in variants.variants.iter().enumerate() {
+            if variants.variants[..i]
+                .iter()
+                .any(|v| v.name == variant.name || v.value == variant.value)
+            {
+                problems.push((variant.name.clone(), Error::DuplicateVariant));
+            }
+            if let Some(value) = variant.value {
+                if width < 64 && value >> width != 0 {
+                    problems.push((variant.name.clone(), Error::ValueOutOfRange));
+                }
+            }
+
+            let prefix = format!("{}.", variant.name);
+            problems.extend(check_items(
+                &variant.fields,
+                &variant.groups,
+                &taken,
+                self.word_bits,
+                &prefix,
+            ));
+        }
+
+        problems
+    }
+
+    /// The variant of `data`, selected by the raw value of the field matched
+    /// on, or none if the layout has no match.
+    pub fn matched(&self, data: &[u8]) -> Result<Option<&Variant>, Error> {
+        let Some(variants) = &self.variants else {
+            return Ok(None);
+        };
+        let layout = self.resolve(data)?;
+        let field = layout.field(&variants.field).ok_or(Error::UnknownField)?;
+        let value = field.bit_spec.extract(data, self.word_bits, 0)?;
+
+        variants
+            .select(value)
+            .map(Some)
+            .ok_or(Error::UnknownVariant)
+    }
+
+    /// The layout with the fields and groups of the named variant in place of
+    /// the match.
+    pub fn with_variant(&self, name: &str) -> Result<Layout, Error> {
+        let variant = self
+            .variants
+            .as_ref()
+            .and_then(|variants| variants.variant(name))
+            .ok_or(Error::UnknownVariant)?;
+
+        let mut layout = self.clone();
+        layout.fields.extend(variant.fields.iter().cloned());
+        layout.groups.extend(variant.groups.iter().cloned());
+        layout.variants = None;
+
+        Ok(layout)
+    }
+
+    /// The layout of `data`, with the fields and groups of the variant that
+    /// matches, moved as [`Layout::resolve`] does.
+    pub fn select(&self, data: &[u8]) -> Result<Layout, Error> {
+        match self.matched(data)? {
+            Some(variant) => self.with_variant(&variant.name)?.resolve(data),
+            None => self.resolve(data),
+        }
+    }
+
+    /// The layout of `data`, with the fields and groups after any absent
+    /// conditional ones moved back over their words.
+    ///
+    /// Absent fields and groups are kept, and have no repetitions in `data`.
+    pub fn resolve(&self, data: &[u8]) -> Result<Layout, Error> {
+        self.resolve_with(|_, bit_spec| Ok(bit_spec.repeats(data, self.word_bits)? == 0))
+    }
+
+    // Move the fields and groups back over the words of the conditional ones
+    // that are `absent`, taking them in the order of their first word so that
+    // a condition is read from where its word has moved to.
+    pub(crate) fn resolve_with(
+        &self,
+        mut absent: impl FnMut(&str, &BitSpec) -> Result<bool, Error>,
+    ) -> Result<Layout, Error> {
+        let mut resolved = self.clone();
+        let mut specs: Vec<(&str, &mut BitSpec)> = resolved
+            .fields
+            .iter_mut()
+            .map(|field| (field.name.as_str(), &mut field.bit_spec))
+            .chain(
+                resolved
+                    .groups
+                    .iter_mut()
+                    .map(|group| (group.name.as_str(), &mut group.bit_spec)),
+            )
+            .collect();
+        specs.sort_by_key(|(_, bit_spec)| match &bit_spec.pointer {
+            Some(pointer) => pointer.index,
+            None => bit_spec.start.index,
+        });
+
+        // The words of absent fields, before any were moved
+        let mut removed = BTreeSet::new();
+        for (name, bit_spec) in specs {
+            let moved = move_words(bit_spec, |index| index - removed.range(..index).count());
+            if matches!(bit_spec.repeat, Repeat::Conditional { .. })
+                && bit_spec.pointer.is_none()
+                && absent(name, &moved)?
+            {
+                let start = bit_spec.start.index;
+                removed.extend(start..start + bit_spec.word_count());
+            }
+            *bit_spec = moved;
+        }
+
+        Ok(resolved)
+    }
+
+    /// Encode a frame with the values of the named fields. Literals are
+    /// set and any fields not given are zero.
+    ///
+    /// The frame is as long as needed for the words of all the fields.
+    /// Indirect fields are written after the others, so the values given
+    /// need to set their pointers. A pointer that would make the frame larger
+    /// than 1 MiB gives [`Error::OutOfBounds`]. The first problem found by
+    /// [`Layout::validate`] is given for an invalid layout.
+    pub fn encode(&self, values: &[(&str, Vec<Value>)]) -> Result<Vec<u8>, Error> {
+        self.encode_with_groups(values, &[])
+    }
+
+    /// Encode a frame with the values of the named fields and the elements of
+    /// the named groups, as [`Group::encode`] does.
+    ///
+    /// Groups that are not given have as few elements as their repeat allows,
+    /// which for a variable repeat is none. Conditional fields and groups are
+    /// present when given a value or element, with the fields after absent
+    /// ones moved back as [`Layout::resolve`] does.
+    pub fn encode_with_groups(
+        &self,
+        values: &[(&str, Vec<Value>)],
+        groups: &[(&str, Vec<Element>)],
+    ) -> Result<Vec<u8>, Error> {
+        let present = |name: &str| {
+            let mut given = values
+                .iter()
+                .map(|(name, values)| (*name, values.len()))
+                .chain(
+                    groups
+                        .iter()
+                        .map(|(name, elements)| (*name, elements.len())),
+                );
+            given.any(|(given, count)| given == name && count > 0)
+        };
+        if let Some((_, error)) = self.validate().into_iter().next() {
+            return Err(error);
+        }
+        let layout = self.resolve_with(|name, _| Ok(!present(name)))?;
+        let mut data = vec![0; self.max_size()];
+
+        // The conditions of absent fields and groups are set first, so that
+        // any values given for their words are kept and then checked
+        let fields = layout
+            .fields
+            .iter()
+            .map(|field| (&field.name, &field.bit_spec));
+        let specs = fields.chain(
+            layout
+                .groups
+                .iter()
+                .map(|group| (&group.name, &group.bit_spec)),
+        );
+        for (name, bit_spec) in specs {
+            if let Repeat::Conditional { .. } = bit_spec.repeat {
+                if !present(name) {
+                    bit_spec.encode_repeats(&mut data, layout.word_bits, 0)?;
+                }
+            }
+        }
+
+        for indirect in [false, true] {
+            let fields = layout
+                .fields
+                .iter()
+                .filter(|field| field.bit_spec.pointer.is_some() == indirect);
+            for field in fields {
+                // The frame grows to hold the words pointed to
+                reserve(&mut data, &field.bit_spec, layout.word_bits)?;
+                set_literal(field, &mut data, layout.word_bits)?;
+            }
+
+            for (name, values) in values {
+                let field = layout.field(name).ok_or(Error::UnknownField)?;
+                if field.bit_spec.pointer.is_some() == indirect {
+                    field.encode(&mut data, layout.word_bits, values)?;
+                }
+            }
+        }
+
+        for (name, _) in groups {
+            layout.group(name).ok_or(Error::UnknownField)?;
+        }
+        for group in &layout.groups {
+            let fewest;
+            let elements = match groups.iter().find(|(name, _)| *name == group.name) {
+                Some((_, elements)) => elements,
+                None => {
+                    fewest = match group.bit_spec.repeat {
+                        Repeat::Variable { .. } | Repeat::Conditional { .. } => Vec::new(),
+                        ref repeat => vec![Vec::new(); repeat.max_repeats()],
+                    };
+                    &fewest
+                }
+            };
+            reserve(&mut data, &group.bit_spec, layout.word_bits)?;
+            group.encode(&mut data, layout.word_bits, elements)?;
+        }
+        if self.resolve(&data)? != layout {
+            return Err(Error::ConditionMismatch);
+        }
+
+        let mut end = 0;
+        let fields = layout.fields.iter().map(|field| &field.bit_spec);
+        for bit_spec in fields.chain(layout.groups.iter().map(|group| &group.bit_spec)) {
+            end = end.max(bit_spec.end_offset(&data, layout.word_bits)?);
+        }
+        data.truncate((end.div_ceil(layout.word_bits) * layout.word_bits).div_ceil(8));
+
+        Ok(data)
+    }
+
+    /// Encode a frame of the named variant with the values of the named
+    /// fields and the elements of the named groups, as
+    /// [`Layout::encode_with_groups`] does.
+    ///
+    /// The field matched on is given the value of the variant unless a value
+    /// is given for it, which is needed for the variant of any other value.
+    pub fn encode_variant(
+        &self,
+        variant: &str,
+        values: &[(&str, Vec<Value>)],
+        groups: &[(&str, Vec<Element>)],
+    ) -> Result<Vec<u8>, Error> {
+        let layout = self.with_variant(variant)?;
+        let variants = self.variants.as_ref().ok_or(Error::UnknownVariant)?;
+        let field = layout.field(&variants.field).ok_or(Error::UnknownField)?;
+
+        let mut values = values.to_vec();
+        let value = variants.variant(variant).and_then(|variant| variant.value);
+        if let Some(value) = value {
+            if !values.iter().any(|(name, _)| *name == field.name) {
+                let width = field.bit_spec.bit_width(self.word_bits);
+                let value = field.bit_spec.field_type.decode(value, width)?;
+                values.push((&field.name, vec![value]));
+            }
+        }
+
+        let data = layout.encode_with_groups(&values, groups)?;
+        if self.matched(&data)?.map(|matched| matched.name.as_str()) != Some(variant) {
+            return Err(Error::ConditionMismatch);
+        }
+
+        Ok(data)
+    }
+}
+
+// The form of a field in a layout file
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.bit_spec)?;
+        if let Some(scaling) = &self.scaling {
+            if scaling.scale != 1.0 || (scaling.offset == 0.0 && scaling.unit.is_none()) {
+                write!(f, " scale={}", scaling.scale)?;
+            }
+            if scaling.offset != 0.0 {
+                write!(f, " offset={}", scaling.offset)?;
+            }
+            if let Some(unit) = &scaling.unit {
+                write!(f, " unit=\"{unit}\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The form of a layout in a layout file
+impl Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "layout {}", self.name)?;
+        if self.word_bits != 8 {
+            write!(f, " word_bits={}", self.word_bits)?;
+        }
+        writeln!(f, " {{")?;
+        write_items(f, &self.fields, &self.groups, "    ")?;
+        if let Some(variants) = &self.variants {
+            writeln!(f, "    match {} {{", variants.field)?;
+            for variant in &variants.variants {
+                match variant.value {
+                    Some(value) => write!(f, "        {value}")?,
+                    None => write!(f, "        _")?,
+                }
+                writeln!(f, " => {} {{", variant.name)?;
+                write_items(f, &variant.fields, &variant.groups, "            ")?;
+                writeln!(f, "        }}")?;
+            }
+            writeln!(f, "    }}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+// Write a line for each field and each group, starting with `indent`
+fn write_items(
+    f: &mut std::fmt::Formatter<'_>,
+    fields: &[Field],
+    groups: &[Group],
+    indent: &str,
+) -> std::fmt::Result {
+    for field in fields {
+        writeln!(f, "{indent}{field}")?;
+    }
+    for group in groups {
+        writeln!(f, "{indent}{}: {} {{", group.name, group.bit_spec)?;
+        for field in &group.fields {
+            writeln!(f, "{indent}    {field}")?;
+        }
+        writeln!(f, "{indent}}}")?;
+    }
+    Ok(())
+}
+
+/// Parse a layout file.
+pub fn parse(input: &str) -> Result<Vec<Layout>, Error> {
+    let (_, layouts) = all_consuming(preceded(blank, many0(terminated(layout, blank))))(input)
+        .map_err(|error| {
+            let remaining = match error {
+                nom::Err::Error(e) | nom::Err::Failure(e) => e.input,
+                nom::Err::Incomplete(_) => "",
+            };
+            let consumed = &input[..input.len() - remaining.len()];
+            Error::LayoutParseError {
+                line: consumed.matches('\n').count() + 1,
+            }
+        })?;
+
+    Ok(layouts)
+}
+
+// Whitespace, new lines and comments
+fn blank(input: &str) -> IResult<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            multispace1,
+            recognize(pair(char('#'), not_line_ending)),
+        ))),
+    )(input)
+}
+
+fn identifier(input: &str) -> IResult<&str, &str> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0(alt((alphanumeric1, tag("_")))),
+    ))(input)
+}
+
+fn quoted(input: &str) -> IResult<&str, &str> {
+    delimited(char('"'), take_till(|c| c == '"' || c == '\n'), char('"'))(input)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Attribute {
+    Scale(f64),
+    Offset(f64),
+    Unit(String),
+}
+
+// attribute = "scale=" number | "offset=" number | "unit=" quoted
+fn attribute(input: &str) -> IResult<&str, Attribute> {
+    alt((
+        map(preceded(tag("scale="), double), Attribute::Scale),
+        map(preceded(tag("offset="), double), Attribute::Offset),
+        map(preceded(tag("unit="), quoted), |unit| {
+            Attribute::Unit(unit.to_string())
+        }),
+    ))(input)
+}
+
+// field = identifier ":" bit_spec {attribute}
+fn field(input: &str) -> IResult<&str, Field> {
+    let (remaining, (name, _, _, _, bit_spec, attributes)) = tuple((
+        identifier,
+        space0,
+        char(':'),
+        space0,
+        bit_spec,
+        many0(preceded(space1, attribute)),
+    ))(input)?;
+
+    let scaling = if attributes.is_empty() {
+        None
+    } else {
+        let mut scaling = Scaling::default();
+        for attribute in attributes {
+            match attribute {
+                Attribute::Scale(scale) => scaling.scale = scale,
+                Attribute::Offset(offset) => scaling.offset = offset,
+                Attribute::Unit(unit) => scaling.unit = Some(unit),
+            }
+        }
+        Some(scaling)
+    };
+
+    Ok((
+        remaining,
+        Field {
+            name: name.to_string(),
+            bit_spec,
+            scaling,
+        },
+    ))
+}
+
+// group = identifier ":" bit_spec "{" {field} "}"
+fn group(input: &str) -> IResult<&str, Group> {
+    let (remaining, (name, _, _, _, bit_spec, _, _, _, fields, _)) = tuple((
+        identifier,
+        space0,
+        char(':'),
+        space0,
+        bit_spec,
+        space0,
+        char('{'),
+        blank,
+        many0(terminated(field, blank)),
+        char('}'),
+    ))(input)?;
+
+    Ok((
+        remaining,
+        Group {
+            name: name.to_string(),
+            bit_spec,
+            fields,
+        },
+    ))
+}
+
+// The fields, groups and match of a layout, which are kept apart
+enum Item {
+    Field(Field),
+    Group(Group),
+    Match(Match),
+}
+
+// member = group | field
+fn member(input: &str) -> IResult<&str, Item> {
+    // A group starts like a field, so needs to be tried first
+    alt((map(group, Item::Group), map(field, Item::Field)))(input)
+}
+
+// The fields and groups of the members
+fn split(items: Vec<Item>) -> (Vec<Field>, Vec<Group>, Vec<Match>) {
+    let (mut fields, mut groups, mut matches) = (Vec::new(), Vec::new(), Vec::new());
+    for item in items {
+        match item {
+            Item::Field(field) => fields.push(field),
+            Item::Group(group) => groups.push(group),
+            Item::Match(variants) => matches.push(variants),
+        }
+    }
+    (fields, groups, matches)
+}
+
+// variant = ("_" | number) "=>" identifier "{" {member} "}"
+fn variant(input: &str) -> IResult<&str, Variant> {
+    let (remaining, (value, _, _, _, name, _, _, _, members, _)) = tuple((
+        alt((value(None, char('_')), map(number, Some))),
+        space0,
+        tag("=>"),
+        space0,
+        identifier,
+        space0,
+        char('{'),
+        blank,
+        many0(terminated(member, blank)),
+        char('}'),
+    ))(input)?;
+
+    let (fields, groups, _) = split(members);
+    Ok((
+        remaining,
+        Variant {
+            name: name.to_string(),
+            value,
+            fields,
+            groups,
+        },
+    ))
+}
+
+// match = "match" identifier "{" {variant} "}"
+fn variants(input: &str) -> IResult<&str, Match> {
+    let (remaining, (_, field, _, _, _, variants, _)) = tuple((
+        terminated(tag("match"), space1),
+        identifier,
+        space0,
+        char('{'),
+        blank,
+        many0(terminated(variant, blank)),
+        char('}'),
+    ))(input)?;
+
+    Ok((
+        remaining,
+        Match {
+            field: field.to_string(),
+            variants,
+        },
+    ))
+}
+
+// item = match | member
+fn item(input: &str) -> IResult<&str, Item> {
+    alt((map(variants, Item::Match), member))(input)
+}
+
+// layout = "layout" identifier ["word_bits=" u8] "{" {item} "}"
+//
+// Words are read into a u64, so have from 1 to 64 bits
+fn layout(input: &str) -> IResult<&str, Layout> {
+    // Once the keyword is found any error is in this layout
+    let (remaining, (_, (name, word_bits, _, _, _, items, _))) = pair(
+        terminated(tag("layout"), space1),
+        cut(tuple((
+            identifier,
+            opt(preceded(
+                pair(space1, tag("word_bits=")),
+                verify(nom::character::complete::u8, |bits| (1..=64).contains(bits)),
+            )),
+            space0,
+            char('{'),
+            blank,
+            many0(terminated(item, blank)),
+            char('}'),
+        ))),
+    )(input)?;
+
+    // There is only one match, as it selects the rest of the frame
+    let (fields, groups, mut matches) = split(items);
+    if matches.len() > 1 {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+
+    Ok((
+        remaining,
+        Layout {
+            name: name.to_string(),
+            word_bits: word_bits.unwrap_or(8).into(),
+            fields,
+            groups,
+            variants: matches.pop(),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{BitRange, Repeat, Word};
+
+    #[test]
+    fn test_attribute() {
+        let (_, r) = attribute("scale=0.25").unwrap();
+        assert_eq!(r, Attribute::Scale(0.25));
+
+        let (_, r) = attribute("offset=-40").unwrap();
+        assert_eq!(r, Attribute::Offset(-40.0));
+
+        let (_, r) = attribute("unit=\"degC\"").unwrap();
+        assert_eq!(r, Attribute::Unit("degC".to_string()));
+
+        assert!(attribute("colour=red").is_err());
+    }
+
+    #[test]
+    fn test_field() {
+        let (_, r) = field("level : 3[] scale=0.5 unit=\"V\"").unwrap();
+        let expected = Field {
+            name: "level".to_string(),
+            bit_spec: BitSpec {
+                pointer: None,
+                start: Word {
+                    index: 3,
+                    bit_range: BitRange::WholeWord,
+                },
+                end: None,
+                repeat: Repeat::None,
+                field_type: FieldType::Unsigned,
+            },
+            scaling: Some(Scaling {
+                scale: 0.5,
+                offset: 0.0,
+                unit: Some("V".to_string()),
+            }),
+        };
+        assert_eq!(r, expected);
+
+        let (_, r) = field("flag: 0[7]:bool").unwrap();
+        assert_eq!(r.scaling, None);
+        assert_eq!(r.bit_spec.field_type, FieldType::Bool);
+    }
+
+    #[test]
+    fn test_parse() {
+        let data = "
+            # A comment
+            layout Sensor {
+                sync: [0xA5]   # Start of frame
+                temperature: 1[]..2[]:q8.8 scale=0.5 offset=-40 unit=\"degC\"
+            }
+
+            layout Wide word_bits=16 {
+            }
+        ";
+        let layouts = parse(data).unwrap();
+        assert_eq!(layouts.len(), 2);
+        assert_eq!(layouts[0].name, "Sensor");
+        assert_eq!(layouts[0].word_bits, 8);
+        assert_eq!(layouts[0].fields.len(), 2);
+        assert!(layouts[0].field("temperature").unwrap().scaling.is_some());
+        assert_eq!(layouts[1].name, "Wide");
+        assert_eq!(layouts[1].word_bits, 16);
+
+        assert_eq!(
+            parse("layout Broken {\n sync: [0xA5]\n count 1[]\n}"),
+            Err(Error::LayoutParseError { line: 3 })
+        );
+        assert_eq!(
+            parse("layout A {}\n\nlayer B {}"),
+            Err(Error::LayoutParseError { line: 3 })
+        );
+        assert_eq!(
+            parse("layout A word_bits=0 {}"),
+            Err(Error::LayoutParseError { line: 1 })
+        );
+        assert_eq!(
+            parse("layout A word_bits=65 {}"),
+            Err(Error::LayoutParseError { line: 1 })
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let text = "layout Sensor word_bits=16 {
+    sync: 0[0xA5]
+    temperature: 1[]:q8.8 scale=0.5 offset=-40 unit=\"degC\"
+    samples: 2[];(0[8..11])<4
+}";
+        let layouts = parse(text).unwrap();
+        assert_eq!(layouts[0].to_string(), text);
+        assert_eq!(parse(&layouts[0].to_string()).unwrap(), layouts);
+    }
+
+    #[test]
+    fn test_validate() {
+        let layouts = parse(
+            "layout Checked {
+                sync: [0x1A5]
+                flag: 1[0..1]:bool
+                flag: 1[2]
+                level: 2[] scale=0
+                mode: 3[0..1]:{0=Off,1=On} scale=2
+                fine: 4[]
+            }",
+        )
+        .unwrap();
+
+        let problems = layouts[0].validate();
+        assert_eq!(
+            problems,
+            vec![
+                ("sync".to_string(), Error::ValueOutOfRange),
+                ("flag".to_string(), Error::TypeMismatch),
+                ("flag".to_string(), Error::DuplicateField),
+                ("level".to_string(), Error::ValueOutOfRange),
+                ("mode".to_string(), Error::TypeMismatch),
+            ]
+        );
+
+        let layout = Layout {
+            word_bits: 0,
+            ..layouts[0].clone()
+        };
+        assert_eq!(
+            layout.validate(),
+            vec![("word_bits".to_string(), Error::InvalidWordSize)]
+        );
+    }
+
+    #[test]
+    fn test_parse_value() {
+        let layouts = parse(
+            "layout Values {
+                count: 0[]
+                flag: 1[0]:bool
+                mode: 1[1..2]:{0=Off,1=On}
+                level: 2[] scale=0.5
+            }",
+        )
+        .unwrap();
+        let layout = &layouts[0];
+
+        let count = layout.field("count").unwrap();
+        assert_eq!(count.parse_value("0x1F"), Ok(Value::Unsigned(31)));
+        assert_eq!(count.parse_value("0b1_0"), Ok(Value::Unsigned(2)));
+        assert_eq!(count.parse_value("12"), Ok(Value::Unsigned(12)));
+        assert_eq!(count.parse_value("-1"), Err(Error::ValueOutOfRange));
+
+        let flag = layout.field("flag").unwrap();
+        assert_eq!(flag.parse_value("true"), Ok(Value::Bool(true)));
+
+        let mode = layout.field("mode").unwrap();
+        assert_eq!(mode.parse_value("On"), Ok(Value::Enum("On".to_string())));
+
+        let level = layout.field("level").unwrap();
+        assert_eq!(level.parse_value("2.5"), Ok(Value::Float(2.5)));
+    }
+
+    #[test]
+    fn test_encode() {
+        let layouts = parse(
+            "layout Frame {
+                sync: [0xA5]
+                count: 1[]
+                samples: 2[]..3[];(1[])<4
+                flag: 4[0]:bool
+            }",
+        )
+        .unwrap();
+        let layout = &layouts[0];
+        assert_eq!(layout.max_size(), 8);
+
+        let data = layout
+            .encode(&[(
+                "samples",
+                vec![Value::Unsigned(0x1234), Value::Unsigned(0x5678)],
+            )])
+            .unwrap();
+        assert_eq!(data, [0xA5, 2, 0x34, 0x12, 0x78, 0x56]);
+
+        let data = layout.encode(&[("flag", vec![Value::Bool(true)])]).unwrap();
+        assert_eq!(data, [0xA5, 0, 0, 0, 1]);
+
+        assert_eq!(
+            layout.encode(&[("missing", vec![])]),
+            Err(Error::UnknownField)
+        );
+
+        // Invalid fields are found before the frame is sized
+        let layouts = parse("layout A { a: 4[]..2[] }").unwrap();
+        assert_eq!(layouts[0].max_size(), 5);
+        assert_eq!(layouts[0].encode(&[]), Err(Error::InvalidBitSpec));
+    }
+
+    #[test]
+    fn test_encode_indirect() {
+        let layouts = parse(
+            "layout Message {
+                length: 0[]
+                offset: 1[]
+                payload: @(1[])[];(0[])<8
+            }",
+        )
+        .unwrap();
+        let layout = &layouts[0];
+        assert_eq!(layout.max_size(), 2);
+
+        let data = layout
+            .encode(&[
+                ("payload", vec![Value::Unsigned(7), Value::Unsigned(8)]),
+                ("length", vec![Value::Unsigned(2)]),
+                ("offset", vec![Value::Unsigned(3)]),
+            ])
+            .unwrap();
+        assert_eq!(data, [2, 3, 0, 7, 8]);
+
+        let payload = layout.field("payload").unwrap();
+        assert_eq!(
+            payload.decode(&data, 8).unwrap(),
+            vec![Value::Unsigned(7), Value::Unsigned(8)]
+        );
+
+        // The pointer has to be given, as zero points into the header
+        assert_eq!(
+            layout.encode(&[("length", vec![Value::Unsigned(1)])]),
+            Err(Error::InvalidPointer)
+        );
+
+        // The frame does not grow without limit for a large pointer
+        let layouts = parse(
+            "layout Wide word_bits=32 {
+                offset: 0[]
+                payload: @(0[])[]
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            layouts[0].encode(&[
+                ("offset", vec![Value::Unsigned(0xFFFF_FFF0)]),
+                ("payload", vec![Value::Unsigned(1)]),
+            ]),
+            Err(Error::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_group() {
+        let text = "layout Log {
+    count: 0[]
+    crc: 9[]
+    records: 1[]..4[];(0[])<3 {
+        id: 0[]
+        flags: 1[0..3]
+        value: 2[]..3[] scale=0.5
+    }
+}";
+        let layouts = parse(text).unwrap();
+        let layout = &layouts[0];
+        assert_eq!(layout.to_string(), text);
+        assert!(layout.validate().is_empty());
+        assert_eq!(layout.max_size(), 10);
+
+        let records = layout.group("records").unwrap();
+        assert_eq!(records.fields.len(), 3);
+        let fields = records.element_fields(&[1; 10], 8, 1).unwrap();
+        assert_eq!(fields[2].bit_spec.to_string(), "7[]..8[]");
+
+        let elements = vec![
+            vec![("id", vec![Value::Unsigned(7)])],
+            vec![
+                ("id", vec![Value::Unsigned(8)]),
+                ("value", vec![Value::Float(2.0)]),
+            ],
+        ];
+        let data = layout
+            .encode_with_groups(
+                &[("crc", vec![Value::Unsigned(0xFF)])],
+                &[("records", elements)],
+            )
+            .unwrap();
+        assert_eq!(data, [2, 7, 0, 0, 0, 8, 0, 4, 0, 0xFF]);
+
+        let decoded = records.decode(&data, 8).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(
+            decoded[1],
+            vec![
+                ("id", vec![Value::Unsigned(8)]),
+                ("flags", vec![Value::Unsigned(0)]),
+                ("value", vec![Value::Float(2.0)]),
+            ]
+        );
+
+        // Without the group there are no records
+        assert_eq!(layout.encode(&[]).unwrap(), [0; 10]);
+        assert_eq!(
+            layout.encode_with_groups(&[], &[("records", vec![Vec::new(); 3])]),
+            Err(Error::RepeatOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_conditional() {
+        let text = "layout Packet {
+    flags: 0[]
+    ext: 1[]..2[];if 0[7]==1
+    length: 3[]
+    payload: 4[];(3[])<4
+}";
+        let layouts = parse(text).unwrap();
+        let layout = &layouts[0];
+        assert_eq!(layout.to_string(), text);
+        assert!(layout.validate().is_empty());
+
+        // The fields after an absent field move back over its words
+        let data = [0x00, 2, 7, 8];
+        let resolved = layout.resolve(&data).unwrap();
+        assert_eq!(resolved.field("ext").unwrap().decode(&data, 8), Ok(vec![]));
+        assert_eq!(
+            resolved.field("length").unwrap().bit_spec.to_string(),
+            "1[]"
+        );
+        let payload = resolved.field("payload").unwrap();
+        assert_eq!(payload.bit_spec.to_string(), "2[];(1[])<4");
+        assert_eq!(
+            payload.decode(&data, 8),
+            Ok(vec![Value::Unsigned(7), Value::Unsigned(8)])
+        );
+
+        let data = [0x80, 0x34, 0x12, 2, 7, 8];
+        let resolved = layout.resolve(&data).unwrap();
+        assert_eq!(resolved, *layout);
+        assert_eq!(
+            resolved.field("ext").unwrap().decode(&data, 8),
+            Ok(vec![Value::Unsigned(0x1234)])
+        );
+
+        let payload = ("payload", vec![Value::Unsigned(7), Value::Unsigned(8)]);
+        assert_eq!(
+            layout.encode(std::slice::from_ref(&payload)).unwrap(),
+            [0x00, 2, 7, 8]
+        );
+        let ext = ("ext", vec![Value::Unsigned(0x1234)]);
+        assert_eq!(
+            layout.encode(&[ext.clone(), payload]).unwrap(),
+            [0x80, 0x34, 0x12, 2, 7, 8]
+        );
+        assert_eq!(
+            layout.encode(&[ext, ("flags", vec![Value::Unsigned(0)])]),
+            Err(Error::ConditionMismatch)
+        );
+    }
+
+    #[test]
+    fn test_conditional_group() {
+        let layouts = parse(
+            "layout Packet {
+                flags: 0[]
+                ext: 1[]..2[];if 0[0]==0 {
+                    id: 0[]
+                    level: 1[]
+                }
+                crc: 3[]
+            }",
+        )
+        .unwrap();
+        let layout = &layouts[0];
+
+        let data = layout
+            .encode_with_groups(
+                &[("crc", vec![Value::Unsigned(0xFF)])],
+                &[("ext", Vec::new())],
+            )
+            .unwrap();
+        assert_eq!(data, [1, 0xFF]);
+
+        let element = vec![("level", vec![Value::Unsigned(5)])];
+        let data = layout
+            .encode_with_groups(&[], &[("ext", vec![element])])
+            .unwrap();
+        assert_eq!(data, [0, 0, 5, 0]);
+        let resolved = layout.resolve(&data).unwrap();
+        assert_eq!(
+            resolved
+                .group("ext")
+                .unwrap()
+                .decode(&data, 8)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_validate_group() {
+        let layouts = parse(
+            "layout Log {
+                records: 1[]..2[];4 {
+                    id: 0[]
+                    value: 1[]..2[]
+                    id: 1[0]
+                }
+                records: 5[]:bool {
+                }
+                flags: 6[]..7[] {
+                    ext: 1[];if 0[0]==1
+                }
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(
+            layouts[0].validate(),
+            vec![
+                ("records.id".to_string(), Error::DuplicateField),
+                ("records.value".to_string(), Error::OutsideGroup),
+                ("records".to_string(), Error::DuplicateField),
+                ("records".to_string(), Error::TypeMismatch),
+                ("flags.ext".to_string(), Error::Unsupported),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_match() {
+        let text = "layout Message {
+    kind: 0[]:{1=Ping,2=Data}
+    match kind {
+        1 => Ping {
+            id: 1[]
+        }
+        2 => Data {
+            length: 1[]
+            payload: 2[];(1[])<4
+        }
+        _ => Other {
+            code: 1[]..2[]
+        }
+    }
+}";
+        let layouts = parse(text).unwrap();
+        let layout = &layouts[0];
+        assert_eq!(layout.to_string(), text);
+        assert!(layout.validate().is_empty());
+        assert_eq!(layout.max_size(), 5);
+        assert!(parse("layout A { a: 0[]\n match a { 0x1 => B { } }\n match a { } }").is_err());
+
+        let variants = layout.variants.as_ref().unwrap();
+        assert_eq!(variants.select(2).unwrap().name, "Data");
+        assert_eq!(variants.select(7).unwrap().name, "Other");
+        assert_eq!(layout.matched(&[1, 5]).unwrap().unwrap().name, "Ping");
+
+        // The fields of the variant that matches are added
+        let data = [2, 2, 7, 8];
+        let selected = layout.select(&data).unwrap();
+        assert_eq!(selected.variants, None);
+        assert!(selected.field("id").is_none());
+        assert_eq!(
+            selected.field("payload").unwrap().decode(&data, 8),
+            Ok(vec![Value::Unsigned(7), Value::Unsigned(8)])
+        );
+        assert_eq!(
+            layout
+                .select(&[9, 0x34, 0x12])
+                .unwrap()
+                .field("code")
+                .unwrap()
+                .decode(&[9, 0x34, 0x12], 8),
+            Ok(vec![Value::Unsigned(0x1234)])
+        );
+        assert_eq!(layout.with_variant("Pong"), Err(Error::UnknownVariant));
+
+        let payload = ("payload", vec![Value::Unsigned(7), Value::Unsigned(8)]);
+        assert_eq!(
+            layout.encode_variant("Data", &[payload], &[]).unwrap(),
+            [2, 2, 7, 8]
+        );
+        // The variant of any other value keeps the field matched on as given
+        let code = ("code", vec![Value::Unsigned(0x1234)]);
+        assert_eq!(
+            layout
+                .encode_variant("Other", std::slice::from_ref(&code), &[])
+                .unwrap(),
+            [0, 0x34, 0x12]
+        );
+        let kind = ("kind", vec![Value::Enum("Ping".to_string())]);
+        assert_eq!(
+            layout.encode_variant("Other", &[code, kind], &[]),
+            Err(Error::ConditionMismatch)
+        );
+    }
+
+    #[test]
+    fn test_validate_match() {
+        let layouts = parse(
+            "layout Message {
+                kind: 0[];2
+                level: 2[] scale=0.5
+                match kind {
+                    1 => Ping {
+                        level: 1[]
+                    }
+                    1 => Pong {
+                    }
+                    0x100 => Data {
+                        length: 1[]
+                        blocks: 2[];(1[])<4 {
+                            id: 1[]
+                        }
+                    }
+                }
+            }
+            layout Reading {
+                value: 0[]..1[]:f16
+                match level {
+                    _ => Any {
+                    }
+                }
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(
+            layouts[0].validate(),
+            vec![
+                ("kind".to_string(), Error::TypeMismatch),
+                ("Ping.level".to_string(), Error::DuplicateField),
+                ("Pong".to_string(), Error::DuplicateVariant),
+                ("Data".to_string(), Error::ValueOutOfRange),
+                ("Data.blocks.id".to_string(), Error::OutsideGroup),
+            ]
+        );
+        assert_eq!(
+            layouts[1].validate(),
+            vec![("level".to_string(), Error::UnknownField)]
+        );
+    }
+
+    #[test]
+    fn test_scaled_decode() {
+        let layouts =
+            parse("layout Sensor { temperature: 0[]..1[]:q8.8 scale=0.5 offset=-40 }").unwrap();
+        let field = layouts[0].field("temperature").unwrap();
+
+        assert_eq!(
+            field.decode(&[0x80, 0x01], 8).unwrap(),
+            vec![Value::Float(-39.25)]
+        );
+    }
+
+    #[test]
+    fn test_scaled_encode() {
+        let layouts = parse("layout Sensor { level: 1[] scale=0.5 offset=-5 unit=\"V\" }").unwrap();
+        let field = layouts[0].field("level").unwrap();
+
+        let mut data = [0u8; 2];
+        field.encode(&mut data, 8, &[Value::Float(7.04)]).unwrap();
+        assert_eq!(data, [0, 24]);
+        assert_eq!(field.decode(&data, 8).unwrap(), vec![Value::Float(7.0)]);
+
+        assert_eq!(
+            field.encode(&mut data, 8, &[Value::Float(200.0)]),
+            Err(Error::ValueOutOfRange)
+        );
+        assert_eq!(
+            field.encode(&mut data, 8, &[Value::Float(-6.0)]),
+            Err(Error::ValueOutOfRange)
+        );
+    }
+}
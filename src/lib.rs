@@ -127,6 +127,14 @@
 //! 3[];(2[])<=48
 //!```
 //!
+//! ### Conditional Words
+//!
+//! Bits that are only present when a flag is set, such as an extended header, are given by a condition on the bits of another word:
+//!```text
+//! 5[];if 0[7]==1
+//!```
+//! This is word 5 if bit 7 of word 0 is 1, and nothing otherwise. The value can also be given in hexadecimal or binary, e.g. `5[];if 0[4..7]==0xA`.
+//!
 //! ### Literals
 //! The actual state of the bits can be set using a literal. This can be shown with the following examples:
 //! - Using hexadecimal to set word 0
@@ -138,19 +146,69 @@
 //! 5[0b1101_0001]
 //! ```
 //!
+//! ### Field Types
+//! By default the bits are an unsigned integer. A type can be given after a colon:
+//! - A single bit as a boolean
+//! ```text
+//! 3[7]:bool
+//! ```
+//! - An IEEE 754 float over 16 or 32 bits
+//! ```text
+//! 2[]..5[]:f32
+//! ```
+//! - A signed fixed point number with 8 integer bits (including the sign) and 8 fractional bits
+//! ```text
+//! 1[]..2[]:q8.8
+//! ```
+//! - An enumeration of named values
+//! ```text
+//! 4[0..1]:{0=Off,1=On,2=Auto}
+//! ```
+//!
+//! ## Indirect Words
+//! The position of some data is only known when decoding, such as a payload whose word index is held in a header. The index can be read from another word by giving that word in brackets before the bits:
+//! ```text
+//! @(2[])[0..7]
+//! ```
+//! This refers to bits 0 to 7 of the word whose index is the value of word 2. The `@` is optional, so `(2[])[0..7]` means the same. An end word is relative to the word pointed to, so `@(2[])[]..1[]` is the word pointed to and the one after it. The words pointed to have to come after the pointer word, so that they cannot overlap the header, and be inside the buffer.
+//!
+//! # Layout Files
+//! A set of bit specifications can be given names in a layout file. A field can also
+//! have a linear scaling and unit so that the decoded value is `raw * scale + offset`:
+//! ```text
+//! layout Sensor {
+//!     sync: [0xA5]
+//!     temperature: 1[]..2[]:q8.8 scale=0.5 offset=-40 unit="degC"
+//! }
+//! ```
+//! See the [`layout`] module for details.
+//!
 //! # Example Code
 //! ```
 //! use bit_lang::parser::{BitRange, BitSpec, Condition, Repeat, Word};
 //!
-//! fn main() {
-//!     let data = "5[3..7]";
-//!     let bit_spec = bit_lang::parse(data).unwrap();
-//!   
-//!     assert_eq!( bit_spec.start.index, 5);
-//!     assert_eq!( bit_spec.start.bit_range, BitRange::Range(3,7));
+//! let data = "5[3..7]";
+//! let bit_spec = bit_lang::parse(data).unwrap();
+//!
+//! assert_eq!( bit_spec.start.index, 5);
+//! assert_eq!( bit_spec.start.bit_range, BitRange::Range(3,7));
+//!
+//! // Bits are numbered from the least significant bit and words are 8 bits wide
+//! let bit_spec = bit_lang::parse("1[]..2[]:q8.8").unwrap();
+//! // Decoding all the repetitions needs the alloc feature
+//! #[cfg(feature = "alloc")]
+//! {
+//!     let values = bit_spec.decode(&[0x00, 0x80, 0x01], 8).unwrap();
+//!     assert_eq!(values, vec![bit_lang::Value::Float(1.5)]);
 //! }
+//!
+//! // For repeated access compile the bit specification for the word size
+//! let compiled = bit_spec.compile(8).unwrap();
+//! assert_eq!(compiled.extract(&[0x00, 0x80, 0x01], 0).unwrap(), 0x0180);
 //! ```
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
 // TODO:
 // - Provide a function that can be directly used with #[serde(deserialize_with = "??")]
 // - The user needs to calculate the position of a variable word which can be complicated, espacially
@@ -164,19 +222,132 @@
 //   use abolution positions at all?).
 //   If using something like ddgen then the symbolic names do not need to be specified, but
 //   are the same as the field name..#[allow(dead_code)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+// The tests use the standard library even when the crate does not
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+pub mod access;
+#[cfg(feature = "alloc")]
+pub mod bitset;
+pub mod borrowed;
+#[cfg(feature = "std")]
+pub mod codegen;
+pub mod compiled;
+#[cfg(feature = "std")]
+pub mod diagram;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod hexdump;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod layout;
+pub mod normalize;
 pub mod parser;
-use std::fmt::Display;
+#[cfg(feature = "std")]
+pub mod svd;
+pub mod value;
+use core::fmt::Display;
+
+// Allocation for the code generated by the macros, which works without `std`
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::{string::String, vec};
+}
 
-pub use parser::{BitRange, BitSpec, Condition, Repeat, Word};
+pub use access::BitOrder;
+#[cfg(feature = "alloc")]
+pub use bitset::BitSet;
+pub use borrowed::BitSpecRef;
+pub use compiled::CompiledSpec;
+#[cfg(feature = "std")]
+pub use layout::{Field, Group, Layout, Match, Scaling, Variant};
+pub use parser::{BitRange, BitSpec, Condition, FieldType, Repeat, Word};
+pub use value::Value;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Error {
     ParseError,
+    // The bits are outside of the buffer
+    OutOfBounds,
+    // More bits than can be held in a u64
+    TooWide,
+    ValueOutOfRange,
+    // The number of repeats is not allowed by the repeat specification
+    RepeatOutOfRange,
+    // The field type does not fit the bits or the value
+    TypeMismatch,
+    InvalidEnumValue,
+    // Layouts take words of 1 to 64 bits. Compiled specifications and the
+    // exports that work in bytes also need a multiple of 8 bits
+    InvalidWordSize,
+    // The bit specification does not refer to any bits
+    InvalidBitSpec,
+    LayoutParseError { line: usize },
+    // More than one field in a layout has the same name
+    DuplicateField,
+    UnknownField,
+    // Bits that are used by more than one field
+    OverlappingBits,
+    SvdParseError,
+    // The bits cannot be given as an offset and width in the bit order
+    NotConsecutive,
+    // A pointer to bits at or before the word holding the pointer
+    InvalidPointer,
+    // The position of the bits is read from a pointer, so is only known when decoding
+    IndirectWord,
+    // A field of a group uses words after the end of the group's element
+    OutsideGroup,
+    // The layout has groups, conditions or variants, which the output cannot represent
+    Unsupported,
+    // A value given sets the word of a condition against the fields given
+    ConditionMismatch,
+    // No variant of a match has the value, or the name
+    UnknownVariant,
+    // More than one variant of a match has the same name or value
+    DuplicateVariant,
+    // A word index over 255, which a bit specification cannot be written with
+    IndexOutOfRange,
 }
 
 impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error in bit specification")
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::ParseError => write!(f, "Error in bit specification"),
+            Error::OutOfBounds => write!(f, "Bits are outside of the buffer"),
+            Error::TooWide => write!(f, "Bits do not fit into 64 bits"),
+            Error::ValueOutOfRange => write!(f, "Value cannot be represented by the bits"),
+            Error::RepeatOutOfRange => write!(f, "Number of repeats is outside of the limit"),
+            Error::TypeMismatch => write!(f, "Field type does not match the bits or value"),
+            Error::InvalidEnumValue => write!(f, "Value is not in the enumeration"),
+            Error::InvalidWordSize => write!(f, "Word size is not supported"),
+            Error::InvalidBitSpec => write!(f, "Bit specification does not refer to any bits"),
+            Error::LayoutParseError { line } => write!(f, "Error in layout at line {line}"),
+            Error::DuplicateField => write!(f, "Field name is used more than once"),
+            Error::UnknownField => write!(f, "Field is not in the layout"),
+            Error::OverlappingBits => write!(f, "Bits are used by more than one field"),
+            Error::SvdParseError => write!(f, "Error in SVD file"),
+            Error::NotConsecutive => write!(f, "Bits are not consecutive in the bit order"),
+            Error::InvalidPointer => write!(f, "Pointer refers to bits before the data"),
+            Error::IndirectWord => write!(f, "Position of the bits is only known when decoding"),
+            Error::OutsideGroup => write!(f, "Field is outside the words of its group"),
+            Error::Unsupported => {
+                write!(f, "Groups, conditions and variants are not supported here")
+            }
+            Error::ConditionMismatch => {
+                write!(f, "Values do not match the conditions of the fields given")
+            }
+            Error::UnknownVariant => write!(f, "Variant is not in the match"),
+            Error::DuplicateVariant => write!(f, "Variant name or value is used more than once"),
+            Error::IndexOutOfRange => write!(f, "Word index is over 255"),
+        }
     }
 }
 
@@ -187,6 +358,13 @@ pub fn parse(bit_spec_string: &str) -> Result<BitSpec, Error> {
     Ok(bit_spec)
 }
 
+/// Parse the bit-lang specification and return a BitSpecRef that borrows from it.
+pub fn parse_ref(bit_spec_string: &str) -> Result<BitSpecRef<'_>, Error> {
+    let (_, bit_spec) = borrowed::bit_spec_ref(bit_spec_string).map_err(|_| Error::ParseError)?;
+
+    Ok(bit_spec)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -196,60 +374,70 @@ mod tests {
         let data = "4";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::Single(4),
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
         let data = "4..6";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::Range(4, 6),
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
         let data = "[4..6]";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::Range(4, 6),
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
         let data = "5[3..7]";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 5,
                 bit_range: BitRange::Range(3, 7),
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
         let data = "5[]";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 5,
                 bit_range: BitRange::WholeWord,
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
     }
@@ -261,6 +449,7 @@ mod tests {
         let bit_spec = parse(data).unwrap();
 
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 3,
                 bit_range: BitRange::Range(4, 7),
@@ -270,6 +459,7 @@ mod tests {
                 bit_range: BitRange::Range(0, 5),
             }),
             repeat: Repeat::Fixed(48),
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
@@ -284,6 +474,7 @@ mod tests {
             limit: 49,
         };
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 4,
                 bit_range: BitRange::WholeWord,
@@ -292,7 +483,8 @@ mod tests {
                 index: 7,
                 bit_range: BitRange::WholeWord,
             }),
-            repeat: repeat,
+            repeat,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
     }
@@ -303,6 +495,7 @@ mod tests {
         let bit_spec = parse(data).unwrap();
 
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 3,
                 bit_range: BitRange::Range(4, 7),
@@ -312,12 +505,14 @@ mod tests {
                 bit_range: BitRange::Range(0, 5),
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
         let data = "4[]..7[]";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 4,
                 bit_range: BitRange::WholeWord,
@@ -327,12 +522,14 @@ mod tests {
                 bit_range: BitRange::WholeWord,
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
         let data = "[]..5[]";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::WholeWord,
@@ -342,24 +539,28 @@ mod tests {
                 bit_range: BitRange::WholeWord,
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
         let data = "[]";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::WholeWord,
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
 
         let data = "[]..6[0..5]";
         let bit_spec = parse(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::WholeWord,
@@ -369,6 +570,7 @@ mod tests {
                 bit_range: BitRange::Range(0, 5),
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(bit_spec, expected);
     }
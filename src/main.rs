@@ -0,0 +1,731 @@
+//! Command-line tool for working with bit specifications and layout files.
+
+use std::fs;
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+use bit_lang::codegen;
+use bit_lang::diagram::Style;
+use bit_lang::diff::{self, LayoutDiff};
+use bit_lang::export;
+use bit_lang::layout::{self, Field, Layout};
+use bit_lang::parser;
+use bit_lang::svd;
+use bit_lang::{BitSpec, Repeat, Value};
+
+const USAGE: &str = "Usage:
+    bit-lang parse <bit-spec> [--json]
+    bit-lang check <layout-file>
+    bit-lang decode <layout-file> [--layout <name>] [--input <file>] [--binary] [--json]
+    bit-lang hexdump <layout-file> [--layout <name>] [--input <file>] [--binary]
+    bit-lang encode <layout-file> [--layout <name>] [--variant <name>] <field>=<value>[,<value>...]...
+    bit-lang diagram <layout-file> [--layout <name>] [--unicode]
+    bit-lang diagram --spec <bit-spec> [--word-bits <n>] [--unicode]
+    bit-lang export <layout-file> --format <format> [--layout <name>]
+    bit-lang import <svd-file>
+    bit-lang diff <old-layout-file> <new-layout-file> [--layout <name>] [--json]
+
+decode and hexdump read hex from the input file or stdin, ignoring whitespace, commas
+and colons. With --binary the input is read as raw bytes. hexdump shows the fields
+covering each word and marks words with problems with `!`.
+
+encode needs --variant to give the fields of a variant of a layout with a match.
+
+--json prints JSON instead, which needs bit-lang to be built with the json feature.
+
+The export formats are svg, wavedrom, kaitai (a .ksy definition), wireshark (a Lua dissector),
+svd (a CMSIS-SVD device), json, c (a header with accessors) and rust (a module with accessors).
+
+import prints a layout file with a layout for each peripheral of a CMSIS-SVD file.
+
+diff compares the layouts with the same name in two files, or the only layout in each, and
+marks the changes that stop older decoders reading new frames with `!`.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<ExitCode, String> {
+    let Some((command, args)) = args.split_first() else {
+        println!("{USAGE}");
+        return Ok(ExitCode::from(2));
+    };
+
+    match command.as_str() {
+        "parse" => parse(args),
+        "check" => check(args),
+        "decode" => decode(args),
+        "encode" => encode(args),
+        "hexdump" => hexdump(args),
+        "diagram" => diagram(args),
+        "export" => export(args),
+        "import" => import(args),
+        "diff" => diff(args),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => Err(format!("unknown command `{command}`\n\n{USAGE}")),
+    }
+}
+
+// Print the canonical form and the syntax tree of a bit specification
+fn parse(args: &[String]) -> Result<ExitCode, String> {
+    let (spec, json) = match args {
+        [spec] => (spec, false),
+        [spec, option] | [option, spec] if option == "--json" => (spec, true),
+        _ => return Err(format!("parse needs a bit specification\n\n{USAGE}")),
+    };
+
+    let bit_spec = parse_spec(spec)?;
+
+    if json {
+        println!("{}", to_json(Json::BitSpec(&bit_spec))?);
+    } else {
+        println!("{bit_spec}");
+        println!("{bit_spec:#?}");
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Report any problems with the layouts in a file
+fn check(args: &[String]) -> Result<ExitCode, String> {
+    let [path] = args else {
+        return Err(format!("check needs a layout file\n\n{USAGE}"));
+    };
+    let layouts = read_layouts(path)?;
+
+    let mut valid = true;
+    for layout in &layouts {
+        if report_problems(layout) {
+            let groups = match layout.groups.len() {
+                0 => String::new(),
+                1 => " and 1 group".to_string(),
+                n => format!(" and {n} groups"),
+            };
+            let variants = match layout.variants.as_ref().map_or(0, |m| m.variants.len()) {
+                0 => String::new(),
+                1 => ", 1 variant".to_string(),
+                n => format!(", {n} variants"),
+            };
+            println!(
+                "{}: {} fields{groups}{variants} ok",
+                layout.name,
+                layout.fields.len()
+            );
+        } else {
+            valid = false;
+        }
+    }
+
+    Ok(if valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+// Print the problems with a layout, returning whether there are none
+fn report_problems(layout: &Layout) -> bool {
+    let problems = layout.validate();
+    for (name, error) in &problems {
+        println!("{}.{name}: {error}", layout.name);
+    }
+    problems.is_empty()
+}
+
+// Print the values of all the fields in a frame
+fn decode(args: &[String]) -> Result<ExitCode, String> {
+    let options = Options::parse(args)?;
+    let layouts = read_layouts(options.path()?)?;
+    let layout = select_layout(&layouts, options.layout.as_deref())?;
+
+    let data = read_frame(&options)?;
+
+    if options.json {
+        println!("{}", to_json(Json::Frame(layout, &data))?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(variants) = &layout.variants {
+        match layout.matched(&data) {
+            Ok(Some(variant)) => println!("{} => {}", variants.field, variant.name),
+            Ok(None) => {}
+            Err(error) => return Err(format!("{}.{}: {error}", layout.name, variants.field)),
+        }
+    }
+
+    // Fields after absent conditional ones are moved back, and the fields of
+    // the variant that matches are added
+    let layout = layout
+        .select(&data)
+        .map_err(|e| format!("{}: {e}", layout.name))?;
+
+    let mut valid = true;
+    for field in &layout.fields {
+        valid &= print_field(field, &data, layout.word_bits);
+    }
+    for group in &layout.groups {
+        let repeats = match group.bit_spec.repeats(&data, layout.word_bits) {
+            Ok(repeats) => repeats,
+            Err(error) => {
+                println!("{}: {error}", group.name);
+                valid = false;
+                continue;
+            }
+        };
+        for element in 0..repeats {
+            let fields = match group.element_fields(&data, layout.word_bits, element) {
+                Ok(fields) => fields,
+                Err(error) => {
+                    println!("{}[{element}]: {error}", group.name);
+                    valid = false;
+                    continue;
+                }
+            };
+            for field in fields {
+                let name = match group.bit_spec.repeat {
+                    Repeat::None | Repeat::Conditional { .. } => {
+                        format!("{}.{}", group.name, field.name)
+                    }
+                    _ => format!("{}[{element}].{}", group.name, field.name),
+                };
+                let field = Field { name, ..field };
+                valid &= print_field(&field, &data, layout.word_bits);
+            }
+        }
+    }
+
+    Ok(if valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+// Print the values of a field, returning whether they are valid
+fn print_field(field: &Field, data: &[u8], word_bits: usize) -> bool {
+    match field.decode(data, word_bits) {
+        Ok(values) => {
+            let mismatch = literal_mismatch(field, data, word_bits);
+            println!(
+                "{} = {}{}",
+                field.name,
+                format_values(field, &values),
+                mismatch.as_deref().unwrap_or_default()
+            );
+            mismatch.is_none()
+        }
+        Err(error) => {
+            println!("{}: {error}", field.name);
+            false
+        }
+    }
+}
+
+// Print the words of a frame with the fields that cover them
+fn hexdump(args: &[String]) -> Result<ExitCode, String> {
+    let options = Options::parse(args)?;
+    let layouts = read_layouts(options.path()?)?;
+    let layout = select_layout(&layouts, options.layout.as_deref())?;
+    let data = read_frame(&options)?;
+
+    let dump = layout
+        .hexdump(&data)
+        .map_err(|e| format!("{}: {e}", layout.name))?;
+    print!("{dump}");
+
+    Ok(if dump.lines().any(|line| line.starts_with('!')) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+// Print a frame built from the values of the fields
+fn encode(args: &[String]) -> Result<ExitCode, String> {
+    let options = Options::parse(args)?;
+    let layouts = read_layouts(options.path()?)?;
+    let layout = select_layout(&layouts, options.layout.as_deref())?;
+    if !report_problems(layout) {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    // The fields of the variant can be given as well
+    let fields = match &options.variant {
+        Some(variant) => layout
+            .with_variant(variant)
+            .map_err(|e| format!("{}.{variant}: {e}", layout.name))?,
+        None => layout.clone(),
+    };
+
+    let mut values = Vec::new();
+    for assignment in &options.values {
+        values.push(parse_assignment(&fields, assignment)?);
+    }
+    let values: Vec<(&str, Vec<Value>)> = values
+        .into_iter()
+        .map(|(field, values)| (field.name.as_str(), values))
+        .collect();
+
+    let data = match &options.variant {
+        Some(variant) => layout.encode_variant(variant, &values, &[]),
+        None => layout.encode(&values),
+    };
+    let data = data.map_err(|e| e.to_string())?;
+    println!("{}", format_hex(&data));
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Draw the layouts in a file, or a single bit specification
+fn diagram(args: &[String]) -> Result<ExitCode, String> {
+    let options = Options::parse(args)?;
+    let style = if options.unicode {
+        Style::Unicode
+    } else {
+        Style::Ascii
+    };
+
+    if let Some(spec) = &options.spec {
+        let bit_spec = parse_spec(spec)?;
+        let word_bits = options.word_bits.unwrap_or(8);
+        print!(
+            "{}",
+            bit_spec
+                .diagram(word_bits, style)
+                .map_err(|e| e.to_string())?
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let layouts = read_layouts(options.path()?)?;
+    let selected: Vec<&Layout> = match &options.layout {
+        Some(name) => vec![select_layout(&layouts, Some(name))?],
+        None => layouts.iter().collect(),
+    };
+    let mut valid = true;
+    for layout in &selected {
+        valid &= report_problems(layout);
+    }
+    if !valid {
+        return Ok(ExitCode::FAILURE);
+    }
+    for (i, layout) in selected.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", layout.name);
+        print!(
+            "{}",
+            layout
+                .diagram(style)
+                .map_err(|e| format!("{}: {e}", layout.name))?
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Print a layout in the format of another tool
+fn export(args: &[String]) -> Result<ExitCode, String> {
+    let options = Options::parse(args)?;
+    let layouts = read_layouts(options.path()?)?;
+    let layout = select_layout(&layouts, options.layout.as_deref())?;
+    if !report_problems(layout) {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let output = match options.format.as_deref() {
+        Some("svg") => export::svg::render(layout),
+        Some("wavedrom") => export::wavedrom::render(layout),
+        Some("kaitai") => export::kaitai::render(layout),
+        Some("wireshark") => export::wireshark::render(layout),
+        Some("json") => {
+            println!("{}", to_json(Json::Layout(layout))?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some("svd") => svd::render(&layout.name, std::slice::from_ref(layout)),
+        Some("c") => codegen::c::header(layout),
+        Some("rust") => codegen::rust::module(std::slice::from_ref(layout)),
+        Some(format) => return Err(format!("unknown format `{format}`\n\n{USAGE}")),
+        None => return Err(format!("export needs --format\n\n{USAGE}")),
+    };
+    print!("{}", output.map_err(|e| format!("{}: {e}", layout.name))?);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Print the peripherals of an SVD file as layouts
+fn import(args: &[String]) -> Result<ExitCode, String> {
+    let [path] = args else {
+        return Err(format!("import needs an SVD file\n\n{USAGE}"));
+    };
+    let text = fs::read_to_string(path).map_err(|e| format!("cannot read `{path}`: {e}"))?;
+    let layouts = svd::parse(&text).map_err(|error| format!("{path}: {error}"))?;
+
+    let layouts: Vec<String> = layouts.iter().map(|layout| layout.to_string()).collect();
+    println!("{}", layouts.join("\n\n"));
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// The arguments of the commands that read a layout file
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    path: Option<String>,
+    layout: Option<String>,
+    input: Option<String>,
+    binary: bool,
+    unicode: bool,
+    json: bool,
+    spec: Option<String>,
+    word_bits: Option<usize>,
+    format: Option<String>,
+    variant: Option<String>,
+    values: Vec<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--layout" => options.layout = Some(option_value(arg, args.next())?),
+                "--input" => options.input = Some(option_value(arg, args.next())?),
+                "--binary" => options.binary = true,
+                "--unicode" => options.unicode = true,
+                "--json" => options.json = true,
+                "--spec" => options.spec = Some(option_value(arg, args.next())?),
+                "--format" => options.format = Some(option_value(arg, args.next())?),
+                "--variant" => options.variant = Some(option_value(arg, args.next())?),
+                "--word-bits" => {
+                    let value = option_value(arg, args.next())?;
+                    let word_bits = value
+                        .parse()
+                        .map_err(|_| format!("invalid word size `{value}`"))?;
+                    options.word_bits = Some(word_bits);
+                }
+                _ if arg.starts_with("--") => return Err(format!("unknown option `{arg}`")),
+                _ if options.path.is_none() => options.path = Some(arg.clone()),
+                _ => options.values.push(arg.clone()),
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn path(&self) -> Result<&str, String> {
+        self.path
+            .as_deref()
+            .ok_or_else(|| format!("missing layout file\n\n{USAGE}"))
+    }
+}
+
+// Compare the layouts in two versions of a layout file
+fn diff(args: &[String]) -> Result<ExitCode, String> {
+    let options = Options::parse(args)?;
+    let [new_path] = options.values.as_slice() else {
+        return Err(format!(
+            "diff needs an old and a new layout file\n\n{USAGE}"
+        ));
+    };
+    let old_layouts = read_layouts(options.path()?)?;
+    let new_layouts = read_layouts(new_path)?;
+
+    let diffs = match (
+        options.layout.as_deref(),
+        &old_layouts[..],
+        &new_layouts[..],
+    ) {
+        (Some(name), _, _) => vec![diff::diff(
+            select_layout(&old_layouts, Some(name))?,
+            select_layout(&new_layouts, Some(name))?,
+        )],
+        (None, [old], [new]) => vec![diff::diff(old, new)],
+        (None, _, _) => diff_by_name(&old_layouts, &new_layouts),
+    };
+
+    let mut compatible = true;
+    for diff in &diffs {
+        compatible &= diff.is_compatible();
+        if options.json {
+            println!("{}", to_json(Json::Diff(diff))?);
+        } else {
+            print!("{diff}");
+        }
+    }
+
+    Ok(if compatible {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+// Compare the layouts with the same name. A layout that is only in one file is
+// compared with an empty layout, so that all its fields are removed or added.
+fn diff_by_name(old_layouts: &[Layout], new_layouts: &[Layout]) -> Vec<LayoutDiff> {
+    let empty = |layout: &Layout| Layout {
+        name: layout.name.clone(),
+        word_bits: layout.word_bits,
+        fields: Vec::new(),
+        groups: Vec::new(),
+        variants: None,
+    };
+
+    let mut diffs = Vec::new();
+    for old in old_layouts {
+        match new_layouts.iter().find(|new| new.name == old.name) {
+            Some(new) => diffs.push(diff::diff(old, new)),
+            None => diffs.push(diff::diff(old, &empty(old))),
+        }
+    }
+    for new in new_layouts {
+        if !old_layouts.iter().any(|old| old.name == new.name) {
+            diffs.push(diff::diff(&empty(new), new));
+        }
+    }
+    diffs
+}
+
+// A document to print as JSON
+#[cfg_attr(not(feature = "json"), allow(dead_code))]
+enum Json<'a> {
+    BitSpec(&'a BitSpec),
+    Layout(&'a Layout),
+    Frame(&'a Layout, &'a [u8]),
+    Diff(&'a LayoutDiff),
+}
+
+#[cfg(feature = "json")]
+fn to_json(document: Json) -> Result<String, String> {
+    match document {
+        Json::BitSpec(bit_spec) => Ok(bit_spec.to_json()),
+        Json::Layout(layout) => Ok(layout.to_json()),
+        Json::Frame(layout, data) => layout.decode_json(data).map_err(|e| e.to_string()),
+        Json::Diff(diff) => Ok(diff.to_json()),
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn to_json(_document: Json) -> Result<String, String> {
+    Err("JSON needs bit-lang to be built with the json feature".to_string())
+}
+
+fn option_value(option: &str, value: Option<&String>) -> Result<String, String> {
+    value
+        .cloned()
+        .ok_or_else(|| format!("`{option}` needs a value"))
+}
+
+fn parse_spec(spec: &str) -> Result<BitSpec, String> {
+    match parser::bit_spec(spec) {
+        Ok(("", bit_spec)) => Ok(bit_spec),
+        Ok((remaining, _)) => Err(format!("unexpected `{remaining}` in `{spec}`")),
+        Err(_) => Err(format!("invalid bit specification `{spec}`")),
+    }
+}
+
+// Read the frame from the input file or stdin, as hex unless --binary is given
+fn read_frame(options: &Options) -> Result<Vec<u8>, String> {
+    let input = match &options.input {
+        Some(path) => fs::read(path).map_err(|e| format!("cannot read `{path}`: {e}"))?,
+        None => {
+            let mut input = Vec::new();
+            io::stdin()
+                .read_to_end(&mut input)
+                .map_err(|e| format!("cannot read stdin: {e}"))?;
+            input
+        }
+    };
+
+    if options.binary {
+        Ok(input)
+    } else {
+        parse_hex(&String::from_utf8_lossy(&input))
+    }
+}
+
+fn read_layouts(path: &str) -> Result<Vec<Layout>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("cannot read `{path}`: {e}"))?;
+
+    layout::parse(&text).map_err(|error| format!("{path}: {error}"))
+}
+
+// The named layout, or the only layout in the file
+fn select_layout<'a>(layouts: &'a [Layout], name: Option<&str>) -> Result<&'a Layout, String> {
+    match (name, layouts) {
+        (Some(name), _) => layouts
+            .iter()
+            .find(|layout| layout.name == name)
+            .ok_or_else(|| format!("no layout named `{name}`")),
+        (None, [layout]) => Ok(layout),
+        (None, []) => Err("the file has no layouts".to_string()),
+        (None, _) => Err("the file has more than one layout, choose one with --layout".to_string()),
+    }
+}
+
+// Parse hex digits, ignoring whitespace, commas, colons and any 0x prefixes
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ':')
+        .map(|chunk| chunk.strip_prefix("0x").unwrap_or(chunk))
+        .collect();
+    if !digits.is_ascii() {
+        return Err("hex input has characters that are not hex digits".to_string());
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err("hex input has an odd number of digits".to_string());
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("invalid hex `{}`", &digits[i..i + 2]))
+        })
+        .collect()
+}
+
+fn format_hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// A single value, or a list for repeated fields, followed by any unit
+fn format_values(field: &Field, values: &[Value]) -> String {
+    let text = match (field.bit_spec.repeat.max_repeats(), values) {
+        (1, [value]) => value.to_string(),
+        (1, []) => return "absent".to_string(),
+        _ => {
+            let values: Vec<String> = values.iter().map(Value::to_string).collect();
+            format!("[{}]", values.join(", "))
+        }
+    };
+
+    match field.scaling.as_ref().and_then(|s| s.unit.as_ref()) {
+        Some(unit) => format!("{text} {unit}"),
+        None => text,
+    }
+}
+
+// A note if the bits of a literal field are not the literal value
+fn literal_mismatch(field: &Field, data: &[u8], word_bits: usize) -> Option<String> {
+    let expected = field.bit_spec.literal()?.value();
+    let repeats = field.bit_spec.repeats(data, word_bits).ok()?;
+
+    (0..repeats)
+        .any(|element| field.bit_spec.extract(data, word_bits, element) != Ok(expected))
+        .then(|| format!(" (expected {expected:#x})"))
+}
+
+// Parse `name=value[,value...]` into the field and its values
+fn parse_assignment<'a>(layout: &'a Layout, text: &str) -> Result<(&'a Field, Vec<Value>), String> {
+    let (name, values) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<field>=<value>` but found `{text}`"))?;
+    let field = layout
+        .field(name)
+        .ok_or_else(|| format!("no field named `{name}` in {}", layout.name))?;
+
+    let values = values
+        .split(',')
+        .map(|value| {
+            field
+                .parse_value(value)
+                .map_err(|error| format!("{name}: `{value}`: {error}"))
+        })
+        .collect::<Result<Vec<Value>, String>>()?;
+
+    Ok((field, values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(
+            parse_hex("a5 01\n0x34,12:ff"),
+            Ok(vec![0xA5, 1, 0x34, 0x12, 0xFF])
+        );
+        assert!(parse_hex("a5 0").is_err());
+        assert!(parse_hex("zz").is_err());
+        assert!(parse_hex("é1").is_err());
+    }
+
+    #[test]
+    fn test_options() {
+        let options = Options::parse(&args(&[
+            "frame.layout",
+            "--layout",
+            "Frame",
+            "count=2",
+            "--binary",
+        ]))
+        .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                path: Some("frame.layout".to_string()),
+                layout: Some("Frame".to_string()),
+                binary: true,
+                values: args(&["count=2"]),
+                ..Options::default()
+            }
+        );
+
+        let options = Options::parse(&args(&["--spec", "1[]", "--word-bits", "16"])).unwrap();
+        assert_eq!(options.spec.as_deref(), Some("1[]"));
+        assert_eq!(options.word_bits, Some(16));
+        assert!(options.path().is_err());
+
+        assert!(Options::parse(&args(&["frame.layout", "--input"])).is_err());
+        assert!(Options::parse(&args(&["--word-bits", "x"])).is_err());
+    }
+
+    #[test]
+    fn test_values() {
+        let layouts = layout::parse(
+            "layout Frame {
+                sync: [0xA5]
+                samples: 1[];2
+                level: 3[] scale=0.5 unit=\"V\"
+            }",
+        )
+        .unwrap();
+        let layout = select_layout(&layouts, None).unwrap();
+
+        let (field, values) = parse_assignment(layout, "samples=1,0x02").unwrap();
+        assert_eq!(format_values(field, &values), "[1, 2]");
+        let (field, values) = parse_assignment(layout, "level=1.5").unwrap();
+        assert_eq!(format_values(field, &values), "1.5 V");
+        assert!(parse_assignment(layout, "missing=1").is_err());
+
+        let sync = layout.field("sync").unwrap();
+        assert_eq!(literal_mismatch(sync, &[0xA5], 8), None);
+        assert_eq!(
+            literal_mismatch(sync, &[0x5A], 8),
+            Some(" (expected 0xa5)".to_string())
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! A canonical form for bit specifications.
+//!
+//! The same bits can be written in several ways, such as `5`, `[5]` and `0[5]`,
+//! `3[]..3[]` and `3[]`, or `4[0..7]` and `4[]` with 8 bit words.
+//! [`BitSpec::normalize`] writes all of them in the same way, so specifications
+//! are equivalent when their normal forms are equal:
+//! - The bits are written in the most compact form, as by [`BitSpec::from_offset`].
+//! - Literals are written in hexadecimal.
+//! - A fixed repeat of one is no repeat, and a `<=` limit is the `<` limit
+//!   one larger.
+//! - The variants of an enumeration are ordered by value.
+//! - The pointer of an indirect specification is written like the other words.
+
+use crate::access::BitOrder;
+#[cfg(feature = "alloc")]
+use crate::parser::FieldType;
+use crate::parser::{BitRange, BitSpec, Condition, LiteralType, Repeat, Word};
+use crate::Error;
+
+impl BitSpec {
+    /// The canonical form of the bit specification with words of `word_bits` bits.
+    pub fn normalize(&self, word_bits: usize) -> Result<BitSpec, Error> {
+        // The bits of an indirect specification are normalized relative to
+        // the word pointed to
+        self.check_bits(word_bits)?;
+        let direct = BitSpec {
+            pointer: None,
+            ..self.clone()
+        };
+        let (offset, width) = direct.to_offset(word_bits, BitOrder::Lsb0)?;
+        let mut normal = BitSpec::from_offset(offset, width, word_bits, BitOrder::Lsb0)?;
+        normal.pointer = match &self.pointer {
+            Some(pointer) => Some(normalize_word(pointer, word_bits)?),
+            None => None,
+        };
+
+        // A literal is always a whole word, which is where the normal form starts
+        if let Some(literal) = self.literal() {
+            normal.start.bit_range = BitRange::Literal(LiteralType::Hex(literal.value()));
+        }
+
+        normal.repeat = match &self.repeat {
+            Repeat::None | Repeat::Fixed(1) => Repeat::None,
+            Repeat::Fixed(number) => Repeat::Fixed(*number),
+            Repeat::Variable {
+                word,
+                condition,
+                limit,
+            } => {
+                let (condition, limit) = match (condition, limit.checked_add(1)) {
+                    (Condition::Lte, Some(limit)) => (Condition::Lt, limit),
+                    (condition, _) => (*condition, *limit),
+                };
+                Repeat::Variable {
+                    word: normalize_word(word, word_bits)?,
+                    condition,
+                    limit,
+                }
+            }
+            Repeat::Conditional { word, value } => Repeat::Conditional {
+                word: normalize_word(word, word_bits)?,
+                value: *value,
+            },
+        };
+
+        normal.field_type = self.field_type.clone();
+        #[cfg(feature = "alloc")]
+        if let FieldType::Enum(variants) = &mut normal.field_type {
+            variants.sort_by_key(|(value, _)| *value);
+        }
+
+        Ok(normal)
+    }
+
+    /// Whether the bit specifications refer to the same bits in the same way
+    /// with words of `word_bits` bits.
+    ///
+    /// Specifications that are not valid for the word size are only
+    /// equivalent if they are written the same.
+    pub fn is_equivalent(&self, other: &BitSpec, word_bits: usize) -> bool {
+        match (self.normalize(word_bits), other.normalize(word_bits)) {
+            (Ok(normal), Ok(other_normal)) => normal == other_normal,
+            _ => self == other,
+        }
+    }
+}
+
+// The canonical form of a word on its own, such as the count of a repeat
+fn normalize_word(word: &Word, word_bits: usize) -> Result<Word, Error> {
+    word.check_bits(word_bits)?;
+    let normal = BitSpec::from_offset(
+        word.bit_offset(word_bits),
+        word.bit_width(word_bits),
+        word_bits,
+        BitOrder::Lsb0,
+    )?;
+
+    Ok(normal.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    #[cfg(not(feature = "std"))]
+    use std::prelude::rust_2021::*;
+
+    fn normal(text: &str, word_bits: usize) -> String {
+        parse(text)
+            .unwrap()
+            .normalize(word_bits)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_normalize() {
+        for text in ["5", "[5]", "0[5]", "[5..5]"] {
+            assert_eq!(normal(text, 8), "0[5]");
+        }
+        assert_eq!(normal("3[]..3[]", 8), "3[]");
+        assert_eq!(normal("4[0..7]", 8), "4[]");
+        assert_eq!(normal("4[0..7]", 16), "4[0..7]");
+        assert_eq!(normal("1[4..7]..2[0..3]", 8), "1[4]..2[3]");
+        assert_eq!(normal("1[0]..2[0..7]", 8), "1[]..2[]");
+        assert_eq!(normal("[0b1010_0101]", 8), "0[0xA5]");
+        assert_eq!(normal("2[];1", 8), "2[]");
+        assert_eq!(normal("2[];(0[0..7])<=3:q4.4", 8), "2[];(0[])<4:q4.4");
+        assert_eq!(normal("(1[0..7])[0..3]..1[0..7]", 8), "@(1[])[]..1[]");
+
+        assert_eq!(
+            parse("[9]").unwrap().normalize(8),
+            Err(Error::InvalidBitSpec)
+        );
+        assert_eq!(
+            parse("1[];(0[9])<3").unwrap().normalize(8),
+            Err(Error::InvalidBitSpec)
+        );
+        assert_eq!(
+            parse("[]").unwrap().normalize(0),
+            Err(Error::InvalidWordSize)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_normalize_enum() {
+        assert_eq!(normal("[0..1]:{1=On, 0=Off}", 8), "0[0..1]:{0=Off,1=On}");
+    }
+
+    #[test]
+    fn test_is_equivalent() {
+        let a = parse("3[]..4[];(0[])<=9").unwrap();
+        let b = parse("3[0..7]..4[7];(0[0..7])<10").unwrap();
+        assert!(a.is_equivalent(&b, 8));
+        assert!(!a.is_equivalent(&b, 16));
+        assert!(!a.is_equivalent(&parse("3[]..4[];(0[])<9").unwrap(), 8));
+        assert!(!a.is_equivalent(&parse("3[]..4[];(0[])<=9:q8.8").unwrap(), 8));
+
+        let invalid = parse("[9]").unwrap();
+        assert!(invalid.is_equivalent(&invalid, 8));
+        assert!(!invalid.is_equivalent(&parse("1[1]").unwrap(), 8));
+    }
+}
@@ -1,19 +1,27 @@
+#[cfg(feature = "alloc")]
+use crate::borrowed::Variants;
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::fmt::Display;
 use nom::{
     branch::alt,
-    bytes::complete::tag,
+    bytes::complete::{tag, take_while1},
+    character::complete::char,
+    character::complete::u64 as u64_parser,
     character::complete::u8 as u8_parser,
-    character::complete::{char, one_of},
-    combinator::{map, opt, recognize, value},
-    multi::many1,
+    character::complete::{alpha1, alphanumeric1, space0, space1},
+    combinator::{map, map_opt, opt, recognize, value},
+    multi::many0_count,
     //number::complete::{i32, u8},
-    sequence::{delimited, preceded, separated_pair, tuple},
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
     IResult,
 };
 
-#[derive(Debug, PartialEq, Clone)]
+// The value of a literal, written in hexadecimal or binary
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum LiteralType {
-    Hex(String),
-    Bin(String),
+    Hex(u64),
+    Bin(u64),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -50,22 +58,27 @@ pub enum Repeat {
         condition: Condition,
         limit: usize,
     },
+    // Present once if the bits of a word have a value, otherwise absent
+    Conditional {
+        word: Word,
+        value: u64,
+    },
     // No repeat has been specified.
     // Having this removes the need to have an extra Option
     None,
 }
 
 impl Repeat {
-    // Get the max number of repeats specified.
-    fn max_repeats(&self) -> usize {
+    /// Get the max number of repeats specified.
+    pub fn max_repeats(&self) -> usize {
         match self {
-            Repeat::None => 1,
+            Repeat::None | Repeat::Conditional { .. } => 1,
             Repeat::Fixed(number) => *number,
             Repeat::Variable {
                 condition: Condition::Lt,
                 limit,
                 ..
-            } => (*limit) - 1,
+            } => limit.saturating_sub(1),
             Repeat::Variable {
                 condition: Condition::Lte,
                 limit,
@@ -75,12 +88,39 @@ impl Repeat {
     }
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum FieldType {
+    // A single bit interpreted as true (1) or false (0)
+    Bool,
+    // An IEEE 754 half precision float
+    F16,
+    // An IEEE 754 single precision float
+    F32,
+    // A signed fixed point number in two's complement. The integer
+    // bits include the sign bit.
+    Fixed {
+        integer_bits: u8,
+        fractional_bits: u8,
+    },
+    // Named values, e.g. {0=Off,1=On}
+    #[cfg(feature = "alloc")]
+    Enum(Vec<(u64, String)>),
+    // No type has been specified so the bits are an unsigned integer.
+    // Having this removes the need to have an extra Option
+    Unsigned,
+}
+
 // #[derive(Debug, PartialEq, Copy, Clone)]
 #[derive(Debug, PartialEq, Clone)]
 pub struct BitSpec {
+    // The word holding the index of the start word, for data whose position is
+    // only known when decoding. The indexes of the start and end words are then
+    // relative to the word it points to.
+    pub pointer: Option<Word>,
     pub start: Word,
     pub end: Option<Word>,
     pub repeat: Repeat,
+    pub field_type: FieldType,
 }
 
 fn index(input: &str) -> IResult<&str, u8> {
@@ -91,22 +131,102 @@ impl BitSpec {
     /// Get the max size in bytes of an array that could
     /// contain the bit specification.
     pub fn max_size(&self) -> usize {
-        let n_words = match self {
-            BitSpec {
-                start: _,
-                end: None,
-                ..
-            } => 1,
-            BitSpec {
-                start: Word { index: w, .. },
-                end: Some(Word { index: v, .. }),
-                ..
-            } => v - w + 1,
-        };
+        self.word_count().saturating_mul(self.repeat.max_repeats())
+    }
+}
+
+// The canonical form of a bit specification always gives the word index
+impl Display for Word {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}[{}]", self.index, self.bit_range)
+    }
+}
+
+// The bits between the brackets of a word
+impl Display for BitRange {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BitRange::Single(bit) => write!(f, "{bit}"),
+            BitRange::Range(start, end) => write!(f, "{start}..{end}"),
+            BitRange::WholeWord => Ok(()),
+            BitRange::Literal(literal) => write!(f, "{literal}"),
+        }
+    }
+}
+
+impl Display for LiteralType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LiteralType::Hex(value) => write!(f, "0x{value:X}"),
+            LiteralType::Bin(value) => write!(f, "0b{value:b}"),
+        }
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Condition::Lt => write!(f, "<"),
+            Condition::Lte => write!(f, "<="),
+        }
+    }
+}
 
-        let repeats = self.repeat.max_repeats();
+impl Display for Repeat {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Repeat::Fixed(number) => write!(f, ";{number}"),
+            Repeat::Variable {
+                word,
+                condition,
+                limit,
+            } => write!(f, ";({word}){condition}{limit}"),
+            Repeat::Conditional { word, value } => write!(f, ";if {word}=={value}"),
+            Repeat::None => Ok(()),
+        }
+    }
+}
 
-        n_words * repeats
+impl Display for FieldType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FieldType::Bool => write!(f, ":bool"),
+            FieldType::F16 => write!(f, ":f16"),
+            FieldType::F32 => write!(f, ":f32"),
+            FieldType::Fixed {
+                integer_bits,
+                fractional_bits,
+            } => write!(f, ":q{integer_bits}.{fractional_bits}"),
+            #[cfg(feature = "alloc")]
+            FieldType::Enum(variants) => {
+                write!(f, ":{{")?;
+                for (i, (value, name)) in variants.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}={name}")?;
+                }
+                write!(f, "}}")
+            }
+            FieldType::Unsigned => Ok(()),
+        }
+    }
+}
+
+impl Display for BitSpec {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.pointer {
+            // Data at the word pointed to is the usual case, so the index is left out
+            Some(pointer) if self.start.index == 0 => {
+                write!(f, "@({pointer})[{}]", self.start.bit_range)?
+            }
+            Some(pointer) => write!(f, "@({pointer}){}", self.start)?,
+            None => write!(f, "{}", self.start)?,
+        }
+        if let Some(end) = &self.end {
+            write!(f, "..{end}")?;
+        }
+        write!(f, "{}{}", self.repeat, self.field_type)
     }
 }
 
@@ -177,12 +297,23 @@ fn literal_word(input: &str) -> IResult<&str, Word> {
 
 // word = bit_range | [index] "[" [bit_range] "]" | index "[" literal "]";   (* NEW *)
 // TODO Ignore literals for the time being
-fn word(input: &str) -> IResult<&str, Word> {
-    let (remaining, word) = alt((fully_qualified_word, bit_range_as_word, literal_word))(input)?;
+pub(crate) fn word(input: &str) -> IResult<&str, Word> {
+    // Literal words need to be tried before a bit range takes the index
+    let (remaining, word) = alt((fully_qualified_word, literal_word, bit_range_as_word))(input)?;
 
     Ok((remaining, word))
 }
 
+// The start word of data whose position is read from another word, e.g.
+// "@(2[])[0..3]". The index of the start word is relative to the word pointed to.
+// indirect_word = ["@"] "(" word ")" ([index] "[" [bit_range] "]" | [index] "[" literal "]") ;
+fn indirect_word(input: &str) -> IResult<&str, (Word, Word)> {
+    pair(
+        preceded(opt(char('@')), delimited(char('('), word, char(')'))),
+        alt((fully_qualified_word, literal_word)),
+    )(input)
+}
+
 fn condition(input: &str) -> IResult<&str, Condition> {
     let (remaining, condition) = alt((
         value(Condition::Lte, tag("<=")),
@@ -221,39 +352,121 @@ fn variable_repeat(input: &str) -> IResult<&str, Repeat> {
     ))
 }
 
-// repeat = ";" (fixed_repeat  | variable_repeat)  ;
-fn repeat(input: &str) -> IResult<&str, Repeat> {
+// A number in decimal, hexadecimal or binary
+// number = literal | u64 ;
+pub(crate) fn number(input: &str) -> IResult<&str, u64> {
+    alt((map(literal, |literal| literal.value()), u64_parser))(input)
+}
+
+// conditional_repeat = "if" " " word "==" number ;
+fn conditional_repeat(input: &str) -> IResult<&str, Repeat> {
+    let (remaining, (_, word, _, value)) =
+        tuple((pair(tag("if"), space1), word, tag("=="), number))(input)?;
+
+    Ok((remaining, Repeat::Conditional { word, value }))
+}
+
+// repeat = ";" (fixed_repeat  | variable_repeat | conditional_repeat)  ;
+pub(crate) fn repeat(input: &str) -> IResult<&str, Repeat> {
     //let (remaining, (_, repeat)) = tuple((tag(";"), alt((variable_repeat, fixed_repeat))))(input)?;
-    let (remaining, repeat) = preceded(tag(";"), alt((variable_repeat, fixed_repeat)))(input)?;
+    let (remaining, repeat) = preceded(
+        tag(";"),
+        alt((variable_repeat, fixed_repeat, conditional_repeat)),
+    )(input)?;
 
     Ok((remaining, repeat))
 }
 
-fn hexadecimal(input: &str) -> IResult<&str, LiteralType> {
-    // preceded(
-    //     alt((tag("0x"), tag("0X"))),
-    //     recognize(many1(terminated(
-    //         one_of("0123456789abcdefABCDEF"),
-    //         many0(char('_')),
-    //     ))),
-    // )
-    // .parse(input)
+// The digits of a literal in `radix`, ignoring underscores
+fn digits(input: &str, radix: u32) -> IResult<&str, u64> {
+    map_opt(
+        take_while1(move |c: char| c == '_' || c.is_digit(radix)),
+        move |digits: &str| {
+            let mut value: u64 = 0;
+            let mut any = false;
+            for c in digits.chars().filter(|c| *c != '_') {
+                let digit = c.to_digit(radix)?;
+                value = value.checked_mul(radix as u64)?.checked_add(digit as u64)?;
+                any = true;
+            }
+            any.then_some(value)
+        },
+    )(input)
+}
 
-    let (input, _) = alt((tag("0x"), tag("0X")))(input)?;
-    //let (remaining, bin) = recognize(many1(one_of("01_")))(input)?;
-    let (remaining, hex) =
-        // recognize(all_consuming(many1(one_of("0123456789abcdefABCDEF_"))))(input)?;
-        recognize(many1(one_of("0123456789abcdefABCDEF_")))(input)?;
+fn hexadecimal(input: &str) -> IResult<&str, LiteralType> {
+    let (remaining, value) = preceded(alt((tag("0x"), tag("0X"))), |i| digits(i, 16))(input)?;
 
-    Ok((remaining, LiteralType::Hex(hex.to_string())))
+    Ok((remaining, LiteralType::Hex(value)))
 }
 
 fn binary(input: &str) -> IResult<&str, LiteralType> {
-    let (input, _) = alt((tag("0b"), tag("0B")))(input)?;
-    //let (remaining, bin) = recognize(many1(one_of("01_")))(input)?;
-    let (remaining, bin) = recognize(many1(one_of("01_")))(input)?;
+    let (remaining, value) = preceded(alt((tag("0b"), tag("0B"))), |i| digits(i, 2))(input)?;
+
+    Ok((remaining, LiteralType::Bin(value)))
+}
 
-    Ok((remaining, LiteralType::Bin(bin.to_string())))
+// enum_variant = u64 "=" identifier ;
+pub(crate) fn enum_variant(input: &str) -> IResult<&str, (u64, &str)> {
+    separated_pair(
+        u64_parser,
+        tag("="),
+        recognize(pair(alpha1, many0_count(alt((alphanumeric1, tag("_")))))),
+    )(input)
+}
+
+pub(crate) fn variant_separator(input: &str) -> IResult<&str, char> {
+    delimited(space0, char(','), space0)(input)
+}
+
+// The text of the variants between the braces of an enumeration
+// variants = enum_variant {"," enum_variant} ;
+pub(crate) fn variants(input: &str) -> IResult<&str, &str> {
+    recognize(pair(
+        enum_variant,
+        many0_count(preceded(variant_separator, enum_variant)),
+    ))(input)
+}
+
+// enumeration = "{" variants "}" ;
+#[cfg(feature = "alloc")]
+fn enumeration(input: &str) -> IResult<&str, FieldType> {
+    let (remaining, variants) = delimited(char('{'), variants, char('}'))(input)?;
+
+    Ok((remaining, FieldType::Enum(Variants::new(variants).to_vec())))
+}
+
+// The names of an enumeration cannot be held without allocation
+#[cfg(not(feature = "alloc"))]
+fn enumeration(input: &str) -> IResult<&str, FieldType> {
+    nom::combinator::fail(input)
+}
+
+// The integer and fractional bits of a fixed point number
+// fixed_point = "q" u8 "." u8 ;
+pub(crate) fn fixed_point(input: &str) -> IResult<&str, (u8, u8)> {
+    preceded(tag("q"), separated_pair(u8_parser, char('.'), u8_parser))(input)
+}
+
+// field_type = ":" ("bool" | "f16" | "f32" | fixed_point | enumeration) ;
+fn field_type(input: &str) -> IResult<&str, FieldType> {
+    let (remaining, field_type) = preceded(
+        tag(":"),
+        alt((
+            value(FieldType::Bool, tag("bool")),
+            value(FieldType::F16, tag("f16")),
+            value(FieldType::F32, tag("f32")),
+            map(fixed_point, |(integer_bits, fractional_bits)| {
+                FieldType::Fixed {
+                    integer_bits,
+                    fractional_bits,
+                }
+            }),
+            enumeration,
+        )),
+    )(input)?;
+
+    Ok((remaining, field_type))
 }
 
 fn literal(input: &str) -> IResult<&str, LiteralType> {
@@ -262,19 +475,41 @@ fn literal(input: &str) -> IResult<&str, LiteralType> {
 }
 
 // This is the top level parser
-// word_range = word [".." word] [repeat]
+// word_range = (word | indirect_word) [".." word] [repeat] [field_type]
 pub fn bit_spec(input: &str) -> IResult<&str, BitSpec> {
-    let (remaining, (start, end, repeat)) =
+    let (remaining, ((pointer, start), end, repeat, field_type)) =
         //tuple((word, opt(preceded(tag(".."), word)), opt(repeat)))(input)?;
-        tuple((word, opt(preceded(tag(".."), word)), 
-               map(opt(repeat), |r| r.unwrap_or(Repeat::None))))(input)?;
+        tuple((start_word, opt(preceded(tag(".."), word)), 
+               map(opt(repeat), |r| r.unwrap_or(Repeat::None)),
+               map(opt(field_type), |t| t.unwrap_or(FieldType::Unsigned))))(input)?;
 
-    Ok((remaining, BitSpec { start, end, repeat }))
+    Ok((
+        remaining,
+        BitSpec {
+            pointer,
+            start,
+            end,
+            repeat,
+            field_type,
+        },
+    ))
+}
+
+// The start word with the pointer to it, if any
+pub(crate) fn start_word(input: &str) -> IResult<&str, (Option<Word>, Word)> {
+    alt((
+        map(indirect_word, |(pointer, start)| (Some(pointer), start)),
+        map(word, |start| (None, start)),
+    ))(input)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(not(feature = "std"))]
+    use std::prelude::rust_2021::*;
+    #[cfg(all(feature = "alloc", not(feature = "std")))]
+    use std::vec;
 
     #[test]
     fn test_literal_word() {
@@ -282,7 +517,7 @@ mod tests {
         let (_, r) = literal_word(data).unwrap();
         let expected = Word {
             index: 0,
-            bit_range: BitRange::Literal(LiteralType::Hex("1234".to_string())),
+            bit_range: BitRange::Literal(LiteralType::Hex(0x1234)),
         };
         assert_eq!(r, expected);
 
@@ -290,7 +525,7 @@ mod tests {
         let (_, r) = literal_word(data).unwrap();
         let expected = Word {
             index: 4,
-            bit_range: BitRange::Literal(LiteralType::Bin("0011_1100".to_string())),
+            bit_range: BitRange::Literal(LiteralType::Bin(0b0011_1100)),
         };
         assert_eq!(r, expected);
 
@@ -326,6 +561,26 @@ mod tests {
         assert!(repeat(data).is_err());
     }
 
+    #[test]
+    fn test_conditional_repeat() {
+        let expected = Repeat::Conditional {
+            word: Word {
+                index: 0,
+                bit_range: BitRange::Single(7),
+            },
+            value: 1,
+        };
+        for data in [";if 0[7]==1", ";if [7]==0b1", ";if 7==0x1"] {
+            let (remaining, r) = repeat(data).unwrap();
+            assert_eq!(remaining, "");
+            assert_eq!(r, expected);
+        }
+        assert_eq!(expected.max_repeats(), 1);
+
+        assert!(repeat(";if0[7]==1").is_err());
+        assert!(repeat(";if 0[7]=1").is_err());
+    }
+
     #[test]
     fn test_variable_repeat() {
         let data = "(4[])<=48";
@@ -414,60 +669,70 @@ mod tests {
         let data = "4";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::Single(4),
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
         let data = "4..6";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::Range(4, 6),
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
         let data = "[4..6]";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::Range(4, 6),
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
         let data = "5[3..7]";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 5,
                 bit_range: BitRange::Range(3, 7),
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
         let data = "5[]";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 5,
                 bit_range: BitRange::WholeWord,
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
     }
@@ -479,6 +744,7 @@ mod tests {
         let (_, r) = bit_spec(data).unwrap();
 
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 3,
                 bit_range: BitRange::Range(4, 7),
@@ -488,6 +754,7 @@ mod tests {
                 bit_range: BitRange::Range(0, 5),
             }),
             repeat: Repeat::Fixed(48),
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
@@ -502,6 +769,7 @@ mod tests {
             limit: 49,
         };
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 4,
                 bit_range: BitRange::WholeWord,
@@ -510,7 +778,8 @@ mod tests {
                 index: 7,
                 bit_range: BitRange::WholeWord,
             }),
-            repeat: repeat,
+            repeat,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
     }
@@ -521,6 +790,7 @@ mod tests {
         let (_, r) = bit_spec(data).unwrap();
 
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 3,
                 bit_range: BitRange::Range(4, 7),
@@ -530,12 +800,14 @@ mod tests {
                 bit_range: BitRange::Range(0, 5),
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
         let data = "4[]..7[]";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 4,
                 bit_range: BitRange::WholeWord,
@@ -545,12 +817,14 @@ mod tests {
                 bit_range: BitRange::WholeWord,
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
         let data = "[]..5[]";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::WholeWord,
@@ -560,24 +834,28 @@ mod tests {
                 bit_range: BitRange::WholeWord,
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
         let data = "[]";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::WholeWord,
             },
             end: None,
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
 
         let data = "[]..6[0..5]";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::WholeWord,
@@ -587,6 +865,7 @@ mod tests {
                 bit_range: BitRange::Range(0, 5),
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
     }
@@ -597,6 +876,7 @@ mod tests {
         let data = "3..5..4[]";
         let (_, r) = bit_spec(data).unwrap();
         let expected = BitSpec {
+            pointer: None,
             start: Word {
                 index: 0,
                 bit_range: BitRange::Range(3, 5),
@@ -606,6 +886,7 @@ mod tests {
                 bit_range: BitRange::WholeWord,
             }),
             repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
         };
         assert_eq!(r, expected);
     }
@@ -660,7 +941,18 @@ mod tests {
                 index: 0,
                 bit_range: BitRange::Single(7)
             }
-        )
+        );
+
+        let data = "5[0b1101_0001]";
+        let (remaining, r) = word(data).unwrap();
+        assert_eq!(
+            r,
+            Word {
+                index: 5,
+                bit_range: BitRange::Literal(LiteralType::Bin(0b1101_0001))
+            }
+        );
+        assert_eq!(remaining, "");
     }
 
     #[test]
@@ -744,11 +1036,11 @@ mod tests {
     fn test_literal() {
         let data = "0xABCD";
         let (_, r) = literal(data).unwrap();
-        assert_eq!(r, LiteralType::Hex("ABCD".to_string()));
+        assert_eq!(r, LiteralType::Hex(0xABCD));
 
         let data = "0b1011_1100";
         let (_, r) = literal(data).unwrap();
-        assert_eq!(r, LiteralType::Bin("1011_1100".to_string()));
+        assert_eq!(r, LiteralType::Bin(0b1011_1100));
 
         // let data = "0b1011_11b0";
         // assert!(literal(data).is_err());
@@ -757,25 +1049,157 @@ mod tests {
         // assert!(literal(data).is_err());
     }
 
+    #[test]
+    fn test_field_type() {
+        let data = ":bool";
+        let (_, r) = field_type(data).unwrap();
+        assert_eq!(r, FieldType::Bool);
+
+        let data = ":f16";
+        let (_, r) = field_type(data).unwrap();
+        assert_eq!(r, FieldType::F16);
+
+        let data = ":f32";
+        let (_, r) = field_type(data).unwrap();
+        assert_eq!(r, FieldType::F32);
+
+        let data = ":q8.8";
+        let (_, r) = field_type(data).unwrap();
+        assert_eq!(
+            r,
+            FieldType::Fixed {
+                integer_bits: 8,
+                fractional_bits: 8
+            }
+        );
+
+        #[cfg(feature = "alloc")]
+        {
+            let data = ":{0=Off,1=On, 2=Auto}";
+            let (_, r) = field_type(data).unwrap();
+            assert_eq!(
+                r,
+                FieldType::Enum(vec![
+                    (0, "Off".to_string()),
+                    (1, "On".to_string()),
+                    (2, "Auto".to_string())
+                ])
+            );
+        }
+
+        let data = ":{}";
+        assert!(field_type(data).is_err());
+
+        let data = ":i32";
+        assert!(field_type(data).is_err());
+    }
+
+    #[test]
+    fn test_bit_spec_with_field_type() {
+        let data = "3[]..4[];2:q8.8";
+        let (_, r) = bit_spec(data).unwrap();
+        let expected = BitSpec {
+            pointer: None,
+            start: Word {
+                index: 3,
+                bit_range: BitRange::WholeWord,
+            },
+            end: Some(Word {
+                index: 4,
+                bit_range: BitRange::WholeWord,
+            }),
+            repeat: Repeat::Fixed(2),
+            field_type: FieldType::Fixed {
+                integer_bits: 8,
+                fractional_bits: 8,
+            },
+        };
+        assert_eq!(r, expected);
+
+        let data = "[7]:bool";
+        let (_, r) = bit_spec(data).unwrap();
+        let expected = BitSpec {
+            pointer: None,
+            start: Word {
+                index: 0,
+                bit_range: BitRange::Single(7),
+            },
+            end: None,
+            repeat: Repeat::None,
+            field_type: FieldType::Bool,
+        };
+        assert_eq!(r, expected);
+    }
+
+    #[test]
+    fn test_display() {
+        for data in [
+            "0[4]",
+            "3[4..7]..6[0..5];48",
+            "4[]..7[];(3[])<=49",
+            "5[0b11010001]",
+            "1[]..2[]:q8.8",
+            #[cfg(feature = "alloc")]
+            "0[0..1]:{0=Off,1=On}",
+            "@(2[])[0..7]",
+            "@(1[0..3])2[]..3[];4",
+            "5[]..6[];if 0[4..7]==10:q8.8",
+        ] {
+            let (_, r) = bit_spec(data).unwrap();
+            assert_eq!(r.to_string(), data);
+        }
+
+        let (_, r) = bit_spec("3..5").unwrap();
+        assert_eq!(r.to_string(), "0[3..5]");
+        let (_, r) = bit_spec("(2[])[]").unwrap();
+        assert_eq!(r.to_string(), "@(2[])[]");
+    }
+
+    #[test]
+    fn test_indirect_word() {
+        let expected = BitSpec {
+            pointer: Some(Word {
+                index: 2,
+                bit_range: BitRange::WholeWord,
+            }),
+            start: Word {
+                index: 0,
+                bit_range: BitRange::Range(0, 7),
+            },
+            end: None,
+            repeat: Repeat::None,
+            field_type: FieldType::Unsigned,
+        };
+        for data in ["(2[])[0..7]", "@(2[])[0..7]", "@(2[])0[0..7]"] {
+            let (remaining, r) = bit_spec(data).unwrap();
+            assert_eq!(remaining, "");
+            assert_eq!(r, expected);
+        }
+
+        let (_, r) = bit_spec("@([4..7])[0xA5]").unwrap();
+        assert_eq!(r.start.bit_range, BitRange::Literal(LiteralType::Hex(0xA5)));
+        assert_eq!(r.pointer.unwrap().bit_range, BitRange::Range(4, 7));
+    }
+
     #[test]
     fn test_hexadecimal() {
         let data = "0x45B7";
         let (_, hex) = hexadecimal(data).unwrap();
-        assert_eq!(hex, LiteralType::Hex("45B7".to_string()));
+        assert_eq!(hex, LiteralType::Hex(0x45B7));
 
         let data = "0X45_B7";
         let (_, hex) = hexadecimal(data).unwrap();
-        assert_eq!(hex, LiteralType::Hex("45_B7".to_string()));
+        assert_eq!(hex, LiteralType::Hex(0x45B7));
     }
 
     #[test]
     fn test_binary() {
         let data = "0b10001100";
         let (_, bin) = binary(data).unwrap();
-        assert_eq!(bin, LiteralType::Bin("10001100".to_string()));
+        assert_eq!(bin, LiteralType::Bin(0b1000_1100));
 
         let data = "0b1000_1100";
         let (_, bin) = binary(data).unwrap();
-        assert_eq!(bin, LiteralType::Bin("1000_1100".to_string()));
+        assert_eq!(bin, LiteralType::Bin(0b1000_1100));
     }
 }
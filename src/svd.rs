@@ -0,0 +1,844 @@
+//! Import and export of CMSIS-SVD register maps.
+//!
+//! Each peripheral of an SVD device becomes a layout with a word for the
+//! smallest register size, and each field of a register becomes a layout field
+//! named `<register>_<field>`, or just the register name if the register has a
+//! single field of the same name. A field at `bitOffset` with `bitWidth` bits in
+//! a register at `addressOffset` refers to the same bits in the layout:
+//! ```text
+//! <register>                          layout Timer word_bits=32 {
+//!   <name>CTRL</name>                     CTRL_EN: 1[0]
+//!   <addressOffset>0x4</addressOffset>    CTRL_MODE: 1[1..2]:{0=Single,1=Repeat}
+//!   <fields>...</fields>              }
+//! </register>
+//! ```
+//! Registers without fields become a field for the whole register. Arrays of
+//! registers and clusters are expanded, replacing `%s` in the name with the
+//! index, and peripherals that are `derivedFrom` another use its registers.
+//! Word indexes in a layout go up to 255, so registers after that word cannot
+//! be imported.
+//!
+//! When exporting, the words used by each field are grouped into registers,
+//! with a register for each repetition of a repeated field. A repeated field
+//! that is alone in its words becomes an array register.
+
+use std::fmt::Write;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till, take_till1, take_until, take_while1},
+    character::complete::{char, multispace0, multispace1},
+    combinator::{all_consuming, map, value},
+    multi::many0,
+    sequence::{delimited, preceded, terminated, tuple},
+    IResult,
+};
+
+use crate::access::BitOrder;
+use crate::export::{description, segments, Segment};
+use crate::layout::{Field, Layout};
+use crate::parser::{BitSpec, FieldType};
+use crate::Error;
+
+// An XML element with its attributes, child elements and text
+#[derive(Debug, PartialEq, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    // The text of a child element
+    fn text_of(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.trim())
+    }
+
+    fn number_of(&self, name: &str) -> Result<Option<u64>, Error> {
+        self.text_of(name).map(number).transpose()
+    }
+}
+
+// Parse the root element of an XML document
+fn document(input: &str) -> Result<Element, Error> {
+    all_consuming(delimited(misc, element, misc))(input)
+        .map(|(_, element)| element)
+        .map_err(|_| Error::SvdParseError)
+}
+
+// Whitespace, comments, processing instructions and document types
+fn misc(input: &str) -> IResult<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            multispace1,
+            comment,
+            delimited(tag("<?"), take_until("?>"), tag("?>")),
+            delimited(tag("<!DOCTYPE"), take_until(">"), tag(">")),
+        ))),
+    )(input)
+}
+
+fn comment(input: &str) -> IResult<&str, &str> {
+    delimited(tag("<!--"), take_until("-->"), tag("-->"))(input)
+}
+
+fn name(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || "_:-.".contains(c))(input)
+}
+
+// attribute = name "=" quoted
+fn attribute(input: &str) -> IResult<&str, (String, String)> {
+    let (remaining, (name, _, _, _, value)) = tuple((
+        name,
+        multispace0,
+        char('='),
+        multispace0,
+        alt((
+            delimited(char('"'), take_till(|c| c == '"'), char('"')),
+            delimited(char('\''), take_till(|c| c == '\''), char('\'')),
+        )),
+    ))(input)?;
+
+    Ok((remaining, (name.to_string(), unescape(value))))
+}
+
+enum Content {
+    Element(Element),
+    Text(String),
+    Comment,
+}
+
+fn content(input: &str) -> IResult<&str, Content> {
+    alt((
+        map(comment, |_| Content::Comment),
+        map(
+            delimited(tag("<![CDATA["), take_until("]]>"), tag("]]>")),
+            |text: &str| Content::Text(text.to_string()),
+        ),
+        map(element, Content::Element),
+        map(take_till1(|c| c == '<'), |text| {
+            Content::Text(unescape(text))
+        }),
+    ))(input)
+}
+
+// element = "<" name {attribute} ("/>" | ">" {content} "</" name ">")
+fn element(input: &str) -> IResult<&str, Element> {
+    let (remaining, (name, attributes, _)) = tuple((
+        preceded(char('<'), name),
+        many0(preceded(multispace1, attribute)),
+        multispace0,
+    ))(input)?;
+    let mut element = Element {
+        name: name.to_string(),
+        attributes,
+        ..Element::default()
+    };
+
+    if let Ok((remaining, _)) = tag::<_, _, nom::error::Error<&str>>("/>")(remaining) {
+        return Ok((remaining, element));
+    }
+
+    let (remaining, contents) = preceded(
+        char('>'),
+        terminated(
+            many0(content),
+            tuple((tag("</"), tag(name), multispace0, char('>'))),
+        ),
+    )(remaining)?;
+
+    for content in contents {
+        match content {
+            Content::Element(child) => element.children.push(child),
+            Content::Text(text) => element.text.push_str(&text),
+            Content::Comment => {}
+        }
+    }
+
+    Ok((remaining, element))
+}
+
+// Replace the predefined and numeric character references
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let reference = &rest[1..end];
+        let c = match reference {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match reference.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => reference.strip_prefix('#').and_then(|d| d.parse().ok()),
+            }
+            .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// An SVD number, which is decimal, hex with 0x or binary with # or 0b
+fn number(text: &str) -> Result<u64, Error> {
+    let text = text.trim();
+    let (digits, radix) =
+        if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            (hex, 16)
+        } else if let Some(binary) = text.strip_prefix('#').or_else(|| text.strip_prefix("0b")) {
+            (binary, 2)
+        } else {
+            (text, 10)
+        };
+
+    u64::from_str_radix(digits, radix).map_err(|_| Error::SvdParseError)
+}
+
+// A register with the absolute bits of its fields
+struct Register {
+    name: String,
+    // The name without the index of an array
+    base_name: String,
+    // The offset in bytes from the start of the peripheral
+    offset: u64,
+    size: usize,
+    fields: Vec<RegisterField>,
+}
+
+struct RegisterField {
+    name: String,
+    offset: usize,
+    width: usize,
+    field_type: FieldType,
+}
+
+/// Import the peripherals of an SVD device as layouts.
+pub fn parse(input: &str) -> Result<Vec<Layout>, Error> {
+    let device = document(input)?;
+    if device.name != "device" {
+        return Err(Error::SvdParseError);
+    }
+    let size = device.number_of("size")?.unwrap_or(32) as usize;
+    let peripherals: Vec<&Element> = device
+        .child("peripherals")
+        .map(|peripherals| peripherals.children("peripheral").collect())
+        .unwrap_or_default();
+
+    let mut layouts = Vec::new();
+    for peripheral in &peripherals {
+        let name = peripheral.text_of("name").ok_or(Error::SvdParseError)?;
+        let size = peripheral.number_of("size")?.map_or(size, |s| s as usize);
+
+        // A derived peripheral without its own registers uses those of the other
+        let mut registers = peripheral.child("registers");
+        if let (None, Some(base)) = (registers, peripheral.attribute("derivedFrom")) {
+            registers = peripherals
+                .iter()
+                .find(|p| p.text_of("name") == Some(base))
+                .and_then(|p| p.child("registers"));
+        }
+
+        let mut collected = Vec::new();
+        if let Some(registers) = registers {
+            collect(registers, "", 0, size, &mut collected)?;
+        }
+        layouts.push(layout(name, &collected)?);
+    }
+
+    Ok(layouts)
+}
+
+// Collect the registers of a `registers` or `cluster` element
+fn collect(
+    parent: &Element,
+    prefix: &str,
+    base: u64,
+    size: usize,
+    registers: &mut Vec<Register>,
+) -> Result<(), Error> {
+    for child in &parent.children {
+        if child.name != "register" && child.name != "cluster" {
+            continue;
+        }
+        let name = child.text_of("name").ok_or(Error::SvdParseError)?;
+        let offset = child
+            .number_of("addressOffset")?
+            .and_then(|offset| base.checked_add(offset))
+            .ok_or(Error::SvdParseError)?;
+        let size = child.number_of("size")?.map_or(size, |s| s as usize);
+
+        let child_name = name;
+        for (name, offset) in expand(child, name, offset)? {
+            let name = format!("{prefix}{name}");
+            if child.name == "cluster" {
+                collect(child, &format!("{name}_"), offset, size, registers)?;
+            } else {
+                registers.push(Register {
+                    fields: register_fields(child, size)?,
+                    base_name: format!("{prefix}{}", name_of_array(child_name)),
+                    name,
+                    offset,
+                    size,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// The name and offset of each element of an array, or of a single register
+fn expand(element: &Element, name: &str, offset: u64) -> Result<Vec<(String, u64)>, Error> {
+    let Some(dim) = element.number_of("dim")? else {
+        return Ok(vec![(name.to_string(), offset)]);
+    };
+    let increment = element
+        .number_of("dimIncrement")?
+        .ok_or(Error::SvdParseError)?;
+    // A layout has at most 256 words, so larger arrays cannot be imported
+    if dim > 256 {
+        return Err(Error::SvdParseError);
+    }
+
+    let indexes: Vec<String> = match element.text_of("dimIndex") {
+        Some(indexes) => match indexes.split_once('-') {
+            Some((first, last)) if !indexes.contains(',') => {
+                let (first, last) = (number(first)?, number(last)?);
+                if last < first || last - first >= dim {
+                    return Err(Error::SvdParseError);
+                }
+                (first..=last).map(|i| i.to_string()).collect()
+            }
+            _ => indexes.split(',').map(|i| i.trim().to_string()).collect(),
+        },
+        None => (0..dim).map(|i| i.to_string()).collect(),
+    };
+    if indexes.len() as u64 != dim {
+        return Err(Error::SvdParseError);
+    }
+
+    indexes
+        .iter()
+        .enumerate()
+        .map(|(i, index)| {
+            let name = name
+                .replace("[%s]", &format!("_{index}"))
+                .replace("%s", index);
+            let offset = (i as u64)
+                .checked_mul(increment)
+                .and_then(|step| offset.checked_add(step))
+                .ok_or(Error::SvdParseError)?;
+            Ok((name, offset))
+        })
+        .collect()
+}
+
+fn name_of_array(name: &str) -> String {
+    name.replace("[%s]", "").replace("%s", "")
+}
+
+fn register_fields(register: &Element, size: usize) -> Result<Vec<RegisterField>, Error> {
+    let Some(fields) = register.child("fields") else {
+        return Ok(Vec::new());
+    };
+
+    let mut register_fields = Vec::new();
+    for field in fields.children("field") {
+        let name = field.text_of("name").ok_or(Error::SvdParseError)?;
+        let (offset, width) = field_bits(field)?;
+        if width == 0 || offset.checked_add(width).is_none_or(|end| end > size) {
+            return Err(Error::SvdParseError);
+        }
+
+        let mut variants = Vec::new();
+        for values in field.children("enumeratedValues") {
+            for variant in values.children("enumeratedValue") {
+                // Default values and values with don't care bits have no single value
+                if let (Some(name), Some(value)) =
+                    (variant.text_of("name"), variant.text_of("value"))
+                {
+                    if let Ok(value) = number(value) {
+                        variants.push((value, name.to_string()));
+                    }
+                }
+            }
+        }
+
+        register_fields.push(RegisterField {
+            name: name.to_string(),
+            offset,
+            width,
+            field_type: match variants.is_empty() {
+                true => FieldType::Unsigned,
+                false => FieldType::Enum(variants),
+            },
+        });
+    }
+
+    Ok(register_fields)
+}
+
+// The offset and width of a field from bitOffset and bitWidth, lsb and msb
+// or a bitRange of "[msb:lsb]"
+fn field_bits(field: &Element) -> Result<(usize, usize), Error> {
+    let (lsb, msb) = if let Some(offset) = field.number_of("bitOffset")? {
+        let width = field.number_of("bitWidth")?.unwrap_or(1);
+        let msb = offset
+            .checked_add(width.max(1) - 1)
+            .ok_or(Error::SvdParseError)?;
+        (offset, msb)
+    } else if let (Some(lsb), Some(msb)) = (field.number_of("lsb")?, field.number_of("msb")?) {
+        (lsb, msb)
+    } else {
+        let range = field.text_of("bitRange").ok_or(Error::SvdParseError)?;
+        let (msb, lsb) = range
+            .strip_prefix('[')
+            .and_then(|r| r.strip_suffix(']'))
+            .and_then(|r| r.split_once(':'))
+            .ok_or(Error::SvdParseError)?;
+        (number(lsb)?, number(msb)?)
+    };
+    if msb < lsb {
+        return Err(Error::SvdParseError);
+    }
+    let lsb = usize::try_from(lsb).map_err(|_| Error::SvdParseError)?;
+    let width = usize::try_from(msb - lsb as u64)
+        .ok()
+        .and_then(|width| width.checked_add(1))
+        .ok_or(Error::SvdParseError)?;
+
+    Ok((lsb, width))
+}
+
+// Make a layout with a word for the smallest register
+fn layout(name: &str, registers: &[Register]) -> Result<Layout, Error> {
+    let word_bits = registers.iter().map(|r| r.size).min().unwrap_or(32);
+    if word_bits == 0 || !word_bits.is_multiple_of(8) || word_bits > 64 {
+        return Err(Error::InvalidWordSize);
+    }
+
+    let mut fields = Vec::new();
+    for register in registers {
+        let start = usize::try_from(register.offset)
+            .ok()
+            .and_then(|offset| offset.checked_mul(8))
+            .ok_or(Error::SvdParseError)?;
+        if !start.is_multiple_of(word_bits) {
+            return Err(Error::InvalidWordSize);
+        }
+
+        if register.fields.is_empty() {
+            fields.push(Field {
+                name: register.name.clone(),
+                bit_spec: BitSpec::from_offset(start, register.size, word_bits, BitOrder::Lsb0)?,
+                scaling: None,
+            });
+        }
+        for field in &register.fields {
+            let name = match register.fields.as_slice() {
+                [only] if only.name == register.base_name => register.name.clone(),
+                _ => format!("{}_{}", register.name, field.name),
+            };
+            fields.push(Field {
+                name,
+                bit_spec: BitSpec {
+                    field_type: field.field_type.clone(),
+                    ..BitSpec::from_offset(
+                        start
+                            .checked_add(field.offset)
+                            .ok_or(Error::SvdParseError)?,
+                        field.width,
+                        word_bits,
+                        BitOrder::Lsb0,
+                    )?
+                },
+                scaling: None,
+            });
+        }
+    }
+
+    Ok(Layout {
+        name: name.to_string(),
+        word_bits,
+        fields,
+        groups: Vec::new(),
+        variants: None,
+    })
+}
+
+/// Export layouts as the peripherals of an SVD device.
+pub fn render(device: &str, layouts: &[Layout]) -> Result<String, Error> {
+    let mut svd = String::new();
+
+    writeln!(svd, "<?xml version=\"1.0\" encoding=\"utf-8\"?>").unwrap();
+    writeln!(
+        svd,
+        "<device schemaVersion=\"1.3\" xmlns:xs=\"http://www.w3.org/2001/XMLSchema-instance\" xs:noNamespaceSchemaLocation=\"CMSIS-SVD.xsd\">"
+    )
+    .unwrap();
+    writeln!(svd, "  <name>{}</name>", escape(device)).unwrap();
+    writeln!(svd, "  <version>1.0</version>").unwrap();
+    writeln!(svd, "  <description>{}</description>", escape(device)).unwrap();
+    writeln!(svd, "  <addressUnitBits>8</addressUnitBits>").unwrap();
+    writeln!(svd, "  <width>32</width>").unwrap();
+    writeln!(svd, "  <peripherals>").unwrap();
+    for layout in layouts {
+        peripheral(&mut svd, layout)?;
+    }
+    writeln!(svd, "  </peripherals>").unwrap();
+    writeln!(svd, "</device>").unwrap();
+
+    Ok(svd)
+}
+
+fn peripheral(svd: &mut String, layout: &Layout) -> Result<(), Error> {
+    let word_bits = layout.word_bits;
+    if word_bits == 0 || !word_bits.is_multiple_of(8) {
+        return Err(Error::InvalidWordSize);
+    }
+    let segments = segments(layout)?;
+
+    // Group the segments that share words
+    let mut groups: Vec<Vec<&Segment>> = Vec::new();
+    let mut group_end = 0;
+    for segment in &segments {
+        let first_word = segment.offset / word_bits;
+        match groups.last_mut() {
+            Some(group) if first_word < group_end => group.push(segment),
+            _ => groups.push(vec![segment]),
+        }
+        group_end = group_end.max((segment.offset + segment.width).div_ceil(word_bits));
+    }
+
+    writeln!(svd, "    <peripheral>").unwrap();
+    writeln!(svd, "      <name>{}</name>", escape(&layout.name)).unwrap();
+    writeln!(svd, "      <baseAddress>0x0</baseAddress>").unwrap();
+    writeln!(svd, "      <registers>").unwrap();
+
+    let mut i = 0;
+    while i < groups.len() {
+        let group = &groups[i];
+        let first_word = group[0].offset / word_bits;
+        let last_word = group
+            .iter()
+            .map(|segment| (segment.offset + segment.width - 1) / word_bits)
+            .max()
+            .unwrap_or(first_word);
+        let words = last_word - first_word + 1;
+        let field = group[0].field;
+
+        // A repeated field alone in its words is an array of registers
+        let repeats = field.bit_spec.repeat.max_repeats();
+        let is_array = group[0].element == Some(0)
+            && repeats > 1
+            && field.bit_spec.word_count() == words
+            && i + repeats <= groups.len()
+            && groups[i..i + repeats]
+                .iter()
+                .all(|g| g.len() == 1 && std::ptr::eq(g[0].field, field));
+
+        writeln!(svd, "        <register>").unwrap();
+        if is_array {
+            writeln!(svd, "          <dim>{repeats}</dim>").unwrap();
+            writeln!(
+                svd,
+                "          <dimIncrement>{:#x}</dimIncrement>",
+                words * word_bits / 8
+            )
+            .unwrap();
+            writeln!(svd, "          <name>{}[%s]</name>", escape(&field.name)).unwrap();
+        } else {
+            let name = match group.as_slice() {
+                [segment] => svd_name(segment),
+                _ => format!("WORD{first_word}"),
+            };
+            writeln!(svd, "          <name>{}</name>", escape(&name)).unwrap();
+        }
+        writeln!(
+            svd,
+            "          <addressOffset>{:#x}</addressOffset>",
+            first_word * word_bits / 8
+        )
+        .unwrap();
+        writeln!(svd, "          <size>{}</size>", words * word_bits).unwrap();
+        writeln!(svd, "          <fields>").unwrap();
+        for segment in group {
+            let name = match is_array {
+                true => field.name.clone(),
+                false => svd_name(segment),
+            };
+            register_field(svd, segment, &name, first_word * word_bits);
+        }
+        writeln!(svd, "          </fields>").unwrap();
+        writeln!(svd, "        </register>").unwrap();
+
+        i += if is_array { repeats } else { 1 };
+    }
+
+    writeln!(svd, "      </registers>").unwrap();
+    writeln!(svd, "    </peripheral>").unwrap();
+
+    Ok(())
+}
+
+// The name of a segment, with the repetition after an underscore
+fn svd_name(segment: &Segment) -> String {
+    match segment.element {
+        Some(element) => format!("{}_{element}", segment.field.name),
+        None => segment.field.name.clone(),
+    }
+}
+
+fn register_field(svd: &mut String, segment: &Segment, name: &str, register_offset: usize) {
+    writeln!(svd, "            <field>").unwrap();
+    writeln!(svd, "              <name>{}</name>", escape(name)).unwrap();
+    writeln!(
+        svd,
+        "              <description>{}</description>",
+        escape(&description(segment.field))
+    )
+    .unwrap();
+    writeln!(
+        svd,
+        "              <bitOffset>{}</bitOffset>",
+        segment.offset - register_offset
+    )
+    .unwrap();
+    writeln!(svd, "              <bitWidth>{}</bitWidth>", segment.width).unwrap();
+    if let FieldType::Enum(variants) = &segment.field.bit_spec.field_type {
+        writeln!(svd, "              <enumeratedValues>").unwrap();
+        for (value, variant) in variants {
+            writeln!(
+                svd,
+                "                <enumeratedValue><name>{}</name><value>{value}</value></enumeratedValue>",
+                escape(variant)
+            )
+            .unwrap();
+        }
+        writeln!(svd, "              </enumeratedValues>").unwrap();
+    }
+    writeln!(svd, "            </field>").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout;
+
+    #[test]
+    fn test_document() {
+        let element = document(
+            "<?xml version=\"1.0\"?>
+            <!-- A comment -->
+            <device a='1' b = \"x &amp; y\">
+              <name>Chip<!-- inside --></name>
+              <empty/>
+              <text><![CDATA[<raw>]]> &lt;&#65;&#x42;&gt;</text>
+            </device>",
+        )
+        .unwrap();
+
+        assert_eq!(element.name, "device");
+        assert_eq!(element.attribute("b"), Some("x & y"));
+        assert_eq!(element.text_of("name"), Some("Chip"));
+        assert!(element.child("empty").is_some());
+        assert_eq!(element.text_of("text"), Some("<raw> <AB>"));
+
+        assert_eq!(document("<a><b></a>"), Err(Error::SvdParseError));
+        assert_eq!(number("0x1F"), Ok(31));
+        assert_eq!(number("#101"), Ok(5));
+        assert_eq!(number("12"), Ok(12));
+    }
+
+    #[test]
+    fn test_parse() {
+        let svd = "<device>
+          <name>Chip</name>
+          <size>32</size>
+          <peripherals>
+            <peripheral>
+              <name>Timer</name>
+              <baseAddress>0x40000000</baseAddress>
+              <registers>
+                <register>
+                  <name>CTRL</name>
+                  <addressOffset>0x4</addressOffset>
+                  <fields>
+                    <field><name>EN</name><bitOffset>0</bitOffset><bitWidth>1</bitWidth></field>
+                    <field>
+                      <name>MODE</name>
+                      <bitRange>[2:1]</bitRange>
+                      <enumeratedValues>
+                        <enumeratedValue><name>Single</name><value>0</value></enumeratedValue>
+                        <enumeratedValue><name>Repeat</name><value>0b1</value></enumeratedValue>
+                        <enumeratedValue><name>Other</name><isDefault>true</isDefault></enumeratedValue>
+                      </enumeratedValues>
+                    </field>
+                  </fields>
+                </register>
+                <register>
+                  <dim>2</dim>
+                  <dimIncrement>2</dimIncrement>
+                  <name>CC[%s]</name>
+                  <addressOffset>0x8</addressOffset>
+                  <size>16</size>
+                </register>
+                <cluster>
+                  <name>CH</name>
+                  <addressOffset>0xC</addressOffset>
+                  <register>
+                    <name>DATA</name>
+                    <addressOffset>0x0</addressOffset>
+                    <fields>
+                      <field><name>VALUE</name><lsb>4</lsb><msb>19</msb></field>
+                    </fields>
+                  </register>
+                </cluster>
+              </registers>
+            </peripheral>
+            <peripheral derivedFrom=\"Timer\">
+              <name>Timer2</name>
+              <baseAddress>0x40001000</baseAddress>
+            </peripheral>
+          </peripherals>
+        </device>";
+
+        let layouts = parse(svd).unwrap();
+        let expected = layout::parse(
+            "layout Timer word_bits=16 {
+                CTRL_EN: 2[0]
+                CTRL_MODE: 2[1..2]:{0=Single,1=Repeat}
+                CC_0: 4[]
+                CC_1: 5[]
+                CH_DATA_VALUE: 6[4]..7[3]
+            }",
+        )
+        .unwrap();
+        assert_eq!(layouts[0], expected[0]);
+        assert_eq!(layouts[1].name, "Timer2");
+        assert_eq!(layouts[1].fields, layouts[0].fields);
+
+        assert_eq!(
+            parse("<device><peripherals><peripheral/></peripherals></device>"),
+            Err(Error::SvdParseError)
+        );
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let peripheral = |register: &str| {
+            format!(
+                "<device><peripherals><peripheral><name>P</name><registers>
+                  <register><name>R</name>{register}</register>
+                </registers></peripheral></peripherals></device>"
+            )
+        };
+
+        for register in [
+            "<addressOffset>0</addressOffset><fields><field><name>F</name>\
+             <bitOffset>18446744073709551615</bitOffset></field></fields>",
+            "<addressOffset>0</addressOffset><fields><field><name>F</name>\
+             <lsb>0</lsb><msb>18446744073709551615</msb></field></fields>",
+            "<addressOffset>0x4000000000000000</addressOffset>",
+            "<addressOffset>0x10</addressOffset><dim>2</dim>\
+             <dimIncrement>0xFFFFFFFFFFFFFFFF</dimIncrement>",
+            "<addressOffset>0</addressOffset><dim>100000</dim><dimIncrement>4</dimIncrement>",
+        ] {
+            assert_eq!(parse(&peripheral(register)), Err(Error::SvdParseError));
+        }
+
+        // Word 256 cannot be given in a bit specification
+        assert_eq!(
+            parse(&peripheral("<addressOffset>0x400</addressOffset>")),
+            Err(Error::IndexOutOfRange)
+        );
+        let layouts = parse(&peripheral("<addressOffset>0x3FC</addressOffset>")).unwrap();
+        assert_eq!(layouts[0].fields[0].bit_spec.to_string(), "255[]");
+    }
+
+    #[test]
+    fn test_render() {
+        let layouts = layout::parse(
+            "layout Frame {
+                sync: [0xA5]
+                mode: 1[0..3]:{0=Off,1=On}
+                count: 1[4..7]
+                samples: 2[]..3[];3
+            }",
+        )
+        .unwrap();
+
+        let svd = render("Device", &layouts).unwrap();
+        assert!(svd.contains(
+            "          <name>WORD1</name>
+          <addressOffset>0x1</addressOffset>
+          <size>8</size>"
+        ));
+        assert!(svd.contains(
+            "          <dim>3</dim>
+          <dimIncrement>0x2</dimIncrement>
+          <name>samples[%s]</name>
+          <addressOffset>0x2</addressOffset>
+          <size>16</size>"
+        ));
+        assert!(svd.contains("<enumeratedValue><name>On</name><value>1</value></enumeratedValue>"));
+
+        // The bits are the same when read back
+        let imported = parse(&svd).unwrap();
+        let names: Vec<String> = imported[0].fields.iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            names,
+            [
+                "sync: 0[]",
+                "WORD1_mode: 1[0..3]:{0=Off,1=On}",
+                "WORD1_count: 1[4..7]",
+                "samples_0: 2[]..3[]",
+                "samples_1: 4[]..5[]",
+                "samples_2: 6[]..7[]",
+            ]
+        );
+    }
+}
@@ -0,0 +1,430 @@
+//! Typed interpretation of the bits referred to by a [`BitSpec`].
+
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::fmt::Display;
+
+use crate::parser::{BitSpec, FieldType, Repeat};
+use crate::Error;
+
+/// A decoded value.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Unsigned(u64),
+    Bool(bool),
+    Float(f64),
+    // The name of an enumeration variant
+    #[cfg(feature = "alloc")]
+    Enum(String),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Value::Unsigned(value) => write!(f, "{value}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Float(value) => write!(f, "{value}"),
+            #[cfg(feature = "alloc")]
+            Value::Enum(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl FieldType {
+    // Check that the type can be represented by `width` bits.
+    pub(crate) fn check_width(&self, width: usize) -> Result<(), Error> {
+        let fits = match self {
+            FieldType::Bool => width == 1,
+            FieldType::F16 => width == 16,
+            FieldType::F32 => width == 32,
+            // The value is scaled by 2^fractional_bits, which must fit a u64
+            FieldType::Fixed {
+                integer_bits,
+                fractional_bits,
+            } => {
+                *fractional_bits < 64
+                    && width == (*integer_bits as usize) + (*fractional_bits as usize)
+            }
+            #[cfg(feature = "alloc")]
+            FieldType::Enum(_) => width <= 64,
+            FieldType::Unsigned => width <= 64,
+        };
+
+        if fits {
+            Ok(())
+        } else {
+            Err(Error::TypeMismatch)
+        }
+    }
+
+    /// Interpret `width` raw bits as a value of this type.
+    pub fn decode(&self, raw: u64, width: usize) -> Result<Value, Error> {
+        self.check_width(width)?;
+
+        let value = match self {
+            FieldType::Unsigned => Value::Unsigned(raw),
+            FieldType::Bool => Value::Bool(raw == 1),
+            FieldType::F16 => Value::Float(f16_to_f64(raw as u16)),
+            FieldType::F32 => Value::Float(f32::from_bits(raw as u32) as f64),
+            FieldType::Fixed {
+                fractional_bits, ..
+            } => {
+                let signed = sign_extend(raw, width);
+                Value::Float(signed as f64 / (1u64 << fractional_bits) as f64)
+            }
+            #[cfg(feature = "alloc")]
+            FieldType::Enum(variants) => variants
+                .iter()
+                .find(|(value, _)| *value == raw)
+                .map(|(_, name)| Value::Enum(name.clone()))
+                .ok_or(Error::InvalidEnumValue)?,
+        };
+
+        Ok(value)
+    }
+
+    /// The `width` raw bits that represent `value` in this type.
+    pub fn encode(&self, value: &Value, width: usize) -> Result<u64, Error> {
+        self.check_width(width)?;
+
+        let raw = match (self, value) {
+            (FieldType::Unsigned, Value::Unsigned(value)) => *value,
+            (FieldType::Bool, Value::Bool(value)) => *value as u64,
+            (FieldType::F16, Value::Float(value)) => f64_to_f16(*value)? as u64,
+            (FieldType::F32, Value::Float(value)) => {
+                let single = *value as f32;
+                if value.is_finite() && single.is_infinite() {
+                    return Err(Error::ValueOutOfRange);
+                }
+                single.to_bits() as u64
+            }
+            (
+                FieldType::Fixed {
+                    fractional_bits, ..
+                },
+                Value::Float(value),
+            ) => {
+                let scaled = round(value * (1u64 << fractional_bits) as f64);
+                let max = ((1u64 << (width - 1)) - 1) as f64;
+                let min = -((1u64 << (width - 1)) as f64);
+                if !(min..=max).contains(&scaled) {
+                    return Err(Error::ValueOutOfRange);
+                }
+                (scaled as i64 as u64) & mask(width)
+            }
+            #[cfg(feature = "alloc")]
+            (FieldType::Enum(variants), Value::Enum(name)) => variants
+                .iter()
+                .find(|(_, variant)| variant == name)
+                .map(|(value, _)| *value)
+                .ok_or(Error::InvalidEnumValue)?,
+            _ => return Err(Error::TypeMismatch),
+        };
+
+        if raw & !mask(width) != 0 {
+            return Err(Error::ValueOutOfRange);
+        }
+
+        Ok(raw)
+    }
+}
+
+impl BitSpec {
+    /// Decode all repetitions in `data` according to the field type.
+    #[cfg(feature = "alloc")]
+    pub fn decode(&self, data: &[u8], word_bits: usize) -> Result<Vec<Value>, Error> {
+        let width = self.bit_width(word_bits);
+
+        (0..self.repeats(data, word_bits)?)
+            .map(|element| {
+                let raw = self.extract(data, word_bits, element)?;
+                self.field_type.decode(raw, width)
+            })
+            .collect()
+    }
+
+    /// Encode `values`, one for each repetition, into `data`.
+    ///
+    /// For a variable repeat the number of values is also written
+    /// to the repeat word. For a conditional repeat the word is given its
+    /// value when there is a value, and changed from it when there is none.
+    pub fn encode(&self, data: &mut [u8], word_bits: usize, values: &[Value]) -> Result<(), Error> {
+        crate::access::check_word_bits(word_bits)?;
+        self.encode_repeats(data, word_bits, values.len())?;
+
+        let width = self.bit_width(word_bits);
+        for (element, value) in values.iter().enumerate() {
+            let raw = self.field_type.encode(value, width)?;
+            self.insert(data, word_bits, element, raw)?;
+        }
+
+        Ok(())
+    }
+
+    // Check the number of repetitions and write it to the repeat word. The
+    // word of a conditional repeat is given its value when there is a
+    // repetition, and changed from it when there is none.
+    pub(crate) fn encode_repeats(
+        &self,
+        data: &mut [u8],
+        word_bits: usize,
+        count: usize,
+    ) -> Result<(), Error> {
+        self.check_repeats(count)?;
+
+        let (word, value) = match &self.repeat {
+            Repeat::Variable { word, .. } => (word, count as u64),
+            Repeat::Conditional { word, value } if count == 1 => (word, *value),
+            // Flipping the lowest bit is enough for the bits to differ
+            Repeat::Conditional { word, value } if word.extract(data, word_bits)? == *value => {
+                (word, value ^ 1)
+            }
+            _ => return Ok(()),
+        };
+
+        crate::access::insert_bits(
+            data,
+            word.bit_offset(word_bits),
+            word.bit_width(word_bits),
+            value,
+        )
+    }
+}
+
+fn mask(width: usize) -> u64 {
+    if width >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << width) - 1
+    }
+}
+
+fn sign_extend(raw: u64, width: usize) -> i64 {
+    if width == 0 || width >= 64 {
+        return raw as i64;
+    }
+    let shift = 64 - width;
+    ((raw << shift) as i64) >> shift
+}
+
+// 2 to the power of `exponent`, which must give a normal number
+fn exp2(exponent: i32) -> f64 {
+    f64::from_bits(((exponent + 1023) as u64) << 52)
+}
+
+// Round half away from zero, as `f64::round` does without needing `std`
+fn round(value: f64) -> f64 {
+    let magnitude = value.abs();
+    if !magnitude.is_finite() || magnitude >= exp2(52) {
+        return value;
+    }
+    let mut rounded = magnitude as u64 as f64;
+    if magnitude - rounded >= 0.5 {
+        rounded += 1.0;
+    }
+    if value.is_sign_negative() {
+        -rounded
+    } else {
+        rounded
+    }
+}
+
+fn f16_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1F) as i32;
+    let fraction = (bits & 0x03FF) as f64;
+
+    let magnitude = match exponent {
+        0 => fraction * exp2(-24),
+        0x1F if fraction == 0.0 => f64::INFINITY,
+        0x1F => f64::NAN,
+        _ => (1.0 + fraction / 1024.0) * exp2(exponent - 15),
+    };
+
+    sign * magnitude
+}
+
+fn f64_to_f16(value: f64) -> Result<u16, Error> {
+    let sign: u16 = if value.is_sign_negative() { 0x8000 } else { 0 };
+    let magnitude = value.abs();
+
+    if value.is_nan() {
+        return Ok(0x7E00);
+    }
+    if magnitude.is_infinite() {
+        return Ok(sign | 0x7C00);
+    }
+    if magnitude < exp2(-14) {
+        // Subnormal, a fraction of 1024 rounds up to the smallest normal number
+        let fraction = round(magnitude / exp2(-24)) as u16;
+        return Ok(sign | fraction);
+    }
+
+    // The magnitude is a normal f64, so its exponent is in its bits
+    let mut exponent = ((magnitude.to_bits() >> 52) & 0x7FF) as i32 - 1023;
+    let mut fraction = round((magnitude / exp2(exponent) - 1.0) * 1024.0) as u16;
+    if fraction == 1024 {
+        fraction = 0;
+        exponent += 1;
+    }
+    if exponent > 15 {
+        return Err(Error::ValueOutOfRange);
+    }
+
+    Ok(sign | (((exponent + 15) as u16) << 10) | fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    #[cfg(not(feature = "std"))]
+    use std::prelude::rust_2021::*;
+    #[cfg(all(feature = "alloc", not(feature = "std")))]
+    use std::vec;
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_decode_bool() {
+        let bit_spec = parse("[7]:bool").unwrap();
+        assert_eq!(
+            bit_spec.decode(&[0x80], 8).unwrap(),
+            vec![Value::Bool(true)]
+        );
+        assert_eq!(
+            bit_spec.decode(&[0x7F], 8).unwrap(),
+            vec![Value::Bool(false)]
+        );
+
+        let bit_spec = parse("[6..7]:bool").unwrap();
+        assert_eq!(bit_spec.decode(&[0x80], 8), Err(Error::TypeMismatch));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_decode_float() {
+        let bit_spec = parse("[]..3[]:f32").unwrap();
+        let data = 1.5f32.to_bits().to_le_bytes();
+        assert_eq!(bit_spec.decode(&data, 8).unwrap(), vec![Value::Float(1.5)]);
+
+        let bit_spec = parse("[]:f16").unwrap();
+        assert_eq!(
+            bit_spec.decode(&[0x00, 0xC0], 16).unwrap(),
+            vec![Value::Float(-2.0)]
+        );
+        assert_eq!(
+            bit_spec.decode(&[0x55, 0x35], 16).unwrap(),
+            vec![Value::Float(0.333251953125)]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_decode_fixed() {
+        let bit_spec = parse("[]..1[]:q8.8").unwrap();
+        assert_eq!(
+            bit_spec.decode(&[0x80, 0x01], 8).unwrap(),
+            vec![Value::Float(1.5)]
+        );
+        assert_eq!(
+            bit_spec.decode(&[0x80, 0xFE], 8).unwrap(),
+            vec![Value::Float(-1.5)]
+        );
+
+        let bit_spec = parse("[]:q4.8").unwrap();
+        assert_eq!(bit_spec.decode(&[0, 0], 8), Err(Error::TypeMismatch));
+
+        // The scale of 2^64 does not fit a u64
+        let bit_spec = parse("[]..7[]:q0.64").unwrap();
+        assert_eq!(bit_spec.validate(8), Err(Error::TypeMismatch));
+        assert_eq!(bit_spec.decode(&[0; 8], 8), Err(Error::TypeMismatch));
+        let mut data = [0; 8];
+        assert_eq!(
+            bit_spec.encode(&mut data, 8, &[Value::Float(0.5)]),
+            Err(Error::TypeMismatch)
+        );
+        let bit_spec = parse("[]..7[]:q1.63").unwrap();
+        assert_eq!(
+            bit_spec.decode(&[0, 0, 0, 0, 0, 0, 0, 0x40], 8).unwrap(),
+            vec![Value::Float(0.5)]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_decode_enum() {
+        let bit_spec = parse("[0..1]:{0=Off,1=On,2=Auto}").unwrap();
+        assert_eq!(
+            bit_spec.decode(&[0x02], 8).unwrap(),
+            vec![Value::Enum("Auto".to_string())]
+        );
+        assert_eq!(bit_spec.decode(&[0x03], 8), Err(Error::InvalidEnumValue));
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_decode_repeat() {
+        let bit_spec = parse("1[];(0[])<=3").unwrap();
+        assert_eq!(
+            bit_spec.decode(&[2, 7, 9, 0], 8).unwrap(),
+            vec![Value::Unsigned(7), Value::Unsigned(9)]
+        );
+        assert_eq!(
+            bit_spec.decode(&[2, 7, 9, 0], 0),
+            Err(Error::InvalidWordSize)
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut data = [0u8; 4];
+        let bit_spec = parse("1[]..2[]:q8.8").unwrap();
+        bit_spec
+            .encode(&mut data, 8, &[Value::Float(-1.5)])
+            .unwrap();
+        assert_eq!(data, [0x00, 0x80, 0xFE, 0x00]);
+
+        assert_eq!(
+            bit_spec.encode(&mut data, 8, &[Value::Float(128.0)]),
+            Err(Error::ValueOutOfRange)
+        );
+        assert_eq!(
+            bit_spec.encode(&mut data, 8, &[Value::Bool(true)]),
+            Err(Error::TypeMismatch)
+        );
+        assert_eq!(
+            bit_spec.encode(&mut data, 0, &[Value::Float(1.0)]),
+            Err(Error::InvalidWordSize)
+        );
+
+        #[cfg(feature = "alloc")]
+        {
+            let mut data = [0u8; 4];
+            let bit_spec = parse("1[];(0[0..3])<3:{1=On,2=Off}").unwrap();
+            let values = vec![
+                Value::Enum("Off".to_string()),
+                Value::Enum("On".to_string()),
+            ];
+            bit_spec.encode(&mut data, 8, &values).unwrap();
+            assert_eq!(data, [2, 2, 1, 0]);
+            assert_eq!(bit_spec.decode(&data, 8).unwrap(), values);
+        }
+    }
+
+    #[test]
+    fn test_round() {
+        for (value, rounded) in [(0.5, 1.0), (-2.5, -3.0), (1.49, 1.0), (-0.2, -0.0)] {
+            assert_eq!(round(value), rounded);
+        }
+        assert_eq!(round(1e300), 1e300);
+    }
+
+    #[test]
+    fn test_f16_round_trip() {
+        for value in [0.0, 1.0, -2.5, 65504.0, 0.000061035156, 5.9604645e-8] {
+            let bits = f64_to_f16(value).unwrap();
+            assert_eq!(f16_to_f64(bits) as f32, value as f32);
+        }
+        assert_eq!(f64_to_f16(70000.0), Err(Error::ValueOutOfRange));
+    }
+}